@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use obj_to_pathfinding_grid::geometry::Triangle;
+use obj_to_pathfinding_grid::testutil::{generate_grid_plane, generate_sphere, NoOpProgress};
+use obj_to_pathfinding_grid::{
+    bounding_box, convert, create_grid, dedup_obstacles, voxelize, ConvertOptions,
+    NoOpPreprocessor,
+};
+
+fn bench_convert(c: &mut Criterion, name: &str, triangles: fn() -> Vec<Triangle>) {
+    let bounding_box = bounding_box(&triangles());
+    let center = bounding_box.center();
+    let width = bounding_box.width() as u32;
+    let height = bounding_box.height().max(1.0) as u32;
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            convert(
+                triangles(),
+                center,
+                width,
+                height,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+            )
+        })
+    });
+}
+
+/// Grid population used before the shared bitset: obstacles of all threads are collected,
+/// deduplicated and set one by one.
+fn bench_obstacle_list(c: &mut Criterion, name: &str, triangles: fn() -> Vec<Triangle>) {
+    let bounding_box = bounding_box(&triangles());
+    let center = bounding_box.center();
+    let width = bounding_box.width() as u32;
+    let height = bounding_box.height().max(1.0) as u32;
+
+    c.bench_function(name, |b| {
+        b.iter(|| {
+            let obstacles = voxelize(
+                triangles(),
+                center,
+                width,
+                height,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &ConvertOptions::default(),
+            )
+            .unwrap();
+
+            create_grid(&dedup_obstacles(obstacles), width, height)
+        })
+    });
+}
+
+fn convert_sphere(c: &mut Criterion) {
+    bench_convert(c, "convert sphere", || generate_sphere(50.0, 4));
+}
+
+fn convert_plane(c: &mut Criterion) {
+    bench_convert(c, "convert plane", || generate_grid_plane(200));
+}
+
+fn obstacle_list_sphere(c: &mut Criterion) {
+    bench_obstacle_list(c, "obstacle list sphere", || generate_sphere(50.0, 4));
+}
+
+criterion_group!(benches, convert_sphere, convert_plane, obstacle_list_sphere);
+criterion_main!(benches);
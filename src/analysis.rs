@@ -0,0 +1,97 @@
+use crate::geometry::{BoundingBox, LocalVector};
+use crate::occupancy::OccupancyGrid;
+use nalgebra::Vector3;
+
+/// Returns count of obstacles in every Z layer, obstacles outside of `[0, height)` are ignored.
+pub fn layer_histogram(obstacles: &[LocalVector], height: u32) -> Vec<usize> {
+    let mut histogram = vec![0; height as usize];
+
+    for obstacle in obstacles {
+        if let Some(count) = histogram.get_mut(obstacle.z as usize) {
+            *count += 1;
+        }
+    }
+
+    histogram
+}
+
+/// Checks that world points, like spawn points of a level, are free cells of the grid converted
+/// with given center, width and height. Returns points which landed on obstacles or outside of
+/// the grid, in order they were given.
+pub fn assert_walkable(
+    grid: &OccupancyGrid,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    points: &[Vector3<f32>],
+) -> Result<(), Vec<Vector3<f32>>> {
+    let grid_bounding_box = BoundingBox::from_grid(&center, width, height);
+
+    let blocked: Vec<Vector3<f32>> = points
+        .iter()
+        .filter(|point| {
+            let cell = LocalVector::from_world_vector(point, &center, width, height);
+
+            !grid_bounding_box.contains(point) || grid.is_obstacle(cell.x, cell.y, cell.z)
+        })
+        .copied()
+        .collect();
+
+    if blocked.is_empty() {
+        Ok(())
+    } else {
+        Err(blocked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{assert_walkable, layer_histogram};
+    use crate::geometry::{LocalVector, Triangle};
+    use crate::occupancy::OccupancyGrid;
+    use crate::voxelize_triangle;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_layer_histogram() {
+        let obstacles = vec![
+            LocalVector::new(0, 0, 0),
+            LocalVector::new(1, 0, 0),
+            LocalVector::new(0, 1, 2),
+            LocalVector::new(5, 5, 3),
+            LocalVector::new(3, 2, 3),
+            LocalVector::new(3, 3, 3),
+            LocalVector::new(0, 0, 4),
+            LocalVector::new(0, 0, 10),
+        ];
+
+        assert_eq!(layer_histogram(&obstacles, 4), vec![2, 0, 1, 3]);
+    }
+
+    #[test]
+    fn test_assert_walkable() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let mut grid = OccupancyGrid::new(10, 10);
+
+        // Wall in the plane x = 2.
+        let wall = Triangle::new(
+            Vector3::new(2.0, -5.0, -5.0),
+            Vector3::new(2.0, 5.0, -5.0),
+            Vector3::new(2.0, 0.0, 5.0),
+        );
+
+        voxelize_triangle(&wall, &center, 10, 10, 1.0, &mut |o| {
+            grid.set_obstacle(o.x, o.y, o.z)
+        });
+
+        let free = Vector3::new(-2.0, 0.3, 0.0);
+        let inside_wall = Vector3::new(2.2, 0.0, -1.0);
+        let outside = Vector3::new(40.0, 0.0, 0.0);
+
+        assert_eq!(assert_walkable(&grid, center, 10, 10, &[free]), Ok(()));
+        assert_eq!(
+            assert_walkable(&grid, center, 10, 10, &[inside_wall, free, outside]),
+            Err(vec![inside_wall, outside])
+        );
+    }
+}
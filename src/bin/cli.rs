@@ -1,28 +1,280 @@
-use nalgebra::Vector3;
+use nalgebra::{Matrix4, Point3, Vector3};
 use obj::Obj;
 use obj_to_pathfinding_grid;
-use obj_to_pathfinding_grid::geometry::Triangle;
-use obj_to_pathfinding_grid::parse_triangles;
-use obj_to_pathfinding_grid::{bounding_box, NoOpPreprocessor, Progress};
+use obj_to_pathfinding_grid::analysis::layer_histogram;
+use obj_to_pathfinding_grid::connectivity::{
+    keep_largest_free_region, remove_isolated, Connectivity,
+};
+use obj_to_pathfinding_grid::distance::apply_clearance;
+use obj_to_pathfinding_grid::error::{ConvertError, GridError};
+use obj_to_pathfinding_grid::estimate::{
+    cells_for_extent, dimensions_for_cell_size, estimate, fit_max_cells, minimal_grid,
+};
+use obj_to_pathfinding_grid::export::{
+    load_any_grid, load_grid, load_points, metadata_path, save_distance_field, save_grid,
+    save_grid_with_layout, save_metadata, save_npy, write_cells, write_csv, write_json, write_obj,
+    CellsFormat, GridMetadata, Layout,
+};
+use obj_to_pathfinding_grid::geometry::{
+    center_from_origin, parse_vector, Axis, BoundingBox, LocalVector, MeshTransform, Triangle,
+};
+use obj_to_pathfinding_grid::merge::merge_coplanar;
+use obj_to_pathfinding_grid::occupancy::OccupancyGrid;
+use obj_to_pathfinding_grid::preprocessors::{
+    ClipPreprocessor, QuantizePreprocessor, SnapToPlanePreprocessor, SubdividePreprocessor,
+    ZBandPreprocessor, MAX_QUANTIZE_DECIMALS,
+};
+use obj_to_pathfinding_grid::render::{contact_sheet, save_png, CONTACT_SHEET_MAX_PIXELS};
+use obj_to_pathfinding_grid::stats::{
+    timed, ConversionStats, EmptyTriangleCounter, EmptyTriangles, Timings,
+};
+use obj_to_pathfinding_grid::surface::{ledge_cells, walkable_surface};
+use obj_to_pathfinding_grid::{
+    add_extra_obstacles, bounding_box, create_grid_checked, dedup_obstacles, empty_triangles,
+    export_grid, load_obj_checked, malformed_faces, parse_triangles_placed, read_obj_checked,
+    triangles_in_region, voxelize, voxelize_with_stats, ConvertOptions, NoOpPreprocessor,
+    NonFinite, OutOfBounds, Placement, Preprocessor, Progress, ProgressWeight, VoxelMode,
+    WallThickness,
+};
+use std::cell::Cell;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process;
+use std::slice;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
 const DEFAULT_OUTPUT_FOLDER: &str = "grid";
+const LAYER_BAR_WIDTH: usize = 50;
+
+/// Input path which reads obj file from stdin.
+const STDIN_INPUT: &str = "-";
+/// Name of default output when input is read from stdin.
+const STDIN_OUTPUT_NAME: &str = "stdin";
+
+/// Shortest time between printed progress updates, about 30 per second.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Arguments which are handled by the top level command instead of a subcommand.
+const TOP_LEVEL_ARGS: [&str; 10] = [
+    "convert",
+    "inspect",
+    "diff",
+    "crop",
+    "batch",
+    "help",
+    "-h",
+    "--help",
+    "-V",
+    "--version",
+];
 
 #[derive(StructOpt, Debug)]
-#[structopt(name = "obj-to-pathfinding-grid")]
-struct Opt {
-    #[structopt(short, long, parse(from_os_str))]
-    input: PathBuf,
-    #[structopt(short, long, parse(from_os_str))]
-    output: Option<PathBuf>,
+#[structopt(
+    name = "obj-to-pathfinding-grid",
+    after_help = "If no subcommand is given, arguments are passed to convert."
+)]
+enum Opt {
+    /// Convert obj file to pathfinding grid, default subcommand.
+    Convert(Box<ConvertOpt>),
+    /// Print mesh bounding box, triangle count and grid diagnostics without converting.
+    Inspect(InspectOpt),
+    /// Compare obstacles of two obj files converted into the same grid.
+    Diff(DiffOpt),
+    /// Cut part of a grid saved with `--occupancy-output`.
+    Crop(CropOpt),
+    /// Convert every obj file of a folder with the same arguments.
+    Batch(BatchOpt),
+}
+
+/// Transformations applied to the mesh before conversion.
+#[derive(StructOpt, Debug)]
+struct MeshOpt {
+    /// Scale mesh by factor about `--scale-pivot`, so the mesh stays in place.
+    #[structopt(short, long, parse(try_from_str = parse_scale))]
+    scale: Option<f32>,
+    /// Point kept in place by `--scale` as x,y,z, centroid of the mesh by default.
+    /// Earlier versions scaled about the origin, pass 0,0,0 to keep that behavior.
+    #[structopt(long, parse(try_from_str = parse_vector))]
+    scale_pivot: Option<Vector3<f32>>,
+    /// Mirror mesh across YZ plane, applied after scaling.
+    #[structopt(long)]
+    flip_x: bool,
+    /// Mirror mesh across XZ plane, applied after scaling.
+    #[structopt(long)]
+    flip_y: bool,
+    /// Mirror mesh across XY plane, applied after scaling.
+    #[structopt(long)]
+    flip_z: bool,
+    /// Convert only objects or groups with given name, can be repeated.
+    /// All objects are converted if not set.
+    #[structopt(long = "object")]
+    objects: Vec<String>,
+    /// Move object or group by offset given as name=x,y,z before conversion, can be repeated.
+    #[structopt(long = "place")]
+    placements: Vec<Placement>,
+    /// Handling of triangles with NaN or infinite coordinates: skip or error.
+    #[structopt(long, default_value = "skip")]
+    non_finite: NonFinite,
+    /// Rotate mesh counter-clockwise around z axis by degrees, applied after scaling.
+    #[structopt(long)]
+    rotate_z: Option<f32>,
+    /// Move mesh by x,y,z after all other transforms.
+    #[structopt(long, parse(try_from_str = parse_vector))]
+    translate: Option<Vector3<f32>>,
+    /// Cut off parts of the mesh below world z before any transform, like lower floors.
+    #[structopt(long)]
+    clip_below_z: Option<f32>,
+    /// Cut off parts of the mesh above world z before any transform, like upper floors.
+    #[structopt(long)]
+    clip_above_z: Option<f32>,
+}
+
+/// Point used as grid center.
+#[derive(Debug, PartialEq)]
+enum Center {
+    Origin,
+    BoundingBox,
+    BoundingBoxMin,
+    BoundingBoxMax,
+    /// Average of all triangle vertices.
+    Centroid,
+    Point(Vector3<f32>),
+}
+
+impl Center {
+    fn resolve(&self, triangles: &[Triangle], bounding_box: &BoundingBox) -> Vector3<f32> {
+        match self {
+            Center::Origin => Vector3::new(0.0, 0.0, 0.0),
+            Center::BoundingBox => bounding_box.center(),
+            Center::BoundingBoxMin => bounding_box.min,
+            Center::BoundingBoxMax => bounding_box.max,
+            Center::Centroid => centroid(triangles),
+            Center::Point(point) => *point,
+        }
+    }
+}
+
+/// Average of triangle centroids, origin if there are no triangles.
+fn centroid(triangles: &[Triangle]) -> Vector3<f32> {
+    if triangles.is_empty() {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    let sum: Vector3<f32> = triangles.iter().map(|t| t.centroid()).sum();
+    sum / triangles.len() as f32
+}
+
+impl FromStr for Center {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "origin" => Ok(Center::Origin),
+            "bbox-center" => Ok(Center::BoundingBox),
+            "bbox-min" => Ok(Center::BoundingBoxMin),
+            "bbox-max" => Ok(Center::BoundingBoxMax),
+            "centroid" => Ok(Center::Centroid),
+            _ => parse_vector(s).map(Center::Point),
+        }
+    }
+}
+
+/// Parses grid cell given as `x,y,z`.
+fn parse_local_vector(s: &str) -> Result<LocalVector, String> {
+    let cell = parse_vector::<u32>(s)?;
+
+    Ok(LocalVector::new(cell.x, cell.y, cell.z))
+}
+
+/// Parses `--scale` factor, zero would collapse the mesh and can't be undone in metadata.
+fn parse_scale(s: &str) -> Result<f32, String> {
+    let scale: f32 = s
+        .parse()
+        .map_err(|e| format!("Invalid scale {}: {}", s, e))?;
+
+    if !scale.is_finite() || scale == 0.0 {
+        return Err(format!("Scale must be finite and non-zero: {}", s));
+    }
+
+    Ok(scale)
+}
+
+/// Parses `--subdivide` length, which must be positive to ever be reached.
+fn parse_edge_length(s: &str) -> Result<f32, String> {
+    let length: f32 = s
+        .parse()
+        .map_err(|e| format!("Invalid edge length {}: {}", s, e))?;
+
+    if !length.is_finite() || length <= 0.0 {
+        return Err(format!("Edge length must be positive: {}", s));
+    }
+
+    Ok(length)
+}
+
+/// Parses `--quantize` decimals, more than `MAX_QUANTIZE_DECIMALS` can't be represented.
+fn parse_decimals(s: &str) -> Result<u32, String> {
+    let decimals: u32 = s
+        .parse()
+        .map_err(|e| format!("Invalid decimals {}: {}", s, e))?;
+
+    if decimals > MAX_QUANTIZE_DECIMALS {
+        return Err(format!(
+            "At most {} decimals are supported: {}",
+            MAX_QUANTIZE_DECIMALS, s
+        ));
+    }
+
+    Ok(decimals)
+}
+
+/// Grid placement, mesh bounding box is used for missing values.
+#[derive(StructOpt, Debug)]
+struct GridOpt {
     #[structopt(short, long)]
     width: Option<u32>,
     #[structopt(short, long)]
     height: Option<u32>,
-    #[structopt(short, long)]
-    scale: Option<f32>,
+    /// Grid center: origin, bbox-center, bbox-min, bbox-max, centroid or x,y,z.
+    /// Center components passed separately take precedence.
+    #[structopt(long, default_value = "bbox-center")]
+    center: Center,
+    /// Resolve `--center` against triangles of object or group with given name only.
+    /// The whole mesh is still converted.
+    #[structopt(long)]
+    center_on: Option<String>,
+    /// World position x,y,z of the first grid cell, replaces grid center.
+    #[structopt(long, parse(try_from_str = parse_vector))]
+    origin: Option<Vector3<f32>>,
+    /// Round position of the first grid cell down to a multiple of given tile size in world
+    /// units, so grids of adjacent tiles line up. Tile size should be a multiple of cell size.
+    /// Grid isn't enlarged, so geometry near its upper borders may need a bigger size.
+    #[structopt(long)]
+    snap_origin: Option<f32>,
+    /// Pick cell size, so grid covering the mesh has at most given count of cells.
+    #[structopt(long)]
+    max_cells: Option<u64>,
+    /// Size of a single cell in world units.
+    #[structopt(long, conflicts_with = "max-cells")]
+    cell_size: Option<f32>,
+    /// Grid width in world units, converted to cells of `--cell-size`.
+    #[structopt(long, conflicts_with = "width")]
+    world_width: Option<f32>,
+    /// Grid height in world units, converted to cells of `--cell-size`.
+    #[structopt(long, conflicts_with = "height")]
+    world_height: Option<f32>,
+    /// Cells used for width or height along which the mesh has no extent, like a flat floor.
+    #[structopt(long, default_value = "1")]
+    flat_cells: u32,
     #[structopt(short = "x", long)]
     center_x: Option<f32>,
     #[structopt(short = "y", long)]
@@ -31,75 +283,2377 @@ struct Opt {
     center_z: Option<f32>,
 }
 
+#[derive(StructOpt, Debug)]
+struct ConvertOpt {
+    /// Obj file, `-` reads it from stdin.
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+    #[structopt(short, long, parse(from_os_str))]
+    output: Option<PathBuf>,
+    #[structopt(flatten)]
+    mesh: MeshOpt,
+    #[structopt(flatten)]
+    grid: GridOpt,
+    /// Clip triangles to the grid instead of clamping parts outside of it to the borders.
+    #[structopt(long)]
+    clip: bool,
+    /// Convert only triangles in world space box from x,y,z, grid is sized and centered to it
+    /// instead of the mesh and triangles are clipped.
+    #[structopt(long, requires = "region-max", parse(try_from_str = parse_vector))]
+    region_min: Option<Vector3<f32>>,
+    /// Opposite corner of `--region-min`.
+    #[structopt(long, requires = "region-min", parse(try_from_str = parse_vector))]
+    region_max: Option<Vector3<f32>>,
+    /// Split triangles until none of their edges is longer than given length.
+    #[structopt(long, parse(try_from_str = parse_edge_length))]
+    subdivide: Option<f32>,
+    /// Round vertex coordinates to given count of decimal places, so float noise doesn't
+    /// change the grid. At most 9 decimals.
+    #[structopt(long, parse(try_from_str = parse_decimals))]
+    quantize: Option<u32>,
+    /// Snap triangles nearly perpendicular to given axis (x, y or z) to a single layer.
+    #[structopt(long)]
+    snap_axis: Option<Axis>,
+    /// Largest angle in degrees between triangle normal and `--snap-axis` to snap it.
+    #[structopt(long, default_value = "5")]
+    snap_angle: f32,
+    /// Merge adjacent triangles whose normals differ by at most given angle in degrees
+    /// into less triangles before conversion.
+    #[structopt(long)]
+    merge_coplanar: Option<f32>,
+    /// Handling of triangles outside of the grid: clamp, drop, error or strict, which clamps
+    /// and fails after conversion with count of clamped triangles.
+    #[structopt(long, default_value = "clamp")]
+    out_of_bounds: OutOfBounds,
+    /// Same as `--out-of-bounds strict`.
+    #[structopt(long)]
+    strict_bounds: bool,
+    /// Obstacle test: precise or center-sample, which is faster but can leave holes.
+    #[structopt(long, default_value = "precise")]
+    mode: VoxelMode,
+    /// Cells added on both sides of surfaces, along axis closest to their normal.
+    #[structopt(long, default_value = "0")]
+    wall_thickness: u32,
+    /// Progress by triangle count or by cells tested, which follows time for uneven meshes.
+    #[structopt(long, default_value = "count")]
+    progress_weight: ProgressWeight,
+    /// Mark at least the centroid cell of every triangle.
+    #[structopt(long)]
+    ensure_nonempty: bool,
+    /// Cells added around the range scanned for obstacles of every triangle, catches cells
+    /// grazed with large epsilon at a cost of speed.
+    #[structopt(long, default_value = "0")]
+    scan_padding: u32,
+    /// Fail instead of converting into a grid with more cells, width * width * height.
+    /// Unlike `--max-cells` it doesn't change the cell size.
+    #[structopt(long)]
+    max_grid_cells: Option<u64>,
+    /// Tolerance of the triangle-box overlap test, larger values mark more cells.
+    #[structopt(long)]
+    epsilon: Option<f32>,
+    /// Count of threads used for conversion, all cores are used if not set or zero.
+    #[structopt(long)]
+    threads: Option<usize>,
+    /// Also write obstacles in world coordinates, as JSON if path ends with `.json`,
+    /// as obj point cloud if it ends with `.obj` or CSV otherwise.
+    #[structopt(long, parse(from_os_str))]
+    world_output: Option<PathBuf>,
+    /// Also write grid cells in local coordinates, see `--format`.
+    #[structopt(long, parse(from_os_str))]
+    cells_output: Option<PathBuf>,
+    /// Cells listed by `--cells-output`: obstacles, free or auto to pick the smaller set.
+    #[structopt(long, default_value = "obstacles")]
+    format: CellsFormat,
+    /// Mark cells listed as `x,y,z` rows in given file, like invisible walls missing in the mesh.
+    /// Points outside of the grid are clamped, skipped or rejected as set by `--out-of-bounds`.
+    #[structopt(long, parse(from_os_str))]
+    extra_obstacles: Option<PathBuf>,
+    /// Points of `--extra-obstacles` are world coordinates, same as `--world-output`, instead
+    /// of local cells.
+    #[structopt(long, requires = "extra-obstacles")]
+    extra_world: bool,
+    /// Start from grid saved with `--occupancy-output`, its size is used unless set explicitly.
+    #[structopt(long, parse(from_os_str))]
+    seed: Option<PathBuf>,
+    /// Also save grid in occupancy format, which can be used as `--seed`.
+    #[structopt(long, parse(from_os_str))]
+    occupancy_output: Option<PathBuf>,
+    /// Order of cells in `--occupancy-output`: x-major or z-major.
+    #[structopt(long, default_value = "x-major")]
+    layout: Layout,
+    /// Save distance from every cell to the nearest obstacle as little endian floats.
+    #[structopt(long, parse(from_os_str))]
+    distance_output: Option<PathBuf>,
+    /// Save grid as numpy `.npy` boolean array indexed by x, y and z.
+    #[structopt(long, parse(from_os_str))]
+    npy_output: Option<PathBuf>,
+    /// Save PNG with all layers of the grid side by side, downscaled if it gets too big.
+    #[structopt(long, parse(from_os_str))]
+    contact_sheet: Option<PathBuf>,
+    /// Mark free cells as obstacles and obstacles as free cells, makes sense for solid meshes.
+    #[structopt(long)]
+    invert: bool,
+    /// Remove obstacles with less than given count of obstacles among their 26 neighbours.
+    #[structopt(long)]
+    remove_isolated: Option<usize>,
+    /// Fill free space outside of the largest free region, like pockets agents can't reach.
+    #[structopt(long)]
+    keep_largest_free: bool,
+    /// Connectivity of `--keep-largest-free` regions: six, through faces only, or twenty-six,
+    /// also through edges and corners. Agents can't pass between cells touching at an edge.
+    #[structopt(long, default_value = "six")]
+    free_connectivity: Connectivity,
+    /// Block free cells closer than given radius in world units to obstacles, so agents can
+    /// be treated as points.
+    #[structopt(long)]
+    agent_radius: Option<f32>,
+    /// Count triangles which didn't mark any cell by reason, after preprocessing.
+    #[structopt(long)]
+    count_empty: bool,
+    /// Count cells tested against triangles, to see how much of voxelization is wasted on
+    /// cells of bounding boxes which triangles don't touch.
+    #[structopt(long)]
+    count_inside_tests: bool,
+    /// Mark only walkable floor: the highest cell of every column among triangles facing up.
+    /// Walls and ceilings aren't marked.
+    #[structopt(long)]
+    walkable_surface: bool,
+    /// Steepest walkable slope in degrees away from +Z for `--walkable-surface`, any triangle
+    /// facing up is walkable if not set.
+    #[structopt(long, requires = "walkable-surface")]
+    max_slope: Option<f32>,
+    /// Highest step in cells agents can climb or descend between adjacent floor columns of
+    /// `--walkable-surface`, floor next to a higher difference is a ledge.
+    #[structopt(long, requires = "walkable-surface")]
+    step_height: Option<u32>,
+    /// Write ledge cells of `--step-height` as CSV in local coordinates.
+    #[structopt(long, parse(from_os_str), requires = "step-height")]
+    ledges_output: Option<PathBuf>,
+    /// Compare triangle-box overlap test against slow reference on some triangles of the mesh
+    /// and print disagreements without converting.
+    #[structopt(long, hidden = true)]
+    self_test: bool,
+    /// Print grid size and memory estimate without converting.
+    #[structopt(long)]
+    estimate: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct InspectOpt {
+    /// Obj file, `-` reads it from stdin.
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+    #[structopt(flatten)]
+    mesh: MeshOpt,
+    #[structopt(flatten)]
+    grid: GridOpt,
+    /// Convert mesh and print count of obstacles in every Z layer.
+    #[structopt(long)]
+    layers: bool,
+    /// Count triangles which don't mark any cell by reason.
+    #[structopt(long)]
+    count_empty: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct DiffOpt {
+    /// Grid saved with `--occupancy-output` or `--cells-output`. Obj file is converted to
+    /// a grid first, `-` reads it from stdin.
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+    /// Grid or obj file compared against input, of the same kind as input.
+    #[structopt(long, parse(from_os_str))]
+    other: PathBuf,
+    /// Mesh options apply only to obj files.
+    #[structopt(flatten)]
+    mesh: MeshOpt,
+    /// Grid covers both meshes if its placement isn't set.
+    #[structopt(flatten)]
+    grid: GridOpt,
+    /// Tolerance of the triangle-box overlap test, larger values mark more cells.
+    #[structopt(long)]
+    epsilon: Option<f32>,
+}
+
+#[derive(StructOpt, Debug)]
+struct CropOpt {
+    /// Grid saved with `--occupancy-output`.
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+    /// First cell x,y,z of the region.
+    #[structopt(long, parse(try_from_str = parse_local_vector))]
+    min: LocalVector,
+    /// Last cell x,y,z of the region, region must be square on x and y axes.
+    #[structopt(long, parse(try_from_str = parse_local_vector))]
+    max: LocalVector,
+}
+
+#[derive(StructOpt, Debug)]
+struct BatchOpt {
+    /// Folder with obj files, files ending with `.obj` or `.obj.gz` are converted.
+    #[structopt(long, parse(from_os_str))]
+    input_dir: PathBuf,
+    /// Folder for grids, which are named after obj files like in convert.
+    #[structopt(short, long, parse(from_os_str), default_value = DEFAULT_OUTPUT_FOLDER)]
+    output_dir: PathBuf,
+    /// Arguments of convert used for every file, given after `--`. Grid size is fitted to
+    /// every mesh unless it is set.
+    #[structopt(last = true)]
+    args: Vec<String>,
+}
+
+/// Error which stops a command, or conversion of a single file in a batch.
+#[derive(Debug)]
+enum CliError {
+    /// File given by an argument couldn't be read or isn't valid.
+    Read {
+        path: PathBuf,
+        error: Box<dyn Error>,
+    },
+    /// Output file couldn't be written.
+    Write {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// Object given by `--center-on` isn't in the input file.
+    ObjectNotFound(String),
+    Grid(GridError),
+    Convert(ConvertError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Read { path, error } => {
+                write!(f, "Failed to read {}: {}", path.display(), error)
+            }
+            CliError::Write { path, error } => {
+                write!(f, "Failed to write {}: {}", path.display(), error)
+            }
+            CliError::ObjectNotFound(name) => write!(f, "Object {} not found", name),
+            CliError::Grid(error) => error.fmt(f),
+            CliError::Convert(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<GridError> for CliError {
+    fn from(error: GridError) -> Self {
+        CliError::Grid(error)
+    }
+}
+
+impl From<ConvertError> for CliError {
+    fn from(error: ConvertError) -> Self {
+        CliError::Convert(error)
+    }
+}
+
+fn read_error<E: Into<Box<dyn Error>>>(path: &Path) -> impl FnOnce(E) -> CliError {
+    let path = path.to_path_buf();
+    move |error| CliError::Read {
+        path,
+        error: error.into(),
+    }
+}
+
+fn write_error(path: &Path) -> impl FnOnce(io::Error) -> CliError {
+    let path = path.to_path_buf();
+    move |error| CliError::Write { path, error }
+}
+
+/// Creates file at `path` and fills it with `write`.
+fn write_file<F>(path: &Path, write: F) -> Result<(), CliError>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    File::create(path)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()
+        })
+        .map_err(write_error(path))
+}
+
 fn main() {
-    let opt: Opt = Opt::from_args();
+    let args = with_default_subcommand(std::env::args_os().collect());
 
-    let input = &opt.input;
+    let result = match Opt::from_iter(args) {
+        Opt::Convert(opt) => convert(&opt),
+        Opt::Inspect(opt) => inspect(&opt),
+        Opt::Diff(opt) => diff(&opt),
+        Opt::Crop(opt) => crop(&opt),
+        Opt::Batch(opt) => batch(&opt).map(|failed| {
+            if failed > 0 {
+                process::exit(1);
+            }
+        }),
+    };
+
+    if let Err(error) = result {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
+/// Inserts `convert` subcommand if arguments don't start with any subcommand.
+fn with_default_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    let has_subcommand = args
+        .get(1)
+        .map_or(false, |arg| TOP_LEVEL_ARGS.iter().any(|a| arg == a));
+
+    if !has_subcommand {
+        args.insert(1.min(args.len()), OsString::from("convert"));
+    }
+
+    args
+}
+
+/// Transformed triangles of the mesh and the transform, which is reused for other meshes
+/// converted into the same grid.
+fn load_triangles(obj: &Obj, opt: &MeshOpt) -> Result<(Vec<Triangle>, MeshTransform), CliError> {
+    let triangles = load_mesh_triangles(obj, opt)?;
+    let transform = mesh_transform(opt, &triangles);
+
+    Ok((transform_triangles(triangles, &transform), transform))
+}
+
+/// Triangles of the object given by `--center-on`, transformed the same way as the mesh.
+fn load_center_triangles(
+    obj: &Obj,
+    mesh: &MeshOpt,
+    grid: &GridOpt,
+    transform: &MeshTransform,
+) -> Result<Option<Vec<Triangle>>, CliError> {
+    let name = match grid.center_on.as_ref() {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    let triangles = load_triangles_for(obj, mesh, slice::from_ref(name))?;
+    let triangles = transform_triangles(triangles, transform);
+
+    if triangles.is_empty() {
+        return Err(CliError::ObjectNotFound(name.clone()));
+    }
+
+    Ok(Some(triangles))
+}
+
+/// Loads input obj file, `-` reads it from stdin. Stdin can be read only once, so the input
+/// is loaded once and shared by everything parsed from it.
+fn load_input(input: &Path) -> Result<Obj, CliError> {
+    if input == Path::new(STDIN_INPUT) {
+        return read_input(io::stdin().lock());
+    }
+
+    let (obj, primitives) = load_obj_checked(input).map_err(read_error(input))?;
+    warn_primitives(primitives);
+
+    Ok(obj)
+}
+
+fn read_input<R: Read>(reader: R) -> Result<Obj, CliError> {
+    let (obj, primitives) = read_obj_checked(reader).map_err(read_error(Path::new(STDIN_INPUT)))?;
+    warn_primitives(primitives);
+
+    Ok(obj)
+}
+
+fn warn_primitives(primitives: usize) {
+    if primitives > 0 {
+        println!("Warning: skipped {} line and point elements", primitives);
+    }
+}
+
+/// Untransformed triangles of the converted objects, clipped to `--clip-below-z` and
+/// `--clip-above-z`.
+fn load_mesh_triangles(obj: &Obj, opt: &MeshOpt) -> Result<Vec<Triangle>, CliError> {
+    let triangles = load_triangles_for(obj, opt, &opt.objects)?;
 
-    let obj = Obj::load(input).expect("Failed to load input file");
-    let scale = opt.scale.unwrap_or(1.0);
+    if opt.clip_below_z.is_none() && opt.clip_above_z.is_none() {
+        return Ok(triangles);
+    }
+
+    let band = ZBandPreprocessor::new(opt.clip_below_z, opt.clip_above_z);
+
+    Ok(triangles
+        .into_iter()
+        .flat_map(|t| band.pre_process(t, 0, 0, Vector3::zeros()))
+        .collect())
+}
+
+fn load_triangles_for(
+    obj: &Obj,
+    opt: &MeshOpt,
+    objects: &[String],
+) -> Result<Vec<Triangle>, CliError> {
+    let (triangles, skipped) =
+        parse_triangles_placed(obj, objects, &opt.placements, opt.non_finite)?;
+
+    if skipped > 0 {
+        println!(
+            "Warning: skipped {} triangles with non-finite coordinates",
+            skipped
+        );
+    }
+
+    let malformed = malformed_faces(obj).len();
+
+    if malformed > 0 {
+        println!(
+            "Warning: skipped {} faces referencing missing vertices",
+            malformed
+        );
+    }
+
+    Ok(triangles)
+}
+
+/// Transform given by options, scale pivot defaults to centroid of untransformed triangles.
+fn mesh_transform(opt: &MeshOpt, triangles: &[Triangle]) -> MeshTransform {
+    let pivot = match (opt.scale, opt.scale_pivot) {
+        (Some(_), Some(pivot)) => pivot,
+        (Some(_), None) => centroid(triangles),
+        (None, _) => Vector3::zeros(),
+    };
+
+    MeshTransform {
+        scale: opt.scale.unwrap_or(1.0),
+        pivot,
+        rotate_z: opt.rotate_z.unwrap_or(0.0).to_radians(),
+        mirror: [opt.flip_x, opt.flip_y, opt.flip_z],
+        translate: opt.translate.unwrap_or_else(Vector3::zeros),
+    }
+}
+
+/// Scales, rotates, mirrors and translates triangles in this order. Grid size and center are
+/// measured on the result, so they cover the geometry which is voxelized.
+fn transform_triangles(triangles: Vec<Triangle>, transform: &MeshTransform) -> Vec<Triangle> {
+    if *transform == MeshTransform::default() {
+        return triangles;
+    }
 
-    let triangles: Vec<Triangle> = parse_triangles(&obj)
+    let matrix = transform.matrix();
+
+    triangles
         .into_iter()
-        .map(|t| t.scale(scale))
+        .map(|t| t.transform(&matrix))
+        .collect()
+}
+
+/// Matrix undoing transforms of `transform_triangles`.
+fn inverse_matrix(transform: &MeshTransform) -> Matrix4<f32> {
+    transform
+        .matrix()
+        .try_inverse()
+        .expect("Mesh scale is non-zero, see `parse_scale`")
+}
+
+/// Bounding box of transformed triangles in coordinates of the input mesh.
+fn input_bounds(triangles: &[Triangle], transform: &MeshTransform) -> BoundingBox {
+    let inverse = inverse_matrix(transform);
+    let untransform = |p: &Vector3<f32>| inverse.transform_point(&Point3::from(*p)).coords;
+
+    let input_triangles: Vec<Triangle> = triangles
+        .iter()
+        .map(|t| Triangle::new(untransform(&t.a), untransform(&t.b), untransform(&t.c)))
         .collect();
 
-    let bounding_box = bounding_box(&triangles);
-    let bounding_box_center = bounding_box.center();
+    bounding_box(&input_triangles)
+}
+
+/// Metadata mapping cells of grid with given parameters to coordinates of the input mesh,
+/// so transforms of `transform_triangles` are undone. Placements aren't undone.
+/// Center is in coordinates of the transformed mesh, `input_bounds` are already in
+/// coordinates of the input mesh like every field of the metadata.
+fn grid_metadata(
+    transform: &MeshTransform,
+    input_bounds: &BoundingBox,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    cell_size: f32,
+) -> GridMetadata {
+    let inverse = inverse_matrix(transform);
+
+    let untransform_direction = |v: Vector3<f32>| inverse.transform_vector(&v);
+    let untransform_point = |p: Vector3<f32>| inverse.transform_point(&Point3::from(p)).coords;
+
+    let origin = LocalVector::new(0, 0, 0).to_world_vector(&(center / cell_size), width, height);
+    let origin = Vector3::new(origin.x as f32, origin.y as f32, origin.z as f32) * cell_size;
+
+    GridMetadata {
+        center: untransform_point(center),
+        width,
+        height,
+        cell_size: cell_size / transform.scale.abs(),
+        origin: untransform_point(origin),
+        axes: [
+            untransform_direction(Vector3::x()).normalize(),
+            untransform_direction(Vector3::y()).normalize(),
+            untransform_direction(Vector3::z()).normalize(),
+        ],
+        mesh_bounds: Some(*input_bounds),
+    }
+}
 
-    let center_x = opt.center_x.unwrap_or(bounding_box_center.x);
-    let center_y = opt.center_y.unwrap_or(bounding_box_center.y);
-    let center_z = opt.center_z.unwrap_or(bounding_box_center.z);
+/// Returns grid center in world units, width, height and cell size.
+/// Center is resolved against `center_triangles` if they are given. `size` replaces width
+/// and height fitted to the mesh, like the size of `--seed`, but not the ones set explicitly.
+fn grid_parameters(
+    opt: &GridOpt,
+    triangles: &[Triangle],
+    bounding_box: &BoundingBox,
+    center_triangles: Option<&[Triangle]>,
+    size: Option<(u32, u32)>,
+) -> (Vector3<f32>, u32, u32, f32) {
+    let anchor = match center_triangles {
+        Some(center_triangles) => {
+            let center_bounding_box = obj_to_pathfinding_grid::bounding_box(center_triangles);
+            opt.center.resolve(center_triangles, &center_bounding_box)
+        }
+        None => opt.center.resolve(triangles, bounding_box),
+    };
+
+    let center_x = opt.center_x.unwrap_or(anchor.x);
+    let center_y = opt.center_y.unwrap_or(anchor.y);
+    let center_z = opt.center_z.unwrap_or(anchor.z);
 
     let center = Vector3::new(center_x, center_y, center_z);
 
-    let width = opt.width.unwrap_or(bounding_box.width() as u32);
-    let height = opt.height.unwrap_or(bounding_box.height() as u32);
+    let (cell_size, fitted_width, fitted_height) = match opt.max_cells {
+        Some(max_cells) => fit_max_cells(bounding_box, max_cells),
+        None => {
+            let cell_size = opt.cell_size.unwrap_or(1.0);
+            let (width, height) = dimensions_for_cell_size(bounding_box, cell_size);
+            (cell_size, width, height)
+        }
+    };
+
+    // Flat mesh would give an empty grid, it still needs a layer of cells.
+    let flat_cells = |cells: u32, name: &str| {
+        if cells > 0 {
+            return cells;
+        }
+
+        println!(
+            "Warning: mesh has no {}, using {} cells",
+            name, opt.flat_cells
+        );
+
+        opt.flat_cells
+    };
+
+    let world_cells = |extent: Option<f32>| extent.map(|e| cells_for_extent(e, cell_size));
+
+    let width = opt
+        .width
+        .or_else(|| world_cells(opt.world_width))
+        .or_else(|| size.map(|(width, _)| width))
+        .unwrap_or_else(|| flat_cells(fitted_width, "width"));
+    let height = opt
+        .height
+        .or_else(|| world_cells(opt.world_height))
+        .or_else(|| size.map(|(_, height)| height))
+        .unwrap_or_else(|| flat_cells(fitted_height, "height"));
+
+    // Origin and snapping are resolved in cells, as they depend on the grid size.
+    let center = match &opt.origin {
+        Some(origin) => center_from_origin(&(origin / cell_size), width, height),
+        None => center / cell_size,
+    };
+
+    let center = match opt.snap_origin {
+        Some(tile) => snap_center(&center, width, height, tile / cell_size),
+        None => center,
+    };
+
+    (center * cell_size, width, height, cell_size)
+}
+
+/// Moves grid center, so its first cell is at multiple of tile size, all in cells.
+fn snap_center(center: &Vector3<f32>, width: u32, height: u32, tile: f32) -> Vector3<f32> {
+    let origin = LocalVector::new(0, 0, 0).to_world_vector(center, width, height);
+    let origin = Vector3::new(origin.x as f32, origin.y as f32, origin.z as f32);
+
+    let snapped = (origin / tile).map(f32::floor) * tile;
+
+    center_from_origin(&snapped, width, height)
+}
+
+/// Options which voxelize world triangles into cells of given size.
+fn cell_size_options<'a>(cell_size: f32) -> ConvertOptions<'a> {
+    ConvertOptions {
+        cell_size_xy: cell_size,
+        cell_size_z: cell_size,
+        ..ConvertOptions::default()
+    }
+}
+
+/// Triangles scaled to cells of given size, for reports which work in cells.
+fn cell_triangles(triangles: &[Triangle], cell_size: f32) -> Vec<Triangle> {
+    let options = cell_size_options(cell_size);
+
+    triangles.iter().map(|t| options.to_cells(t)).collect()
+}
+
+/// Count of triangles checked by `--self-test`, spread evenly over the mesh.
+const SELF_TEST_TRIANGLES: usize = 100;
+
+/// Points sampled along every triangle edge by `--self-test`.
+const SELF_TEST_SAMPLES: u32 = 64;
+
+fn self_test(triangles: &[Triangle]) {
+    let step = (triangles.len() / SELF_TEST_TRIANGLES).max(1);
+    let mut mismatches = 0;
+
+    for (index, triangle) in triangles.iter().enumerate().step_by(step) {
+        for cell in triangle.is_inside_mismatches(SELF_TEST_SAMPLES) {
+            println!(
+                "Triangle {} disagrees with reference at ({}, {}, {})",
+                index, cell.x, cell.y, cell.z
+            );
+
+            mismatches += 1;
+        }
+    }
+
+    println!("Self test found {} mismatches", mismatches);
+}
+
+fn convert(opt: &ConvertOpt) -> Result<(), CliError> {
+    let input = &opt.input;
+
+    let (loaded, parse_duration) = timed(|| -> Result<_, CliError> {
+        let obj = load_input(input)?;
+        let loaded = load_triangles(&obj, &opt.mesh)?;
+        Ok((obj, loaded))
+    });
+    let (obj, (triangles, transform)) = loaded?;
+    let center_triangles = load_center_triangles(&obj, &opt.mesh, &opt.grid, &transform)?;
+    let mesh_bounds = bounding_box(&triangles);
+    let input_mesh_bounds = input_bounds(&triangles, &transform);
+
+    let (triangles, bounding_box) = match (opt.region_min, opt.region_max) {
+        (Some(min), Some(max)) => {
+            let region = BoundingBox::new(min.zip_map(&max, f32::min), min.zip_map(&max, f32::max));
+            (triangles_in_region(triangles, &region), region)
+        }
+        _ => (triangles, mesh_bounds),
+    };
+
+    // Seed is loaded first, as its size changes where origin puts the grid center.
+    let seed = match &opt.seed {
+        Some(seed) => Some(load_grid(seed).map_err(read_error(seed))?),
+        None => None,
+    };
+
+    let (center, width, height, cell_size) = grid_parameters(
+        &opt.grid,
+        &triangles,
+        &bounding_box,
+        center_triangles.as_deref(),
+        seed.as_ref().map(|seed| (seed.width(), seed.height())),
+    );
+
+    let triangles = match opt.merge_coplanar {
+        Some(angle) => merge_coplanar(triangles, angle.to_radians()),
+        None => triangles,
+    };
+
+    let clip = opt.clip || opt.region_min.is_some();
+    let (min_width, min_height, _) = minimal_grid(&triangles, cell_size, 0);
+
+    // Clipped triangles are never clamped.
+    if !clip && (width < min_width || height < min_height) {
+        println!(
+            "Warning: grid {}x{}x{} is smaller than {}x{}x{} needed to contain the mesh, \
+             some geometry will be clamped",
+            width, width, height, min_width, min_width, min_height
+        );
+    }
+
+    if opt.estimate {
+        let center = center / cell_size;
+        print_estimate(
+            &cell_triangles(&triangles, cell_size),
+            center,
+            width,
+            height,
+        );
+        return Ok(());
+    }
+
+    if opt.self_test {
+        self_test(&cell_triangles(&triangles, cell_size));
+        return Ok(());
+    }
 
     let output = match &opt.output {
         Some(v) => v.clone(),
         None => {
-            let input_name_without_extension = input.file_stem().unwrap().to_str().unwrap();
-
             let output_folder = Path::new(DEFAULT_OUTPUT_FOLDER);
 
             if !output_folder.exists() {
-                fs::create_dir(output_folder).expect("Failed to create output folder");
+                fs::create_dir(output_folder).map_err(write_error(output_folder))?;
             }
 
-            let output_name = format!("{}.{}", input_name_without_extension, "dat");
-            output_folder.join(PathBuf::from(output_name))
+            output_folder.join(output_name(input))
         }
     };
 
     let progress = StdOutProgress::new();
 
+    let inside_tests = AtomicU64::new(0);
+    let empty_triangles = EmptyTriangleCounter::default();
+
+    let mut options = ConvertOptions {
+        threads: opt.threads,
+        out_of_bounds: if opt.strict_bounds {
+            OutOfBounds::Strict
+        } else {
+            opt.out_of_bounds
+        },
+        mode: opt.mode,
+        wall_thickness: WallThickness(opt.wall_thickness),
+        ensure_nonempty: opt.ensure_nonempty,
+        progress_weight: opt.progress_weight,
+        scan_padding: opt.scan_padding,
+        ..cell_size_options(cell_size)
+    };
+
+    if let Some(max_grid_cells) = opt.max_grid_cells {
+        options.max_cells = max_grid_cells;
+    }
+
+    if let Some(epsilon) = opt.epsilon {
+        options.epsilon = epsilon;
+    }
+
+    if opt.count_inside_tests {
+        options.inside_tests = Some(&inside_tests);
+    }
+
+    if opt.count_empty {
+        options.empty_triangles = Some(&empty_triangles);
+    }
+
+    let mut preprocessors: Vec<Box<dyn Preprocessor>> = Vec::new();
+
+    // Snap whole triangles before they are split, so all parts land in the same layer.
+    if let Some(axis) = opt.snap_axis {
+        let max_angle = opt.snap_angle.to_radians();
+        preprocessors.push(Box::new(SnapToPlanePreprocessor::new(axis, max_angle)));
+    }
+
+    if clip {
+        preprocessors.push(Box::new(ClipPreprocessor {}));
+    }
+
+    if let Some(max_edge_length) = opt.subdivide {
+        preprocessors.push(Box::new(SubdividePreprocessor::new(max_edge_length)));
+    }
+
+    // Quantize last, so vertices created by other preprocessors are rounded too.
+    if let Some(decimals) = opt.quantize {
+        preprocessors.push(Box::new(QuantizePreprocessor::new(decimals)));
+    }
+
     println!("Starting to convert obj file");
 
-    obj_to_pathfinding_grid::convert(
-        triangles,
-        center,
-        width,
-        height,
-        progress,
-        NoOpPreprocessor {},
-    )
-    .export(output)
-    .expect("Failed to save output file");
+    let triangles_count = triangles.len();
 
-    print!("\nFinished converting obj to grid");
-}
+    let max_slope = opt.max_slope.unwrap_or(90.0).to_radians();
 
-struct StdOutProgress {}
+    let (obstacles, mut stats) = if opt.walkable_surface {
+        let (obstacles, voxelize_duration) = timed(|| {
+            walkable_surface(
+                triangles,
+                center,
+                width,
+                height,
+                max_slope,
+                &progress,
+                preprocessors,
+                &options,
+            )
+        });
+        let obstacles = obstacles?;
+        let (obstacles, dedup_duration) = timed(|| dedup_obstacles(obstacles));
 
-impl StdOutProgress {
-    pub fn new() -> Self {
-        StdOutProgress {}
+        let stats = ConversionStats {
+            triangles: triangles_count,
+            empty_triangles: empty_triangles.counts(),
+            inside_tests: options
+                .inside_tests
+                .map(|tests| tests.load(Ordering::Relaxed)),
+            timings: Timings {
+                voxelize: voxelize_duration,
+                dedup: dedup_duration,
+                ..Timings::default()
+            },
+            ..ConversionStats::default()
+        };
+
+        (obstacles, stats)
+    } else {
+        voxelize_with_stats(
+            triangles,
+            center,
+            width,
+            height,
+            &progress,
+            preprocessors,
+            &options,
+        )?
+    };
+
+    // Both are always counted by `voxelize_with_stats`, but printed only if asked for.
+    if !opt.count_empty {
+        stats.empty_triangles = EmptyTriangles::default();
     }
-}
 
-impl Progress for StdOutProgress {
-    fn update_progress(&self, percent: f32) {
-        print!("Current progress: {:.2}%\r", percent)
+    if !opt.count_inside_tests {
+        stats.inside_tests = None;
+    }
+
+    if let Some(step_height) = opt.step_height {
+        let ledges = ledge_cells(&obstacles, step_height);
+        println!("Ledge cells: {}", ledges.len());
+
+        if let Some(ledges_output) = &opt.ledges_output {
+            let ledges: Vec<_> = ledges
+                .iter()
+                .map(|c| Vector3::new(c.x as i32, c.y as i32, c.z as i32))
+                .collect();
+
+            write_file(ledges_output, |writer| write_csv(&ledges, writer))?;
+        }
+    }
+
+    let obstacles = match &opt.extra_obstacles {
+        Some(path) => {
+            let extra_obstacles =
+                load_extra_obstacles(path, opt.extra_world, center, width, height, &options)?;
+
+            add_extra_obstacles(obstacles, extra_obstacles)
+        }
+        None => obstacles,
+    };
+
+    let obstacles = match seed {
+        Some(mut seed) => {
+            let grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+            seed.union(&grid)?;
+            seed.obstacles()
+        }
+        None => obstacles,
+    };
+
+    let obstacles = if opt.invert {
+        let mut grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+        grid.invert();
+        grid.obstacles()
+    } else {
+        obstacles
+    };
+
+    let obstacles = match opt.remove_isolated {
+        Some(min_neighbors) => {
+            let mut grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+            remove_isolated(&mut grid, min_neighbors);
+            grid.obstacles()
+        }
+        None => obstacles,
+    };
+
+    let obstacles = if opt.keep_largest_free {
+        let mut grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+        keep_largest_free_region(&mut grid, opt.free_connectivity);
+        grid.obstacles()
+    } else {
+        obstacles
+    };
+
+    let obstacles = match opt.agent_radius {
+        Some(radius) => {
+            let mut grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+            apply_clearance(&mut grid, radius, cell_size);
+            grid.obstacles()
+        }
+        None => obstacles,
+    };
+
+    if let Some(occupancy_output) = &opt.occupancy_output {
+        let occupancy = OccupancyGrid::from_obstacles(&obstacles, width, height);
+        save_grid_with_layout(&occupancy, opt.layout, occupancy_output)
+            .map_err(write_error(occupancy_output))?;
+    }
+
+    if let Some(distance_output) = &opt.distance_output {
+        let occupancy = OccupancyGrid::from_obstacles(&obstacles, width, height);
+        save_distance_field(&occupancy, distance_output).map_err(write_error(distance_output))?;
+    }
+
+    if let Some(npy_output) = &opt.npy_output {
+        let occupancy = OccupancyGrid::from_obstacles(&obstacles, width, height);
+        save_npy(&occupancy, npy_output).map_err(write_error(npy_output))?;
+    }
+
+    if let Some(contact_sheet_path) = &opt.contact_sheet {
+        let occupancy = OccupancyGrid::from_obstacles(&obstacles, width, height);
+        let sheet = contact_sheet(&occupancy, CONTACT_SHEET_MAX_PIXELS);
+
+        save_png(&sheet, contact_sheet_path).map_err(write_error(contact_sheet_path))?;
+    }
+
+    if let Some(world_output) = &opt.world_output {
+        let world_obstacles = options.world_positions(&obstacles, center, width, height);
+
+        let extension = world_output.extension().and_then(|e| e.to_str());
+
+        write_file(world_output, |writer| match extension {
+            Some("json") => write_json(&world_obstacles, writer),
+            Some("obj") => write_obj(&world_obstacles, writer),
+            _ => write_csv(&world_obstacles, writer),
+        })?;
+    }
+
+    if let Some(cells_output) = &opt.cells_output {
+        let occupancy = OccupancyGrid::from_obstacles(&obstacles, width, height);
+        let polarity = opt.format.polarity(&occupancy);
+
+        write_file(cells_output, |writer| {
+            write_cells(&occupancy, polarity, writer)
+        })?;
+    }
+
+    let ((grid, clamped_obstacles), populate_duration) =
+        timed(|| create_grid_checked(&obstacles, width, height));
+
+    let (exported, export_duration) = timed(|| export_grid(&grid, &output));
+    exported?;
+
+    // Sidecar is written after the grid, so it never describes a grid which failed to export.
+    let metadata = grid_metadata(
+        &transform,
+        &input_mesh_bounds,
+        center,
+        width,
+        height,
+        cell_size,
+    );
+    let metadata_output = metadata_path(&output);
+
+    save_metadata(&metadata, &metadata_output).map_err(|error| ConvertError::Export {
+        path: metadata_output,
+        error,
+    })?;
+
+    stats.obstacles = obstacles.len();
+    stats.clamped_obstacles = clamped_obstacles;
+    stats.timings.parse = parse_duration;
+    stats.timings.populate = populate_duration;
+    stats.timings.export = export_duration;
+
+    print!("\nFinished converting obj to grid\n");
+
+    progress.print_summary(&stats);
+
+    Ok(())
+}
+
+fn inspect(opt: &InspectOpt) -> Result<(), CliError> {
+    let obj = load_input(&opt.input)?;
+    let (triangles, transform) = load_triangles(&obj, &opt.mesh)?;
+    let center_triangles = load_center_triangles(&obj, &opt.mesh, &opt.grid, &transform)?;
+
+    let bounding_box = bounding_box(&triangles);
+    let (center, width, height, cell_size) = grid_parameters(
+        &opt.grid,
+        &triangles,
+        &bounding_box,
+        center_triangles.as_deref(),
+        None,
+    );
+    let options = cell_size_options(cell_size);
+
+    let degenerate_triangles = triangles
+        .iter()
+        .filter(|t| t.normal().norm() == 0.0)
+        .count();
+
+    println!("Grid center: {}", format_vector(&center));
+    print_estimate(
+        &cell_triangles(&triangles, cell_size),
+        center / cell_size,
+        width,
+        height,
+    );
+    println!("Degenerate triangles: {}", degenerate_triangles);
+
+    if opt.count_empty {
+        print_empty_triangles(&empty_triangles(
+            &triangles, center, width, height, &options,
+        ));
+    }
+
+    if opt.layers {
+        let obstacles = voxelize(
+            triangles,
+            center,
+            width,
+            height,
+            StdOutProgress::new(),
+            NoOpPreprocessor {},
+            &options,
+        )?;
+
+        let histogram = layer_histogram(&dedup_obstacles(obstacles), height);
+        let max_count = histogram.iter().copied().max().unwrap_or(0).max(1);
+
+        println!();
+
+        for (z, count) in histogram.iter().enumerate().rev() {
+            let bar = "#".repeat(count * LAYER_BAR_WIDTH / max_count);
+            println!("{:>5} {:>8} {}", z, count, bar);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_empty_triangles(empty: &EmptyTriangles) {
+    println!("Triangles without obstacles: {}", empty.total());
+    println!("  degenerate: {}", empty.degenerate);
+    println!("  outside of the grid: {}", empty.outside);
+    println!("  smaller than a cell: {}", empty.too_small);
+    println!("  between cells: {}", empty.grazing);
+}
+
+fn diff(opt: &DiffOpt) -> Result<(), CliError> {
+    let (first, second) = if is_obj_path(&opt.input) {
+        mesh_grids(opt)?
+    } else {
+        let load = |path: &PathBuf| load_any_grid(path).map_err(read_error(path));
+
+        (load(&opt.input)?, load(&opt.other)?)
+    };
+
+    let (shared, only_first, only_second) = diff_grids(&first, &second)?;
+
+    println!();
+    println!(
+        "Grid size: {}x{}x{}",
+        first.width(),
+        first.width(),
+        first.height()
+    );
+    println!("Shared obstacles: {}", shared);
+    println!("Only in input: {}", only_first);
+    println!("Only in other: {}", only_second);
+
+    Ok(())
+}
+
+/// Counts obstacles shared by both grids and found only in the first or only in the second.
+fn diff_grids(
+    first: &OccupancyGrid,
+    second: &OccupancyGrid,
+) -> Result<(usize, usize, usize), GridError> {
+    let mut shared = first.clone();
+    shared.intersection(second)?;
+
+    let shared_count = shared.obstacle_count();
+
+    Ok((
+        shared_count,
+        first.obstacle_count() - shared_count,
+        second.obstacle_count() - shared_count,
+    ))
+}
+
+/// Converts input and other mesh of `diff` to grids of the same placement.
+fn mesh_grids(opt: &DiffOpt) -> Result<(OccupancyGrid, OccupancyGrid), CliError> {
+    if !is_obj_path(&opt.other) {
+        return Err(CliError::Read {
+            path: opt.other.clone(),
+            error: "Expected obj file like input".into(),
+        });
+    }
+
+    let obj = load_input(&opt.input)?;
+    let (triangles, transform) = load_triangles(&obj, &opt.mesh)?;
+    let center_triangles = load_center_triangles(&obj, &opt.mesh, &opt.grid, &transform)?;
+
+    // Other mesh is scaled about the same pivot, so both stay aligned.
+    let other = load_input(&opt.other)?;
+    let other_triangles = load_mesh_triangles(&other, &opt.mesh)?;
+    let other_triangles = transform_triangles(other_triangles, &transform);
+
+    let bounding_box = {
+        let first = bounding_box(&triangles);
+        let second = bounding_box(&other_triangles);
+
+        let min = first.min.zip_map(&second.min, f32::min);
+        let max = first.max.zip_map(&second.max, f32::max);
+
+        BoundingBox::new(min, max)
+    };
+
+    // Center is resolved against both meshes, like the bounding box.
+    let mut triangles = triangles;
+    let input_count = triangles.len();
+    triangles.extend(other_triangles);
+
+    let (center, width, height, cell_size) = grid_parameters(
+        &opt.grid,
+        &triangles,
+        &bounding_box,
+        center_triangles.as_deref(),
+        None,
+    );
+
+    let other_triangles = triangles.split_off(input_count);
+
+    let mut options = cell_size_options(cell_size);
+
+    if let Some(epsilon) = opt.epsilon {
+        options.epsilon = epsilon;
+    }
+
+    let to_occupancy_grid = |triangles: Vec<Triangle>| -> Result<_, ConvertError> {
+        let obstacles = voxelize(
+            triangles,
+            center,
+            width,
+            height,
+            StdOutProgress::new(),
+            NoOpPreprocessor {},
+            &options,
+        )?;
+
+        Ok(OccupancyGrid::from_obstacles(&obstacles, width, height))
+    };
+
+    Ok((
+        to_occupancy_grid(triangles)?,
+        to_occupancy_grid(other_triangles)?,
+    ))
+}
+
+/// Cells of `--extra-obstacles`, given in local or world coordinates. Points outside of the
+/// grid are handled like triangles by `out_of_bounds` of the options: clamped to its borders,
+/// skipped with a warning or rejected.
+fn load_extra_obstacles(
+    path: &Path,
+    world: bool,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Result<Vec<LocalVector>, CliError> {
+    let points = load_points(path).map_err(read_error(path))?;
+
+    // Local cells are world cells of a grid with origin at zero.
+    let (scale, center) = if world {
+        (
+            options.cell_scale(),
+            options.grid_center(center, width, height),
+        )
+    } else {
+        let origin = Vector3::zeros();
+        (
+            Vector3::new(1.0, 1.0, 1.0),
+            center_from_origin(&origin, width, height),
+        )
+    };
+
+    let mut obstacles = Vec::new();
+    let mut outside = 0;
+
+    for point in &points {
+        let point = point.component_mul(&scale);
+
+        match LocalVector::try_from_world_vector(&point, &center, width, height) {
+            Some(cell) => obstacles.push(cell),
+            None if options.out_of_bounds == OutOfBounds::Clamp => obstacles.push(
+                LocalVector::from_world_vector(&point, &center, width, height),
+            ),
+            None => outside += 1,
+        }
+    }
+
+    if outside > 0 {
+        if options.out_of_bounds == OutOfBounds::Drop {
+            println!(
+                "Warning: {} extra obstacles outside of the grid were skipped",
+                outside
+            );
+        } else {
+            let error = format!("{} points are outside of the grid", outside);
+            return Err(read_error(path)(error));
+        }
+    }
+
+    Ok(obstacles)
+}
+
+/// Name of grid converted from input file, `name.obj` and `name.obj.gz` give `name.dat`.
+fn output_name(input: &Path) -> PathBuf {
+    let input_name = if input == Path::new(STDIN_INPUT) {
+        STDIN_OUTPUT_NAME
+    } else {
+        input.file_stem().unwrap().to_str().unwrap()
+    };
+
+    // Compressed `name.obj.gz` has two extensions.
+    let input_name_without_extension = input_name.trim_end_matches(".obj");
+
+    PathBuf::from(format!("{}.{}", input_name_without_extension, "dat"))
+}
+
+/// Checks if path is an obj file or stdin input, compressed `name.obj.gz` too.
+fn is_obj_path(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+
+    path == Path::new(STDIN_INPUT) || name.ends_with(".obj") || name.ends_with(".obj.gz")
+}
+
+/// Obj files of the folder sorted by name.
+fn batch_inputs(folder: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+
+        if is_obj_path(&path) {
+            inputs.push(path);
+        }
+    }
+
+    inputs.sort();
+
+    Ok(inputs)
+}
+
+/// Converts every obj file of the folder, a failed file doesn't stop the others.
+/// Returns count of failed files.
+fn batch(opt: &BatchOpt) -> Result<usize, CliError> {
+    let inputs = batch_inputs(&opt.input_dir).map_err(read_error(&opt.input_dir))?;
+    fs::create_dir_all(&opt.output_dir).map_err(write_error(&opt.output_dir))?;
+
+    // Arguments are parsed for all files first, so a typo stops the batch before converting.
+    let convert_opts: Vec<ConvertOpt> = inputs
+        .iter()
+        .map(|input| {
+            let output = opt.output_dir.join(output_name(input));
+            let args = [
+                OsString::from("convert"),
+                OsString::from("--input"),
+                input.clone().into_os_string(),
+                OsString::from("--output"),
+                output.into_os_string(),
+            ];
+            let args = args
+                .iter()
+                .cloned()
+                .chain(opt.args.iter().map(OsString::from));
+
+            ConvertOpt::from_iter_safe(args).unwrap_or_else(|e| e.exit())
+        })
+        .collect();
+
+    let mut failed = Vec::new();
+
+    for (index, convert_opt) in convert_opts.iter().enumerate() {
+        println!(
+            "\n[{}/{}] {}",
+            index + 1,
+            inputs.len(),
+            convert_opt.input.display()
+        );
+
+        if let Err(error) = convert(convert_opt) {
+            eprintln!("Error: {}", error);
+            failed.push(&convert_opt.input);
+        }
+    }
+
+    println!(
+        "\nConverted {} of {} files",
+        inputs.len() - failed.len(),
+        inputs.len()
+    );
+
+    for input in &failed {
+        println!("Failed: {}", input.display());
+    }
+
+    Ok(failed.len())
+}
+
+fn crop(opt: &CropOpt) -> Result<(), CliError> {
+    let grid = load_grid(&opt.input).map_err(read_error(&opt.input))?;
+    let cropped = grid.crop(opt.min, opt.max)?;
+
+    save_grid(&cropped, &opt.output).map_err(write_error(&opt.output))?;
+
+    println!("Cropped grid: {}", cropped.summary());
+
+    Ok(())
+}
+
+fn print_estimate(triangles: &[Triangle], center: Vector3<f32>, width: u32, height: u32) {
+    let estimate = estimate(triangles, center, width, height);
+
+    println!("Triangles: {}", estimate.triangles);
+    println!(
+        "Mesh bounding box: {} - {}",
+        format_vector(&estimate.bounding_box.min),
+        format_vector(&estimate.bounding_box.max)
+    );
+    println!(
+        "Grid size: {}x{}x{}",
+        estimate.width, estimate.width, estimate.height
+    );
+    println!("Cells: {}", estimate.cells);
+    println!("Estimated size: {} bytes", estimate.bytes);
+
+    if estimate.outside {
+        println!("Warning: some geometry falls outside of the grid and will be clamped");
+    }
+}
+
+fn format_vector(vector: &Vector3<f32>) -> String {
+    format!("({}, {}, {})", vector.x, vector.y, vector.z)
+}
+
+/// Prints progress at most once per `PROGRESS_INTERVAL`, so converting millions of triangles
+/// doesn't flood the console. 100% is always printed.
+struct StdOutProgress {
+    last_update: Cell<Option<Instant>>,
+}
+
+impl StdOutProgress {
+    pub fn new() -> Self {
+        StdOutProgress {
+            last_update: Cell::new(None),
+        }
+    }
+
+    /// Whether update at given time should be printed, remembers time of printed updates.
+    fn should_print(&self, percent: f32, now: Instant) -> bool {
+        let print = match self.last_update.get() {
+            Some(last) => percent >= 100.0 || now.duration_since(last) >= PROGRESS_INTERVAL,
+            None => true,
+        };
+
+        if print {
+            self.last_update.set(Some(now));
+        }
+
+        print
+    }
+
+    pub fn print_summary(&self, stats: &ConversionStats) {
+        let timings = &stats.timings;
+
+        println!("Triangles: {}", stats.triangles);
+        println!("Obstacles: {}", stats.obstacles);
+
+        if stats.clamped_obstacles > 0 {
+            println!(
+                "Warning: {} obstacles outside of the grid were moved to its border",
+                stats.clamped_obstacles
+            );
+        }
+
+        if stats.empty_triangles.total() > 0 {
+            print_empty_triangles(&stats.empty_triangles);
+        }
+
+        if let Some(inside_tests) = stats.inside_tests {
+            println!("Inside tests: {}", inside_tests);
+        }
+
+        println!("Parse: {:.2?}", timings.parse);
+        println!("Voxelize: {:.2?}", timings.voxelize);
+        println!("Dedup: {:.2?}", timings.dedup);
+        println!("Populate: {:.2?}", timings.populate);
+        println!("Export: {:.2?}", timings.export);
+        println!("Total: {:.2?}", timings.total());
+    }
+}
+
+impl Progress for StdOutProgress {
+    fn update_progress(&self, percent: f32) {
+        if self.should_print(percent, Instant::now()) {
+            print!("Current progress: {:.2}%\r", percent)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        batch, batch_inputs, centroid, convert, diff_grids, grid_metadata, grid_parameters,
+        input_bounds, load_center_triangles, load_extra_obstacles, load_input, load_triangles,
+        mesh_grids, parse_decimals, parse_edge_length, parse_scale, read_input,
+        transform_triangles, with_default_subcommand, Center, CliError, Opt, StdOutProgress,
+        PROGRESS_INTERVAL,
+    };
+    use nalgebra::Vector3;
+    use obj_to_pathfinding_grid::error::ConvertError;
+    use obj_to_pathfinding_grid::export::{
+        load_any_grid, load_grid, load_points, save_grid, write_cells, Polarity,
+    };
+    use obj_to_pathfinding_grid::geometry::{BoundingBox, LocalVector, Triangle};
+    use obj_to_pathfinding_grid::occupancy::OccupancyGrid;
+    use obj_to_pathfinding_grid::{bounding_box, voxelize_triangle, ConvertOptions, OutOfBounds};
+    use std::ffi::OsString;
+    use std::fs;
+    use std::fs::File;
+    use std::path::Path;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+    use structopt::StructOpt;
+
+    fn parse(args: &[&str]) -> Opt {
+        let args = args.iter().map(OsString::from).collect();
+
+        Opt::from_iter_safe(with_default_subcommand(args)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_convert() {
+        let opt = parse(&["cli", "convert", "-i", "a.obj", "-o", "a.dat", "-w", "100"]);
+
+        match opt {
+            Opt::Convert(opt) => {
+                assert_eq!(opt.input, PathBuf::from("a.obj"));
+                assert_eq!(opt.output, Some(PathBuf::from("a.dat")));
+                assert_eq!(opt.grid.width, Some(100));
+            }
+            _ => panic!("Expected convert subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_scale() {
+        assert_eq!(parse_scale("2"), Ok(2.0));
+        assert_eq!(parse_scale("-0.5"), Ok(-0.5));
+        assert!(parse_scale("0").is_err());
+        assert!(parse_scale("inf").is_err());
+
+        let args = ["cli", "-i", "a.obj", "--scale", "0"];
+        let args = args.iter().map(OsString::from).collect();
+
+        assert!(Opt::from_iter_safe(with_default_subcommand(args)).is_err());
+    }
+
+    #[test]
+    fn test_parse_edge_length() {
+        assert_eq!(parse_edge_length("0.5"), Ok(0.5));
+        assert!(parse_edge_length("0").is_err());
+        assert!(parse_edge_length("-1").is_err());
+        assert!(parse_edge_length("NaN").is_err());
+    }
+
+    #[test]
+    fn test_parse_decimals() {
+        assert_eq!(parse_decimals("9"), Ok(9));
+        assert!(parse_decimals("10").is_err());
+        assert!(parse_decimals("-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_default_subcommand() {
+        let opt = parse(&["cli", "--input=a.obj", "--scale", "2"]);
+
+        match opt {
+            Opt::Convert(opt) => {
+                assert_eq!(opt.input, PathBuf::from("a.obj"));
+                assert_eq!(opt.mesh.scale, Some(2.0));
+            }
+            _ => panic!("Expected convert subcommand"),
+        }
+
+        // Both help flags show subcommands of the top level command.
+        for help in &["-h", "--help"] {
+            let args = vec![OsString::from("cli"), OsString::from(help)];
+            assert_eq!(with_default_subcommand(args.clone()), args);
+
+            let error = Opt::from_iter_safe(args).unwrap_err();
+            assert_eq!(error.kind, structopt::clap::ErrorKind::HelpDisplayed);
+            assert!(error.message.contains("SUBCOMMANDS"));
+        }
+    }
+
+    #[test]
+    fn test_parse_inspect() {
+        let opt = parse(&["cli", "inspect", "-i", "a.obj", "--flip-x", "-x", "5"]);
+
+        match opt {
+            Opt::Inspect(opt) => {
+                assert_eq!(opt.input, PathBuf::from("a.obj"));
+                assert!(opt.mesh.flip_x);
+                assert_eq!(opt.grid.center_x, Some(5.0));
+            }
+            _ => panic!("Expected inspect subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let opt = parse(&["cli", "diff", "-i", "a.obj", "--other", "b.obj"]);
+
+        match opt {
+            Opt::Diff(opt) => {
+                assert_eq!(opt.input, PathBuf::from("a.obj"));
+                assert_eq!(opt.other, PathBuf::from("b.obj"));
+            }
+            _ => panic!("Expected diff subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_diff_grids() {
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-diff-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&folder).unwrap();
+
+        let mut first = OccupancyGrid::new(4, 2);
+        first.set_obstacle(0, 0, 0);
+        first.set_obstacle(1, 2, 1);
+
+        let mut second = OccupancyGrid::new(4, 2);
+        second.set_obstacle(1, 2, 1);
+        second.set_obstacle(3, 3, 0);
+        second.set_obstacle(3, 3, 1);
+
+        let first_path = folder.join("first.dat");
+        let second_path = folder.join("second.txt");
+
+        save_grid(&first, &first_path).unwrap();
+        write_cells(
+            &second,
+            Polarity::Obstacles,
+            &mut File::create(&second_path).unwrap(),
+        )
+        .unwrap();
+
+        let first = load_any_grid(&first_path).unwrap();
+        let second = load_any_grid(&second_path).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(diff_grids(&first, &second).unwrap(), (1, 1, 2));
+        assert!(diff_grids(&first, &OccupancyGrid::new(3, 2)).is_err());
+
+        // Meshes are converted to grids of the same placement and compared the same way.
+        let opt = match parse(&[
+            "cli",
+            "diff",
+            "-i",
+            "tests/bare.obj",
+            "--other",
+            "tests/bare.obj",
+        ]) {
+            Opt::Diff(opt) => opt,
+            _ => panic!("Expected diff subcommand"),
+        };
+        let (first, second) = mesh_grids(&opt).unwrap();
+        let (shared, only_first, only_second) = diff_grids(&first, &second).unwrap();
+
+        assert!(shared > 0);
+        assert_eq!((only_first, only_second), (0, 0));
+
+        let opt = match parse(&["cli", "diff", "-i", "tests/bare.obj", "--other", "b.dat"]) {
+            Opt::Diff(opt) => opt,
+            _ => panic!("Expected diff subcommand"),
+        };
+
+        assert!(mesh_grids(&opt).is_err());
+    }
+
+    #[test]
+    fn test_diff_centroid() {
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-diff-centroid-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&folder).unwrap();
+
+        // Centroid covers both meshes, so a moved copy of `bare.obj` isn't clamped to the grid
+        // border.
+        let moved_path = folder.join("moved.obj");
+        let moved = "v 10 0 0\nv 13 0 0\nv 10 3 0\nv 10 0 3\nf 1 2 3\nf 1 2 4\n";
+
+        fs::write(&moved_path, moved).unwrap();
+
+        let args = [
+            "cli",
+            "diff",
+            "-i",
+            "tests/bare.obj",
+            "--other",
+            moved_path.to_str().unwrap(),
+            "--center",
+            "centroid",
+        ];
+        let opt = match parse(&args) {
+            Opt::Diff(opt) => opt,
+            _ => panic!("Expected diff subcommand"),
+        };
+        let (first, second) = mesh_grids(&opt).unwrap();
+
+        assert_eq!(first.obstacle_count(), second.obstacle_count());
+        assert_eq!(diff_grids(&first, &second).unwrap().0, 0);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_parse_objects() {
+        let opt = parse(&[
+            "cli",
+            "-i",
+            "a.obj",
+            "--object",
+            "terrain",
+            "--object",
+            "roads",
+            "--place",
+            "roads=0,0,1.5",
+        ]);
+
+        match opt {
+            Opt::Convert(opt) => {
+                assert_eq!(opt.mesh.objects, vec!["terrain", "roads"]);
+                assert_eq!(opt.mesh.placements[0].name, "roads");
+                assert_eq!(opt.mesh.placements[0].offset, Vector3::new(0.0, 0.0, 1.5));
+            }
+            _ => panic!("Expected convert subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_parse_crop() {
+        let opt = parse(&[
+            "cli", "crop", "-i", "a.opg", "-o", "b.opg", "--min", "1,2,3", "--max", "4,5,6",
+        ]);
+
+        match opt {
+            Opt::Crop(opt) => {
+                assert_eq!(opt.min, LocalVector::new(1, 2, 3));
+                assert_eq!(opt.max, LocalVector::new(4, 5, 6));
+            }
+            _ => panic!("Expected crop subcommand"),
+        }
+
+        let args = [
+            "cli", "crop", "-i", "a", "-o", "b", "--min", "1,2", "--max", "4,5,6",
+        ];
+        let args = args.iter().map(OsString::from).collect();
+
+        assert!(Opt::from_iter_safe(with_default_subcommand(args)).is_err());
+    }
+
+    #[test]
+    fn test_resolve_center() {
+        let triangles = vec![
+            Triangle::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(6.0, 0.0, 0.0),
+                Vector3::new(0.0, 6.0, 0.0),
+            ),
+            Triangle::new(
+                Vector3::new(0.0, 0.0, 3.0),
+                Vector3::new(6.0, 0.0, 3.0),
+                Vector3::new(0.0, 6.0, 3.0),
+            ),
+        ];
+
+        let bounding_box = bounding_box(&triangles);
+
+        let resolve = |center: &str| {
+            let center: Center = center.parse().unwrap();
+            center.resolve(&triangles, &bounding_box)
+        };
+
+        assert_eq!(resolve("origin"), Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(resolve("bbox-center"), Vector3::new(3.0, 3.0, 1.5));
+        assert_eq!(resolve("bbox-min"), Vector3::new(-1.0, -1.0, -1.0));
+        assert_eq!(resolve("bbox-max"), Vector3::new(7.0, 7.0, 4.0));
+        assert_eq!(resolve("centroid"), Vector3::new(2.0, 2.0, 1.5));
+        assert_eq!(resolve("1.5, -2, 3"), Vector3::new(1.5, -2.0, 3.0));
+
+        assert!("1,2".parse::<Center>().is_err());
+        assert!("corner".parse::<Center>().is_err());
+    }
+
+    #[test]
+    fn test_parse_center() {
+        let opt = parse(&["cli", "-i", "a.obj", "--center", "bbox-min", "-z", "4"]);
+
+        match opt {
+            Opt::Convert(opt) => {
+                assert_eq!(opt.grid.center, Center::BoundingBoxMin);
+                assert_eq!(opt.grid.center_z, Some(4.0));
+            }
+            _ => panic!("Expected convert subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_center_on_object() {
+        let opt = parse(&["cli", "-i", "tests/objects.obj", "--center-on", "terrain"]);
+
+        let opt = match opt {
+            Opt::Convert(opt) => opt,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let input = &load_input(Path::new("tests/objects.obj")).unwrap();
+        let (triangles, transform) = load_triangles(input, &opt.mesh).unwrap();
+        let center_triangles = load_center_triangles(input, &opt.mesh, &opt.grid, &transform)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(triangles.len(), 3);
+        assert_eq!(center_triangles.len(), 1);
+
+        let terrain_bounding_box = bounding_box(&center_triangles);
+        let bounding_box = bounding_box(&triangles);
+
+        let (center, width, height, _) = grid_parameters(
+            &opt.grid,
+            &triangles,
+            &bounding_box,
+            Some(&center_triangles),
+            None,
+        );
+
+        assert_eq!(center, terrain_bounding_box.center());
+        assert_ne!(center, bounding_box.center());
+        assert_eq!(width, bounding_box.width() as u32);
+        assert_eq!(height, bounding_box.height() as u32);
+    }
+
+    #[test]
+    fn test_clip_z_band() {
+        let input = &load_input(Path::new("tests/bare.obj")).unwrap();
+
+        let triangles = |args: &[&str]| match parse(args) {
+            Opt::Convert(opt) => load_triangles(input, &opt.mesh).unwrap().0,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let heights = |triangles: &[Triangle]| {
+            let heights: Vec<f32> = triangles
+                .iter()
+                .flat_map(|t| vec![t.a.z, t.b.z, t.c.z])
+                .collect();
+            let min = heights.iter().copied().fold(f32::INFINITY, f32::min);
+            let max = heights.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+            (min, max)
+        };
+
+        // Floor at z 0 is cut off, the wall rising from it to z 3 keeps its top.
+        let above = triangles(&["cli", "-i", "tests/bare.obj", "--clip-below-z", "0.5"]);
+
+        assert_eq!(above.len(), 1);
+        assert_eq!(heights(&above), (0.5, 3.0));
+
+        let args = [
+            "cli",
+            "-i",
+            "tests/bare.obj",
+            "--clip-above-z",
+            "1",
+            "--clip-below-z=-1",
+        ];
+        let below = triangles(&args);
+
+        // Bottom of the wall is a quad, split into two triangles.
+        assert_eq!(below.len(), 3);
+        assert_eq!(heights(&below), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_transformed_bounds() {
+        let input = &load_input(Path::new("tests/bare.obj")).unwrap();
+
+        let mesh_opt = |args: &[&str]| match parse(args) {
+            Opt::Convert(opt) => opt.mesh,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let opt = mesh_opt(&["cli", "-i", "tests/bare.obj"]);
+        let original = bounding_box(&load_triangles(input, &opt).unwrap().0);
+
+        let args = [
+            "cli",
+            "-i",
+            "tests/bare.obj",
+            "--rotate-z",
+            "45",
+            "--translate",
+            "0,0,2",
+        ];
+        let opt = mesh_opt(&args);
+        let (triangles, _) = load_triangles(input, &opt).unwrap();
+        let transformed = bounding_box(&triangles);
+
+        // Rotated by 45 degrees the mesh spans diagonal of its 3x3 base on y axis.
+        assert_eq!((original.width(), original.height()), (5.0, 5.0));
+        assert_eq!((transformed.width(), transformed.height()), (6.0, 5.0));
+        assert_eq!(transformed.min.z, original.min.z + 2.0);
+
+        let grid_opt = match parse(&args) {
+            Opt::Convert(opt) => opt.grid,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let (center, width, height, _) =
+            grid_parameters(&grid_opt, &triangles, &transformed, None, None);
+
+        assert_eq!(center, transformed.center());
+        assert_eq!((width, height), (6, 5));
+
+        // Scaling keeps centroid of the mesh in place unless pivot is given.
+        let original = load_triangles(input, &mesh_opt(&["cli", "-i", "tests/bare.obj"]))
+            .unwrap()
+            .0;
+        let scaled = load_triangles(input, &mesh_opt(&["cli", "-i", "a.obj", "-s", "2"]))
+            .unwrap()
+            .0;
+
+        assert!((centroid(&scaled) - centroid(&original)).norm() < 1e-4);
+
+        let args = ["cli", "-i", "a.obj", "-s", "2", "--scale-pivot", "0,0,0"];
+        let scaled = load_triangles(input, &mesh_opt(&args)).unwrap().0;
+
+        assert!((centroid(&scaled) - centroid(&original) * 2.0).norm() < 1e-4);
+    }
+
+    #[test]
+    fn test_grid_metadata() {
+        let args = [
+            "cli",
+            "-i",
+            "tests/bare.obj",
+            "--scale",
+            "2",
+            "--rotate-z",
+            "90",
+            "--flip-x",
+            "--translate",
+            "1,0,0",
+            "--cell-size",
+            "0.5",
+        ];
+
+        let opt = match parse(&args) {
+            Opt::Convert(opt) => opt,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let (triangles, transform) =
+            load_triangles(&load_input(&opt.input).unwrap(), &opt.mesh).unwrap();
+        let bounding_box = bounding_box(&triangles);
+        let (center, width, height, cell_size) =
+            grid_parameters(&opt.grid, &triangles, &bounding_box, None, None);
+
+        let input_bounds = input_bounds(&triangles, &transform);
+        let metadata = grid_metadata(&transform, &input_bounds, center, width, height, cell_size);
+
+        assert_eq!((metadata.width, metadata.height), (width, height));
+        assert_eq!(metadata.cell_size, 0.25);
+
+        // Mesh bounds are in the input frame like the center, not in the transformed one.
+        let input_opt = match parse(&["cli", "-i", "tests/bare.obj"]) {
+            Opt::Convert(opt) => opt.mesh,
+            _ => panic!("Expected convert subcommand"),
+        };
+        let (input_triangles, _) =
+            load_triangles(&load_input(&opt.input).unwrap(), &input_opt).unwrap();
+        let expected_bounds = obj_to_pathfinding_grid::bounding_box(&input_triangles);
+        let mesh_bounds = metadata.mesh_bounds.unwrap();
+
+        assert_ne!(expected_bounds, bounding_box);
+        assert!((mesh_bounds.min - expected_bounds.min).norm() < 1e-4);
+        assert!((mesh_bounds.max - expected_bounds.max).norm() < 1e-4);
+        assert!((metadata.center - mesh_bounds.center()).norm() < 1e-4);
+
+        // Input mesh x is the grid y after rotation and mirrored grid x is the input y.
+        assert!((metadata.axes[0] - Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-6);
+        assert!((metadata.axes[1] - Vector3::new(1.0, 0.0, 0.0)).norm() < 1e-6);
+        assert_eq!(metadata.axes[2], Vector3::new(0.0, 0.0, 1.0));
+
+        // Transforming world position of a cell gives position of the cell in the grid.
+        for cell in &[LocalVector::new(0, 0, 0), LocalVector::new(3, 5, 2)] {
+            let point = metadata.cell_to_world(cell);
+            let point = Triangle::new(point, point, point);
+
+            let transformed = transform_triangles(vec![point], &transform)[0].a / cell_size;
+            let expected = cell.to_world_vector(&(center / cell_size), width, height);
+            let expected = Vector3::new(expected.x as f32, expected.y as f32, expected.z as f32);
+
+            assert!((transformed - expected).norm() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_cell_size() {
+        let bounding_box =
+            BoundingBox::new(Vector3::new(-5.0, -3.0, 0.0), Vector3::new(5.0, 7.0, 3.0));
+
+        let grid_opt = |args: &[&str]| match parse(args) {
+            Opt::Convert(opt) => opt.grid,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let opt = grid_opt(&["cli", "-i", "tests/teapot.obj"]);
+        let (center, width, height, cell_size) =
+            grid_parameters(&opt, &[], &bounding_box, None, None);
+
+        assert_eq!((width, height, cell_size), (10, 3, 1.0));
+        assert_eq!(center, Vector3::new(0.0, 2.0, 1.5));
+
+        // Center stays in world units, only the count of cells changes.
+        let opt = grid_opt(&["cli", "-i", "tests/teapot.obj", "--cell-size", "0.5"]);
+        let (center, width, height, cell_size) =
+            grid_parameters(&opt, &[], &bounding_box, None, None);
+
+        assert_eq!((width, height, cell_size), (20, 6, 0.5));
+        assert_eq!(center, Vector3::new(0.0, 2.0, 1.5));
+
+        // Partial cells are rounded up.
+        let opt = grid_opt(&["cli", "-i", "tests/teapot.obj", "--cell-size", "4"]);
+        let (_, width, height, _) = grid_parameters(&opt, &[], &bounding_box, None, None);
+
+        assert_eq!((width, height), (3, 1));
+    }
+
+    #[test]
+    fn test_snap_origin() {
+        let bounding_box =
+            BoundingBox::new(Vector3::new(-5.0, -3.0, 0.0), Vector3::new(5.0, 7.0, 3.0));
+
+        let args = [
+            "cli",
+            "-i",
+            "a.obj",
+            "--cell-size",
+            "0.5",
+            "--snap-origin",
+            "4",
+        ];
+        let opt = match parse(&args) {
+            Opt::Convert(opt) => opt.grid,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let (center, width, height, cell_size) =
+            grid_parameters(&opt, &[], &bounding_box, None, None);
+        let center = center / cell_size;
+
+        assert_eq!((width, height, cell_size), (20, 6, 0.5));
+
+        // Unsnapped first cell would be at -5, -3, 0 in world units.
+        let origin = LocalVector::new(0, 0, 0).to_world_vector(&center, width, height);
+        let origin = Vector3::new(origin.x as f32, origin.y as f32, origin.z as f32) * cell_size;
+
+        assert_eq!(origin, Vector3::new(-8.0, -4.0, 0.0));
+
+        // Obstacles are at their position relative to the tile corner, in cells.
+        let world = Vector3::new(1.0, 2.5, 0.5);
+        let cell = LocalVector::from_world_vector(&(world / cell_size), &center, width, height);
+
+        assert_eq!(cell, LocalVector::new(18, 13, 1));
+    }
+
+    #[test]
+    fn test_world_size() {
+        let bounding_box =
+            BoundingBox::new(Vector3::new(-5.0, -3.0, 0.0), Vector3::new(5.0, 7.0, 3.0));
+
+        let grid_opt = |args: &[&str]| match parse(args) {
+            Opt::Convert(opt) => opt.grid,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let args = [
+            "cli",
+            "-i",
+            "a.obj",
+            "--world-width",
+            "100",
+            "--world-height",
+            "10",
+            "--cell-size",
+            "0.5",
+        ];
+        let opt = grid_opt(&args);
+        let (_, width, height, _) = grid_parameters(&opt, &[], &bounding_box, None, None);
+
+        assert_eq!((width, height), (200, 20));
+
+        let args = ["cli", "-i", "a.obj", "-w", "10", "--world-width", "100"];
+        let args = args.iter().map(OsString::from).collect();
+
+        assert!(Opt::from_iter_safe(with_default_subcommand(args)).is_err());
+    }
+
+    #[test]
+    fn test_flat_mesh() {
+        let triangles = vec![
+            Triangle::new(
+                Vector3::new(-4.0, -4.0, 0.0),
+                Vector3::new(4.0, -4.0, 0.0),
+                Vector3::new(4.0, 4.0, 0.0),
+            ),
+            Triangle::new(
+                Vector3::new(-4.0, -4.0, 0.0),
+                Vector3::new(4.0, 4.0, 0.0),
+                Vector3::new(-4.0, 4.0, 0.0),
+            ),
+        ];
+
+        // Exact box of the floor, like a flat `--region-min` and `--region-max`.
+        let bounding_box =
+            BoundingBox::new(Vector3::new(-4.0, -4.0, 0.0), Vector3::new(4.0, 4.0, 0.0));
+
+        let grid_opt = |args: &[&str]| match parse(args) {
+            Opt::Convert(opt) => opt.grid,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        let opt = grid_opt(&["cli", "-i", "floor.obj"]);
+        let (center, width, height, _) =
+            grid_parameters(&opt, &triangles, &bounding_box, None, None);
+
+        assert_eq!((width, height), (8, 1));
+
+        let mut obstacles = Vec::new();
+
+        for triangle in &triangles {
+            voxelize_triangle(triangle, &center, width, height, 1.0, &mut |o| {
+                obstacles.push(o)
+            });
+        }
+
+        let grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+        // The only layer is the floor, covered whole.
+        assert_eq!(grid.obstacle_count(), 8 * 8);
+
+        let opt = grid_opt(&["cli", "-i", "floor.obj", "--flat-cells", "3"]);
+        let (_, _, height, _) = grid_parameters(&opt, &triangles, &bounding_box, None, None);
+
+        assert_eq!(height, 3);
+    }
+
+    #[test]
+    fn test_progress_throttling() {
+        let progress = StdOutProgress::new();
+        let start = Instant::now();
+        let at = |millis: u64| start + Duration::from_millis(millis);
+
+        assert!(progress.should_print(0.0, at(0)));
+        assert!(!progress.should_print(10.0, at(1)));
+        assert!(!progress.should_print(20.0, at(32)));
+        assert!(progress.should_print(30.0, at(33)));
+
+        // Interval restarts at the last printed update.
+        assert!(!progress.should_print(40.0, at(60)));
+        assert!(progress.should_print(50.0, at(33) + PROGRESS_INTERVAL));
+
+        // Final update is printed even right after another one.
+        assert!(progress.should_print(100.0, at(67)));
+    }
+
+    #[test]
+    fn test_convert_errors() {
+        let convert_args = |args: &[&str]| match parse(args) {
+            Opt::Convert(opt) => convert(&opt),
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        match convert_args(&["cli", "-i", "tests/missing.obj", "-o", "missing.dat"]) {
+            Err(CliError::Read { path, .. }) => assert_eq!(path, Path::new("tests/missing.obj")),
+            result => panic!("Expected read error, got {:?}", result),
+        }
+
+        let args = ["cli", "-i", "tests/objects.obj", "--center-on", "missing"];
+
+        match convert_args(&args) {
+            Err(CliError::ObjectNotFound(name)) => assert_eq!(name, "missing"),
+            result => panic!("Expected missing object, got {:?}", result),
+        }
+
+        let args = ["cli", "-i", "tests/bare.obj", "--max-grid-cells", "1"];
+
+        match convert_args(&args) {
+            Err(CliError::Convert(ConvertError::GridTooLarge { max_cells, .. })) => {
+                assert_eq!(max_cells, 1)
+            }
+            result => panic!("Expected too large grid, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_world_output_cell_size() {
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-world-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&folder).unwrap();
+
+        let output = folder.join("bare.dat");
+        let world_output = folder.join("world.csv");
+
+        let args = [
+            "cli",
+            "-i",
+            "tests/bare.obj",
+            "--cell-size",
+            "0.5",
+            "-o",
+            output.to_str().unwrap(),
+            "--world-output",
+            world_output.to_str().unwrap(),
+        ];
+
+        match parse(&args) {
+            Opt::Convert(opt) => convert(&opt).unwrap(),
+            _ => panic!("Expected convert subcommand"),
+        }
+
+        let points = load_points(&world_output).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        // Mesh spans 0 - 3, obstacles stay within a cell of it and are not scaled to cells.
+        assert!(!points.is_empty());
+        assert!(points
+            .iter()
+            .all(|p| p.iter().all(|c| (-0.5..=3.5).contains(c))));
+        assert!(points.iter().any(|p| p.x > 2.0));
+        assert!(points.iter().any(|p| p.x.fract() != 0.0));
+    }
+
+    #[test]
+    fn test_seed_origin() {
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-seed-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&folder).unwrap();
+
+        let seed = folder.join("seed.grid");
+        save_grid(&OccupancyGrid::new(16, 8), &seed).unwrap();
+
+        let run = |name: &str, size: &[&str]| {
+            let output = folder.join(format!("{}.dat", name));
+            let occupancy_output = folder.join(format!("{}.grid", name));
+
+            let mut args = vec![
+                "cli",
+                "-i",
+                "tests/bare.obj",
+                "--origin",
+                "0,0,0",
+                "-o",
+                output.to_str().unwrap(),
+                "--occupancy-output",
+                occupancy_output.to_str().unwrap(),
+            ];
+            args.extend_from_slice(size);
+
+            match parse(&args) {
+                Opt::Convert(opt) => convert(&opt).unwrap(),
+                _ => panic!("Expected convert subcommand"),
+            }
+
+            load_grid(&occupancy_output).unwrap()
+        };
+
+        let seeded = run("seeded", &["--seed", seed.to_str().unwrap()]);
+        let sized = run("sized", &["--width", "16", "--height", "8"]);
+        fs::remove_dir_all(&folder).unwrap();
+
+        // Seed size differs from the auto size, origin still maps to local (0, 0, 0).
+        assert_eq!((seeded.width(), seeded.height()), (16, 8));
+        assert!(seeded.is_obstacle(0, 0, 0));
+        assert_eq!(seeded.obstacles(), sized.obstacles());
+    }
+
+    #[test]
+    fn test_extra_obstacles_out_of_bounds() {
+        let path = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-extra-{}.csv",
+            std::process::id()
+        ));
+        fs::write(&path, "x,y,z\n1,2,0\n-1,0,0\n0,4,1\n").unwrap();
+
+        let load = |out_of_bounds: OutOfBounds, world: bool| {
+            let options = ConvertOptions {
+                out_of_bounds,
+                ..ConvertOptions::default()
+            };
+
+            load_extra_obstacles(&path, world, Vector3::zeros(), 4, 2, &options)
+        };
+
+        let clamped = load(OutOfBounds::Clamp, false).unwrap();
+        let dropped = load(OutOfBounds::Drop, false).unwrap();
+        let rejected = load(OutOfBounds::Error, false);
+
+        // Grid is centered on the origin, so only the second point is inside in world units.
+        let world = load(OutOfBounds::Drop, true).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            clamped,
+            vec![
+                LocalVector::new(1, 2, 0),
+                LocalVector::new(0, 0, 0),
+                LocalVector::new(0, 4, 1)
+            ]
+        );
+        assert_eq!(dropped, vec![LocalVector::new(1, 2, 0)]);
+        assert!(matches!(rejected, Err(CliError::Read { .. })));
+        assert_eq!(world, vec![LocalVector::new(1, 2, 1)]);
+    }
+
+    #[test]
+    fn test_read_input() {
+        // Stands in for obj piped to `--input -`.
+        let stdin = b"v 0 0 0\nv 4 0 0\nv 0 4 0\nl 1 2\nf 1 2 3\n";
+
+        let opt = match parse(&["cli", "-i", "-"]) {
+            Opt::Convert(opt) => opt,
+            _ => panic!("Expected convert subcommand"),
+        };
+
+        assert_eq!(opt.input, PathBuf::from("-"));
+
+        let obj = read_input(&stdin[..]).unwrap();
+        let (triangles, _) = load_triangles(&obj, &opt.mesh).unwrap();
+
+        assert_eq!(triangles.len(), 1);
+
+        let bounding_box = bounding_box(&triangles);
+        let (center, width, height, _) =
+            grid_parameters(&opt.grid, &triangles, &bounding_box, None, None);
+
+        let mut obstacles = Vec::new();
+
+        voxelize_triangle(&triangles[0], &center, width, height, 1.0, &mut |o| {
+            obstacles.push(o)
+        });
+
+        let grid = OccupancyGrid::from_obstacles(&obstacles, width, height);
+
+        assert_eq!((width, height), (6, 2));
+        assert!(grid.obstacle_count() > 0);
+    }
+
+    #[test]
+    fn test_batch() {
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-batch-{}",
+            std::process::id()
+        ));
+        let input_dir = folder.join("input");
+        let output_dir = folder.join("output");
+
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&input_dir).unwrap();
+
+        fs::copy("tests/bare.obj", input_dir.join("bare.obj")).unwrap();
+        fs::copy("tests/objects.obj", input_dir.join("objects.obj")).unwrap();
+        fs::write(input_dir.join("notes.txt"), "not a mesh").unwrap();
+
+        // Folder can't be read as a file, so its conversion fails.
+        fs::create_dir(input_dir.join("broken.obj")).unwrap();
+
+        let opt = match parse(&[
+            "cli",
+            "batch",
+            "--input-dir",
+            input_dir.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--",
+            "--cell-size",
+            "0.5",
+        ]) {
+            Opt::Batch(opt) => opt,
+            _ => panic!("Expected batch subcommand"),
+        };
+
+        assert_eq!(opt.args, vec!["--cell-size", "0.5"]);
+        assert_eq!(batch_inputs(&input_dir).unwrap().len(), 3);
+
+        let failed = batch(&opt).unwrap();
+
+        let mut outputs: Vec<_> = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        outputs.sort();
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(failed, 1);
+        assert_eq!(
+            outputs,
+            vec!["bare.dat", "bare.json", "objects.dat", "objects.json"]
+        );
+    }
+
+    #[test]
+    fn test_batch_missing_input_dir() {
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-batch-missing-{}",
+            std::process::id()
+        ));
+        let input_dir = folder.join("input");
+        let output_dir = folder.join("output");
+
+        let _ = fs::remove_dir_all(&folder);
+
+        let opt = match parse(&[
+            "cli",
+            "batch",
+            "--input-dir",
+            input_dir.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+        ]) {
+            Opt::Batch(opt) => opt,
+            _ => panic!("Expected batch subcommand"),
+        };
+
+        match batch(&opt) {
+            Err(CliError::Read { path, .. }) => assert_eq!(path, input_dir),
+            result => panic!("Expected read error, got {:?}", result),
+        }
+
+        // Nothing is created if inputs can't be listed.
+        assert!(!folder.exists());
     }
 }
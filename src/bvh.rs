@@ -0,0 +1,172 @@
+use crate::geometry::{BoundingBox, Triangle};
+use nalgebra::Vector3;
+
+/// Most triangles kept in a single leaf.
+const LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy of triangles, finds triangles near a region without testing
+/// all of them. Build it once and reuse it for every conversion of the same mesh.
+pub struct Bvh {
+    triangles: Vec<Triangle>,
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    bounding_box: BoundingBox,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    /// Range of triangles of the leaf.
+    Leaf { start: usize, end: usize },
+    /// Indices of child nodes.
+    Inner { left: usize, right: usize },
+}
+
+impl Bvh {
+    /// Triangles are split in halves along the longest axis of their centroids,
+    /// until leaves have at most `LEAF_SIZE` triangles.
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let mut bvh = Bvh {
+            triangles,
+            nodes: Vec::new(),
+        };
+
+        if !bvh.triangles.is_empty() {
+            bvh.build(0, bvh.triangles.len());
+        }
+
+        bvh
+    }
+
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
+    /// Triangles which bounding boxes intersect given region, in order of the hierarchy.
+    pub fn query(&self, region: &BoundingBox) -> Vec<&Triangle> {
+        let mut found = Vec::new();
+        let mut stack = Vec::new();
+
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+
+            if !node.bounding_box.intersects(region) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { start, end } => found.extend(
+                    self.triangles[start..end]
+                        .iter()
+                        .filter(|t| t.bounding_box().intersects(region)),
+                ),
+                NodeKind::Inner { left, right } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Builds node of triangles in given range and returns its index.
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let bounding_box = enclosing_box(&self.triangles[start..end]);
+        let index = self.nodes.len();
+
+        self.nodes.push(Node {
+            bounding_box,
+            kind: NodeKind::Leaf { start, end },
+        });
+
+        if end - start <= LEAF_SIZE {
+            return index;
+        }
+
+        let centroids: Vec<Vector3<f32>> = self.triangles[start..end]
+            .iter()
+            .map(|t| t.centroid())
+            .collect();
+
+        let min = centroids
+            .iter()
+            .fold(centroids[0], |m, c| m.zip_map(c, f32::min));
+        let max = centroids
+            .iter()
+            .fold(centroids[0], |m, c| m.zip_map(c, f32::max));
+        let axis = (max - min).imax();
+
+        self.triangles[start..end].sort_by(|a, b| {
+            let a = a.centroid()[axis];
+            let b = b.centroid()[axis];
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let middle = start + (end - start) / 2;
+
+        let left = self.build(start, middle);
+        let right = self.build(middle, end);
+
+        self.nodes[index].kind = NodeKind::Inner { left, right };
+
+        index
+    }
+}
+
+/// Box enclosing bounding boxes of all triangles, slice must not be empty.
+fn enclosing_box(triangles: &[Triangle]) -> BoundingBox {
+    let first = triangles[0].bounding_box();
+
+    triangles[1..]
+        .iter()
+        .map(|t| t.bounding_box())
+        .fold(first, |enclosing, b| {
+            BoundingBox::new(
+                enclosing.min.zip_map(&b.min, f32::min),
+                enclosing.max.zip_map(&b.max, f32::max),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bvh::Bvh;
+    use crate::geometry::BoundingBox;
+    use crate::testutil::generate_sphere;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_query() {
+        let triangles = generate_sphere(10.0, 3);
+        let bvh = Bvh::new(generate_sphere(10.0, 3));
+
+        assert_eq!(bvh.triangles().len(), triangles.len());
+
+        let region = BoundingBox::new(Vector3::new(2.0, -3.0, 4.0), Vector3::new(12.0, 1.0, 6.0));
+
+        let mut found: Vec<_> = bvh.query(&region).into_iter().map(|t| t.a).collect();
+        let mut expected: Vec<_> = triangles
+            .iter()
+            .filter(|t| t.bounding_box().intersects(&region))
+            .map(|t| t.a)
+            .collect();
+
+        let by_position =
+            |a: &Vector3<f32>, b: &Vector3<f32>| a.as_slice().partial_cmp(b.as_slice()).unwrap();
+
+        found.sort_by(by_position);
+        expected.sort_by(by_position);
+
+        assert!(!expected.is_empty());
+        assert!(expected.len() < triangles.len());
+        assert_eq!(found, expected);
+
+        assert!(Bvh::new(Vec::new()).query(&region).is_empty());
+    }
+}
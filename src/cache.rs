@@ -0,0 +1,325 @@
+use crate::error::ConvertError;
+use crate::geometry::{LocalVector, Triangle};
+use crate::{
+    check_grid_size, create_grid, dedup_obstacles, voxelize, ConvertOptions, OutOfBounds,
+    Preprocessor, Progress, VoxelMode, WallThickness,
+};
+use flying_pathfinding::Grid;
+use nalgebra::Vector3;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Vertices closer than `1 / QUANTIZATION` are cached as the same vertex.
+const QUANTIZATION: f32 = 1024.0;
+
+/// Remembers obstacles of converted meshes, so converting the same triangles with the same
+/// parameters again skips voxelization. Preprocessor is owned by the cache, because it
+/// can't be a part of the key. For the same reason obstacles are cached before
+/// `ConvertOptions::obstacle_filter` is applied. Every entry also keeps its quantized
+/// triangles, 72 bytes per triangle, to tell apart meshes with the same hash.
+pub struct ConversionCache<Pre: Preprocessor> {
+    preprocessor: Pre,
+    entries: HashMap<CacheKey, Vec<LocalVector>, BuildHasherDefault<FnvHasher>>,
+    hits: usize,
+    misses: usize,
+}
+
+impl<Pre: Preprocessor> ConversionCache<Pre> {
+    pub fn new(preprocessor: Pre) -> Self {
+        ConversionCache {
+            preprocessor,
+            entries: HashMap::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Same as `convert_with_options`, but returns cached obstacles if they are known.
+    /// Failed conversions aren't cached.
+    pub fn convert<Prg: Progress>(
+        &mut self,
+        triangles: Vec<Triangle>,
+        center: Vector3<f32>,
+        width: u32,
+        height: u32,
+        progress: Prg,
+        options: &ConvertOptions,
+    ) -> Result<Grid, ConvertError> {
+        // Limit isn't a part of the key, so it's checked even if obstacles are cached.
+        check_grid_size(width, height, options.max_cells)?;
+
+        let key = CacheKey::new(&triangles, center, width, height, options);
+
+        let obstacles = match self.entries.entry(key) {
+            Entry::Occupied(entry) => {
+                self.hits += 1;
+                progress.update_progress(100.0);
+
+                entry.into_mut()
+            }
+            Entry::Vacant(entry) => {
+                self.misses += 1;
+
+                let unfiltered_options = ConvertOptions {
+                    obstacle_filter: None,
+                    ..*options
+                };
+
+                let obstacles = voxelize(
+                    triangles,
+                    center,
+                    width,
+                    height,
+                    progress,
+                    &self.preprocessor,
+                    &unfiltered_options,
+                )?;
+
+                entry.insert(dedup_obstacles(obstacles))
+            }
+        };
+
+        let grid = match options.obstacle_filter {
+            Some(filter) => {
+                let obstacles: Vec<LocalVector> =
+                    obstacles.iter().copied().filter(|&o| filter(o)).collect();
+
+                create_grid(&obstacles, width, height)
+            }
+            None => create_grid(obstacles, width, height),
+        };
+
+        Ok(grid)
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Everything obstacles depend on, compared in full, so different meshes with the same hash
+/// never share obstacles. Vertices and center are quantized by `QUANTIZATION`. Count of threads
+/// doesn't change obstacles, so it isn't a part of the key.
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    triangles: Vec<[i64; 9]>,
+    /// Center after `origin` is applied, so conversions with different origins don't share
+    /// obstacles.
+    center: [i64; 3],
+    width: u32,
+    height: u32,
+    epsilon: u32,
+    out_of_bounds: OutOfBounds,
+    mode: VoxelMode,
+    wall_thickness: WallThickness,
+    ensure_nonempty: bool,
+    scan_padding: u32,
+    cell_size_xy: u32,
+    cell_size_z: u32,
+}
+
+impl CacheKey {
+    fn new(
+        triangles: &[Triangle],
+        center: Vector3<f32>,
+        width: u32,
+        height: u32,
+        options: &ConvertOptions,
+    ) -> Self {
+        let triangles = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = [quantize(&t.a), quantize(&t.b), quantize(&t.c)];
+                [a[0], a[1], a[2], b[0], b[1], b[2], c[0], c[1], c[2]]
+            })
+            .collect();
+
+        CacheKey {
+            triangles,
+            center: quantize(&options.grid_center(center, width, height)),
+            width,
+            height,
+            epsilon: options.epsilon.to_bits(),
+            out_of_bounds: options.out_of_bounds,
+            mode: options.mode,
+            wall_thickness: options.wall_thickness,
+            ensure_nonempty: options.ensure_nonempty,
+            scan_padding: options.scan_padding,
+            cell_size_xy: options.cell_size_xy.to_bits(),
+            cell_size_z: options.cell_size_z.to_bits(),
+        }
+    }
+}
+
+fn quantize(vector: &Vector3<f32>) -> [i64; 3] {
+    let component = |c: f32| (c * QUANTIZATION).round() as i64;
+
+    [
+        component(vector.x),
+        component(vector.y),
+        component(vector.z),
+    ]
+}
+
+/// 64 bit FNV-1a, much faster than default hasher for short keys like vertex components.
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::{CacheKey, ConversionCache};
+    use crate::error::ConvertError;
+    use crate::geometry::Triangle;
+    use crate::testutil::NoOpProgress;
+    use crate::{ConvertOptions, NoOpPreprocessor};
+    use nalgebra::Vector3;
+
+    fn triangles() -> Vec<Triangle> {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(5.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        vec![Triangle::new(a, b, c)]
+    }
+
+    #[test]
+    fn test_conversion_cache() {
+        let mut cache = ConversionCache::new(NoOpPreprocessor {});
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        cache
+            .convert(triangles(), center, 10, 10, NoOpProgress {}, &options)
+            .unwrap();
+        cache
+            .convert(triangles(), center, 10, 10, NoOpProgress {}, &options)
+            .unwrap();
+
+        assert_eq!(cache.misses(), 1);
+        assert_eq!(cache.hits(), 1);
+
+        let changed_options = ConvertOptions {
+            epsilon: 0.1,
+            ..ConvertOptions::default()
+        };
+
+        cache
+            .convert(
+                triangles(),
+                center,
+                10,
+                10,
+                NoOpProgress {},
+                &changed_options,
+            )
+            .unwrap();
+        cache
+            .convert(triangles(), center, 12, 10, NoOpProgress {}, &options)
+            .unwrap();
+
+        assert_eq!(cache.misses(), 3);
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_conversion_cache_origin_and_limit() {
+        let mut cache = ConversionCache::new(NoOpPreprocessor {});
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let convert = |cache: &mut ConversionCache<NoOpPreprocessor>, options: &ConvertOptions| {
+            cache.convert(triangles(), center, 10, 10, NoOpProgress {}, options)
+        };
+
+        convert(&mut cache, &ConvertOptions::default()).unwrap();
+
+        let moved_options = ConvertOptions {
+            origin: Some(Vector3::new(-2.0, -2.0, -2.0)),
+            ..ConvertOptions::default()
+        };
+
+        convert(&mut cache, &moved_options).unwrap();
+
+        assert_eq!(cache.misses(), 2);
+        assert_eq!(cache.hits(), 0);
+
+        // Cached obstacles don't skip the cell limit.
+        let limited_options = ConvertOptions {
+            max_cells: 10,
+            ..ConvertOptions::default()
+        };
+
+        assert_eq!(
+            convert(&mut cache, &limited_options).err(),
+            Some(ConvertError::GridTooLarge {
+                width: 10,
+                height: 10,
+                max_cells: 10,
+            })
+        );
+        assert_eq!(cache.hits(), 0);
+    }
+
+    #[test]
+    fn test_conversion_cache_full_key() {
+        let mut cache = ConversionCache::new(NoOpPreprocessor {});
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        let mut other = triangles();
+        other[0].b = Vector3::new(5.0, -5.0, 0.0);
+
+        let key = CacheKey::new(&triangles(), center, 10, 10, &options);
+        let other_key = CacheKey::new(&other, center, 10, 10, &options);
+
+        assert!(key == CacheKey::new(&triangles(), center, 10, 10, &options));
+        assert!(key != other_key);
+
+        let grid = |cache: &mut ConversionCache<NoOpPreprocessor>, triangles| {
+            cache
+                .convert(triangles, center, 10, 10, NoOpProgress {}, &options)
+                .unwrap()
+        };
+
+        grid(&mut cache, triangles());
+        grid(&mut cache, other);
+
+        assert_eq!(cache.misses(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+}
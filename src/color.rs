@@ -0,0 +1,165 @@
+use crate::geometry::{LocalVector, Triangle};
+use crate::{face_corners, find_obstacles_with_options, ConvertOptions, GZIP_MAGIC};
+use flate2::read::GzDecoder;
+use nalgebra::Vector3;
+use obj::Obj;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Vertex color, components are usually between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Color {
+    pub fn new(r: f32, g: f32, b: f32) -> Self {
+        Color { r, g, b }
+    }
+}
+
+/// Reads colors of vertices stored by some exporters after their position, `v x y z r g b`.
+/// Returns one entry per vertex in order of the file, `None` for vertices without color.
+pub fn read_vertex_colors<R: BufRead>(reader: R) -> io::Result<Vec<Option<Color>>> {
+    let mut colors = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+
+        if words.next() != Some("v") {
+            continue;
+        }
+
+        let components: Vec<f32> = words.filter_map(|w| w.parse().ok()).collect();
+
+        colors.push(match components.as_slice() {
+            [_, _, _, r, g, b, ..] => Some(Color::new(*r, *g, *b)),
+            _ => None,
+        });
+    }
+
+    Ok(colors)
+}
+
+/// Same as `read_vertex_colors`, but reads obj file, which may be gzip compressed
+/// like in `load_obj`.
+pub fn load_vertex_colors<P: AsRef<Path>>(path: P) -> io::Result<Vec<Option<Color>>> {
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+
+    if bytes.starts_with(&GZIP_MAGIC) {
+        read_vertex_colors(BufReader::new(GzDecoder::new(bytes.as_slice())))
+    } else {
+        read_vertex_colors(bytes.as_slice())
+    }
+}
+
+/// Same triangles as `parse_triangles`, each with average color of its vertices.
+/// Color is `None` unless all three vertices have one, so meshes without colors still convert.
+pub fn colored_triangles(obj: &Obj, colors: &[Option<Color>]) -> Vec<(Triangle, Option<Color>)> {
+    face_corners(obj, &[], &[])
+        .filter(|(_, triangle)| triangle.is_finite())
+        .map(|(corners, triangle)| (triangle, average_color(&corners, colors)))
+        .collect()
+}
+
+fn average_color(corners: &[usize; 3], colors: &[Option<Color>]) -> Option<Color> {
+    let mut sum = Color::new(0.0, 0.0, 0.0);
+
+    for &corner in corners {
+        let color = colors.get(corner).copied().flatten()?;
+
+        sum.r += color.r;
+        sum.g += color.g;
+        sum.b += color.b;
+    }
+
+    Some(Color::new(sum.r / 3.0, sum.g / 3.0, sum.b / 3.0))
+}
+
+/// Obstacles of colored triangles tagged by their color, sorted and without duplicates.
+/// Cell shared by several triangles gets color of the first one, triangles without color
+/// don't tag any cell. Triangles are in grid units like in `voxelize`.
+pub fn voxelize_tagged(
+    triangles: &[(Triangle, Option<Color>)],
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Vec<(LocalVector, Color)> {
+    let center = options.grid_center(center, width, height);
+    let mut tagged = Vec::new();
+
+    for (triangle, color) in triangles {
+        if let Some(color) = color {
+            let triangle = options.to_cells(triangle);
+            let obstacles = find_obstacles_with_options(&triangle, &center, width, height, options);
+            tagged.extend(obstacles.into_iter().map(|o| (o, *color)));
+        }
+    }
+
+    // Stable sort keeps cells of earlier triangles first.
+    tagged.sort_by_key(|(cell, _)| *cell);
+    tagged.dedup_by_key(|(cell, _)| *cell);
+
+    tagged
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{colored_triangles, load_vertex_colors, voxelize_tagged, Color};
+    use crate::ConvertOptions;
+    use nalgebra::Vector3;
+    use obj::Obj;
+
+    #[test]
+    fn test_voxelize_tagged() {
+        let obj = Obj::load("tests/colored.obj").unwrap();
+        let colors = load_vertex_colors("tests/colored.obj").unwrap();
+
+        let red = Color::new(1.0, 0.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 0.5);
+
+        assert_eq!(colors.len(), 7);
+        assert_eq!(colors[0], Some(red));
+        assert_eq!(colors[6], None);
+
+        let triangles = colored_triangles(&obj, &colors);
+
+        assert_eq!(triangles.len(), 3);
+        assert_eq!(triangles[0].1, Some(red));
+        assert_eq!(triangles[1].1, Some(blue));
+        assert_eq!(triangles[2].1, None);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+        let tagged = voxelize_tagged(&triangles, center, 16, 16, &options);
+
+        assert!(!tagged.is_empty());
+        assert!(tagged.windows(2).all(|w| w[0].0 < w[1].0));
+
+        // Red triangle lies at z = 0, the blue one at z = 4, the last one has no color.
+        for (cell, color) in &tagged {
+            match cell.z {
+                8 => assert_eq!(*color, red),
+                12 => assert_eq!(*color, blue),
+                _ => panic!("Unexpected tagged cell {:?}", cell),
+            }
+        }
+
+        // Mesh without colors isn't tagged at all.
+        let obj = Obj::load("tests/bare.obj").unwrap();
+        let colors = load_vertex_colors("tests/bare.obj").unwrap();
+
+        assert!(colors.iter().all(|c| c.is_none()));
+        let triangles = colored_triangles(&obj, &colors);
+
+        assert_eq!(triangles.len(), 2);
+        assert!(voxelize_tagged(&triangles, center, 16, 16, &options).is_empty());
+    }
+}
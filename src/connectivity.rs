@@ -0,0 +1,416 @@
+use crate::occupancy::OccupancyGrid;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Connectivity {
+    /// Cells are connected only through shared faces.
+    Six,
+    /// Cells are connected through shared faces, edges and corners.
+    TwentySix,
+}
+
+impl Connectivity {
+    pub(crate) fn offsets(self) -> Vec<(i32, i32, i32)> {
+        let mut offsets = Vec::new();
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let distance = i32::abs(dx) + i32::abs(dy) + i32::abs(dz);
+
+                    let connected = match self {
+                        Connectivity::Six => distance == 1,
+                        Connectivity::TwentySix => distance > 0,
+                    };
+
+                    if connected {
+                        offsets.push((dx, dy, dz));
+                    }
+                }
+            }
+        }
+
+        offsets
+    }
+}
+
+impl FromStr for Connectivity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "six" => Ok(Connectivity::Six),
+            "twenty-six" => Ok(Connectivity::TwentySix),
+            _ => Err(format!("Unknown connectivity: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub connectivity: Connectivity,
+    /// Sizes of free space components in cells, ordered by their first cell.
+    pub component_sizes: Vec<usize>,
+}
+
+impl Report {
+    pub fn component_count(&self) -> usize {
+        self.component_sizes.len()
+    }
+}
+
+pub fn connectivity_report(grid: &OccupancyGrid, connectivity: Connectivity) -> Report {
+    let (_, component_sizes) = label_free_space(grid, connectivity);
+
+    Report {
+        connectivity,
+        component_sizes,
+    }
+}
+
+/// Labels free space cells by their component index, obstacles get `None`.
+/// Returns labels indexed like grid cells and size of every component.
+pub(crate) fn label_free_space(
+    grid: &OccupancyGrid,
+    connectivity: Connectivity,
+) -> (Vec<Option<usize>>, Vec<usize>) {
+    let offsets = connectivity.offsets();
+
+    let mut labels = vec![None; grid.len()];
+    let mut component_sizes = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for start in 0..grid.len() {
+        let position = grid.position(start);
+
+        if labels[start].is_some() || grid.is_obstacle(position.x, position.y, position.z) {
+            continue;
+        }
+
+        let label = component_sizes.len();
+        let mut size = 0;
+
+        labels[start] = Some(label);
+        queue.push_back(position);
+
+        while let Some(current) = queue.pop_front() {
+            size += 1;
+
+            for (dx, dy, dz) in &offsets {
+                let x = current.x as i32 + dx;
+                let y = current.y as i32 + dy;
+                let z = current.z as i32 + dz;
+
+                if x < 0 || y < 0 || z < 0 {
+                    continue;
+                }
+
+                let (x, y, z) = (x as u32, y as u32, z as u32);
+
+                if !grid.contains(x, y, z) || grid.is_obstacle(x, y, z) {
+                    continue;
+                }
+
+                let index = grid.index(x, y, z);
+
+                if labels[index].is_none() {
+                    labels[index] = Some(label);
+                    queue.push_back(grid.position(index));
+                }
+            }
+        }
+
+        component_sizes.push(size);
+    }
+
+    (labels, component_sizes)
+}
+
+/// Fills every free space component except the largest one, like closed pockets inside walls
+/// which agents can never reach. Of equally large components the one with the first cell is
+/// kept. `Connectivity::Six` is the usual choice, as agents can't pass between cells touching
+/// only at an edge or corner. Returns count of filled cells.
+pub fn keep_largest_free_region(grid: &mut OccupancyGrid, connectivity: Connectivity) -> usize {
+    let (labels, component_sizes) = label_free_space(grid, connectivity);
+
+    // `max_by_key` returns the last of equal elements, so components are iterated in reverse.
+    let largest = match component_sizes
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, s)| **s)
+    {
+        Some((largest, _)) => largest,
+        None => return 0,
+    };
+
+    let mut filled = 0;
+
+    for (index, label) in labels.into_iter().enumerate() {
+        if label.map_or(false, |label| label != largest) {
+            let position = grid.position(index);
+
+            grid.set_obstacle(position.x, position.y, position.z);
+            filled += 1;
+        }
+    }
+
+    filled
+}
+
+/// Fills cells between obstacles touching only at an edge or corner, so agents moving
+/// diagonally can't pass between them. Every such pair gets connected through shared faces
+/// by filling cells along x, then y, then z axis, so one cell for edge and two for corner.
+/// Returns count of filled cells, calling it again on the same grid fills nothing.
+pub fn seal_diagonal_gaps(grid: &mut OccupancyGrid) -> usize {
+    let offsets: Vec<(i32, i32, i32)> = Connectivity::TwentySix
+        .offsets()
+        .into_iter()
+        .filter(|(dx, dy, dz)| i32::abs(*dx) + i32::abs(*dy) + i32::abs(*dz) > 1)
+        .collect();
+
+    let mut filled = 0;
+    let mut changed = true;
+
+    // Filled cells may form new diagonal pairs, so repeat until nothing changes.
+    while changed {
+        changed = false;
+
+        for index in 0..grid.len() {
+            let position = grid.position(index);
+            let (x, y, z) = (position.x as i32, position.y as i32, position.z as i32);
+
+            if !grid.is_obstacle(position.x, position.y, position.z) {
+                continue;
+            }
+
+            for (dx, dy, dz) in &offsets {
+                if !is_obstacle(grid, x + dx, y + dy, z + dz) {
+                    continue;
+                }
+
+                if is_face_connected(grid, (x, y, z), (*dx, *dy, *dz)) {
+                    continue;
+                }
+
+                let path = [
+                    (x + dx, y, z),
+                    (x + dx, y + dy, z),
+                    (x + dx, y + dy, z + dz),
+                ];
+
+                for (px, py, pz) in path.iter() {
+                    if !is_obstacle(grid, *px, *py, *pz) {
+                        grid.set_obstacle(*px as u32, *py as u32, *pz as u32);
+                        filled += 1;
+                    }
+                }
+
+                changed = true;
+            }
+        }
+    }
+
+    filled
+}
+
+/// Removes obstacles with less than `min_neighbors` obstacles among their 26 neighbours, which
+/// are usually noise of thin or degenerate geometry. Neighbours are counted before anything is
+/// removed, so the result doesn't depend on order of cells. Returns count of removed cells.
+pub fn remove_isolated(grid: &mut OccupancyGrid, min_neighbors: usize) -> usize {
+    let offsets = Connectivity::TwentySix.offsets();
+
+    let isolated: Vec<_> = grid
+        .obstacles()
+        .into_iter()
+        .filter(|position| {
+            let (x, y, z) = (position.x as i32, position.y as i32, position.z as i32);
+            let neighbors = offsets
+                .iter()
+                .filter(|(dx, dy, dz)| is_obstacle(grid, x + dx, y + dy, z + dz))
+                .count();
+
+            neighbors < min_neighbors
+        })
+        .collect();
+
+    for position in &isolated {
+        grid.remove_obstacle(position.x, position.y, position.z);
+    }
+
+    isolated.len()
+}
+
+fn is_obstacle(grid: &OccupancyGrid, x: i32, y: i32, z: i32) -> bool {
+    x >= 0 && y >= 0 && z >= 0 && grid.is_obstacle(x as u32, y as u32, z as u32)
+}
+
+/// Checks if obstacle at start reaches obstacle at start + offset through shared faces
+/// of obstacles inside their bounding box.
+fn is_face_connected(
+    grid: &OccupancyGrid,
+    start: (i32, i32, i32),
+    offset: (i32, i32, i32),
+) -> bool {
+    let (x, y, z) = start;
+    let (dx, dy, dz) = offset;
+
+    let mut visited = vec![(0, 0, 0)];
+    let mut stack = vec![(0, 0, 0)];
+
+    while let Some((cx, cy, cz)) = stack.pop() {
+        if (cx, cy, cz) == offset {
+            return true;
+        }
+
+        // Steps towards the target along every axis where it's not reached yet.
+        let steps = [(dx - cx, 0, 0), (0, dy - cy, 0), (0, 0, dz - cz)];
+
+        for (sx, sy, sz) in steps.iter() {
+            let next = (cx + sx, cy + sy, cz + sz);
+
+            if (*sx, *sy, *sz) == (0, 0, 0) || visited.contains(&next) {
+                continue;
+            }
+
+            if is_obstacle(grid, x + next.0, y + next.1, z + next.2) {
+                visited.push(next);
+                stack.push(next);
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connectivity::{
+        connectivity_report, keep_largest_free_region, remove_isolated, seal_diagonal_gaps,
+        Connectivity,
+    };
+    use crate::occupancy::OccupancyGrid;
+
+    #[test]
+    fn test_diagonal_wall() {
+        let mut grid = OccupancyGrid::new(4, 1);
+
+        grid.set_obstacle(0, 3, 0);
+        grid.set_obstacle(1, 2, 0);
+        grid.set_obstacle(2, 1, 0);
+        grid.set_obstacle(3, 0, 0);
+
+        let six = connectivity_report(&grid, Connectivity::Six);
+        let twenty_six = connectivity_report(&grid, Connectivity::TwentySix);
+
+        assert_eq!(six.component_count(), 2);
+        assert_eq!(six.component_sizes, vec![6, 6]);
+        assert_eq!(twenty_six.component_count(), 1);
+        assert_eq!(twenty_six.component_sizes, vec![12]);
+    }
+
+    #[test]
+    fn test_empty_grid() {
+        let grid = OccupancyGrid::new(3, 3);
+        let report = connectivity_report(&grid, Connectivity::Six);
+
+        assert_eq!(report.component_sizes, vec![27]);
+    }
+
+    #[test]
+    fn test_seal_diagonal_gaps() {
+        let mut grid = OccupancyGrid::new(4, 1);
+
+        grid.set_obstacle(0, 3, 0);
+        grid.set_obstacle(1, 2, 0);
+        grid.set_obstacle(2, 1, 0);
+        grid.set_obstacle(3, 0, 0);
+
+        assert_eq!(seal_diagonal_gaps(&mut grid), 3);
+
+        assert!(grid.is_obstacle(0, 2, 0));
+        assert!(grid.is_obstacle(1, 1, 0));
+        assert!(grid.is_obstacle(2, 0, 0));
+
+        let report = connectivity_report(&grid, Connectivity::TwentySix);
+
+        assert_eq!(report.component_count(), 2);
+        assert_eq!(seal_diagonal_gaps(&mut grid), 0);
+    }
+
+    #[test]
+    fn test_seal_corner_gap() {
+        let mut grid = OccupancyGrid::new(2, 2);
+
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(1, 1, 1);
+
+        assert_eq!(seal_diagonal_gaps(&mut grid), 2);
+
+        assert!(grid.is_obstacle(1, 0, 0));
+        assert!(grid.is_obstacle(1, 1, 0));
+        assert_eq!(seal_diagonal_gaps(&mut grid), 0);
+    }
+
+    #[test]
+    fn test_remove_isolated() {
+        let mut grid = OccupancyGrid::new(6, 2);
+
+        grid.set_obstacle(5, 5, 1);
+
+        for x in 0..2 {
+            for y in 0..2 {
+                grid.set_obstacle(x, y, 0);
+            }
+        }
+
+        assert_eq!(remove_isolated(&mut grid, 1), 1);
+        assert!(!grid.is_obstacle(5, 5, 1));
+        assert_eq!(grid.obstacle_count(), 4);
+        assert_eq!(remove_isolated(&mut grid, 1), 0);
+
+        // Every cell of the cluster has three neighbours.
+        assert_eq!(remove_isolated(&mut grid, 4), 4);
+        assert_eq!(grid.obstacle_count(), 0);
+    }
+
+    #[test]
+    fn test_keep_largest_free_region() {
+        let pocket_grid = || {
+            let mut grid = OccupancyGrid::new(8, 4);
+
+            // Walls around a single cell, its edges and corners stay open.
+            for (dx, dy, dz) in Connectivity::Six.offsets() {
+                grid.set_obstacle((6 + dx) as u32, (6 + dy) as u32, (2 + dz) as u32);
+            }
+
+            grid
+        };
+
+        let mut grid = pocket_grid();
+
+        assert_eq!(keep_largest_free_region(&mut grid, Connectivity::Six), 1);
+        assert!(grid.is_obstacle(6, 6, 2));
+        assert_eq!(grid.obstacle_count(), 7);
+
+        let report = connectivity_report(&grid, Connectivity::Six);
+
+        assert_eq!(report.component_sizes, vec![8 * 8 * 4 - 7]);
+        assert_eq!(keep_largest_free_region(&mut grid, Connectivity::Six), 0);
+
+        // Pocket reaches the cavity through corners.
+        let mut grid = pocket_grid();
+
+        assert_eq!(
+            keep_largest_free_region(&mut grid, Connectivity::TwentySix),
+            0
+        );
+        assert!(!grid.is_obstacle(6, 6, 2));
+
+        let mut grid = OccupancyGrid::new(2, 2);
+        grid.invert();
+
+        assert_eq!(keep_largest_free_region(&mut grid, Connectivity::Six), 0);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::occupancy::OccupancyGrid;
+
+/// Euclidean distance in cells from every cell to the nearest obstacle, indexed like cells of
+/// the grid, x changes fastest. Obstacles have distance 0, all cells of a grid without
+/// obstacles have infinite distance. Exact squared distances are found by three passes of
+/// one dimensional transform, one per axis, so time is linear in count of cells.
+pub fn distance_field(grid: &OccupancyGrid) -> Vec<f32> {
+    let width = grid.width() as usize;
+    let height = grid.height() as usize;
+
+    let mut squared: Vec<f32> = (0..grid.len())
+        .map(|index| {
+            let cell = grid.position(index);
+
+            if grid.is_obstacle(cell.x, cell.y, cell.z) {
+                0.0
+            } else {
+                f32::INFINITY
+            }
+        })
+        .collect();
+
+    // Stride and length of lines along x, y and z axes.
+    let axes = [
+        (1, width, width * height),
+        (width, width, width * height),
+        (width * width, height, width * width),
+    ];
+
+    let mut line = Vec::new();
+    let mut transformed = Vec::new();
+
+    for &(stride, length, line_count) in axes.iter() {
+        if length == 0 {
+            continue;
+        }
+
+        for line_index in 0..line_count {
+            let start = line_start(line_index, stride, width);
+
+            line.clear();
+            line.extend((0..length).map(|i| squared[start + i * stride]));
+
+            transform_line(&line, &mut transformed);
+
+            for (i, value) in transformed.iter().enumerate() {
+                squared[start + i * stride] = *value;
+            }
+        }
+    }
+
+    squared.into_iter().map(f32::sqrt).collect()
+}
+
+/// Blocks every free cell closer than `radius` to the nearest obstacle, so agent of given radius
+/// can be treated as a point. Distances are measured between cell centers, radius and cell size
+/// are in world units.
+pub fn apply_clearance(grid: &mut OccupancyGrid, radius: f32, cell_size: f32) {
+    let field = distance_field(grid);
+
+    for (index, distance) in field.into_iter().enumerate() {
+        if distance * cell_size < radius {
+            let cell = grid.position(index);
+            grid.set_obstacle(cell.x, cell.y, cell.z);
+        }
+    }
+}
+
+/// Index of the first cell of line with given index among lines along axis with given stride.
+fn line_start(line_index: usize, stride: usize, width: usize) -> usize {
+    if stride == 1 {
+        // Lines along x: one per y and z.
+        line_index * width
+    } else if stride == width {
+        // Lines along y: one per x and z.
+        line_index % width + line_index / width * width * width
+    } else {
+        // Lines along z: one per x and y.
+        line_index
+    }
+}
+
+/// One dimensional squared distance transform of Felzenszwalb and Huttenlocher: every value
+/// becomes minimum of `values[q] + (p - q)^2` over all q, using lower envelope of parabolas.
+fn transform_line(values: &[f32], transformed: &mut Vec<f32>) {
+    transformed.clear();
+
+    // Positions of parabolas in the envelope and boundaries between them.
+    let mut parabolas: Vec<usize> = Vec::with_capacity(values.len());
+    let mut boundaries: Vec<f32> = Vec::with_capacity(values.len() + 1);
+
+    let intersection = |q: usize, p: usize| {
+        let (qf, pf) = (q as f32, p as f32);
+        ((values[q] + qf * qf) - (values[p] + pf * pf)) / (2.0 * (qf - pf))
+    };
+
+    for (q, value) in values.iter().enumerate() {
+        if value.is_infinite() {
+            continue;
+        }
+
+        while let Some(&last) = parabolas.last() {
+            if intersection(q, last) <= boundaries[boundaries.len() - 1] {
+                parabolas.pop();
+                boundaries.pop();
+            } else {
+                break;
+            }
+        }
+
+        boundaries.push(match parabolas.last() {
+            Some(&last) => intersection(q, last),
+            None => f32::NEG_INFINITY,
+        });
+        parabolas.push(q);
+    }
+
+    if parabolas.is_empty() {
+        transformed.extend(values.iter().map(|_| f32::INFINITY));
+        return;
+    }
+
+    let mut current = 0;
+
+    for p in 0..values.len() {
+        while current + 1 < parabolas.len() && boundaries[current + 1] < p as f32 {
+            current += 1;
+        }
+
+        let q = parabolas[current];
+        let offset = p as f32 - q as f32;
+
+        transformed.push(values[q] + offset * offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distance::{apply_clearance, distance_field};
+    use crate::occupancy::OccupancyGrid;
+
+    #[test]
+    fn test_single_obstacle() {
+        let mut grid = OccupancyGrid::new(7, 5);
+        grid.set_obstacle(2, 3, 1);
+
+        let field = distance_field(&grid);
+
+        assert_eq!(field.len(), grid.len());
+
+        for (index, distance) in field.iter().enumerate() {
+            let cell = grid.position(index);
+
+            let dx = cell.x as f32 - 2.0;
+            let dy = cell.y as f32 - 3.0;
+            let dz = cell.z as f32 - 1.0;
+            let expected = (dx * dx + dy * dy + dz * dz).sqrt();
+
+            assert!((distance - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_nearest_of_many() {
+        let mut grid = OccupancyGrid::new(6, 3);
+        let obstacles = [(0, 0, 0), (5, 5, 2), (3, 0, 2), (1, 4, 1)];
+
+        for (x, y, z) in obstacles.iter() {
+            grid.set_obstacle(*x, *y, *z);
+        }
+
+        let field = distance_field(&grid);
+
+        for (index, distance) in field.iter().enumerate() {
+            let cell = grid.position(index);
+
+            let expected = obstacles
+                .iter()
+                .map(|(x, y, z)| {
+                    let dx = cell.x as f32 - *x as f32;
+                    let dy = cell.y as f32 - *y as f32;
+                    let dz = cell.z as f32 - *z as f32;
+                    (dx * dx + dy * dy + dz * dz).sqrt()
+                })
+                .fold(f32::INFINITY, f32::min);
+
+            assert!((distance - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_no_obstacles() {
+        let field = distance_field(&OccupancyGrid::new(3, 2));
+
+        assert_eq!(field.len(), 18);
+        assert!(field.iter().all(|d| d.is_infinite()));
+    }
+
+    #[test]
+    fn test_apply_clearance() {
+        let mut grid = OccupancyGrid::new(7, 7);
+        grid.set_obstacle(3, 3, 3);
+
+        apply_clearance(&mut grid, 1.5, 1.0);
+
+        // Cells sharing a face or an edge are closer than 1.5, corners are sqrt(3) away.
+        assert_eq!(grid.obstacle_count(), 1 + 6 + 12);
+        assert!(grid.is_obstacle(4, 4, 3));
+        assert!(!grid.is_obstacle(4, 4, 4));
+        assert!(!grid.is_obstacle(5, 3, 3));
+
+        // Radius of 3 world units is 1.5 cells of size 2.
+        let mut grid = OccupancyGrid::new(7, 7);
+        grid.set_obstacle(3, 3, 3);
+
+        apply_clearance(&mut grid, 3.0, 2.0);
+
+        assert_eq!(grid.obstacle_count(), 1 + 6 + 12);
+    }
+}
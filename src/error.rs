@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum ConvertError {
+    /// Triangle with given index isn't fully inside of the grid.
+    OutOfBounds(usize),
+    /// Triangle with given index has NaN or infinite coordinate.
+    NonFinite(usize),
+    /// Given count of triangles wasn't fully inside of the grid and got clamped.
+    Clipped(usize),
+    /// Face with given index references vertex which doesn't exist.
+    MalformedFace {
+        face_index: usize,
+        vertex_index: usize,
+    },
+    /// Grid of given dimensions has more cells than allowed or than can be addressed.
+    GridTooLarge {
+        width: u32,
+        height: u32,
+        max_cells: u64,
+    },
+    /// Grid couldn't be written to given path, partially written file is removed.
+    Export { path: PathBuf, error: io::Error },
+    /// Thread pool for `ConvertOptions::threads` couldn't be built.
+    #[cfg(feature = "parallel")]
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+/// `io::Error` can't be compared, so export errors are equal if they have the same path and kind.
+/// Thread pool errors can't be compared either, so all of them are equal.
+impl PartialEq for ConvertError {
+    fn eq(&self, other: &Self) -> bool {
+        use ConvertError::*;
+
+        match (self, other) {
+            (OutOfBounds(a), OutOfBounds(b))
+            | (NonFinite(a), NonFinite(b))
+            | (Clipped(a), Clipped(b)) => a == b,
+            (
+                MalformedFace {
+                    face_index,
+                    vertex_index,
+                },
+                MalformedFace {
+                    face_index: other_face_index,
+                    vertex_index: other_vertex_index,
+                },
+            ) => face_index == other_face_index && vertex_index == other_vertex_index,
+            (
+                GridTooLarge {
+                    width,
+                    height,
+                    max_cells,
+                },
+                GridTooLarge {
+                    width: other_width,
+                    height: other_height,
+                    max_cells: other_max_cells,
+                },
+            ) => width == other_width && height == other_height && max_cells == other_max_cells,
+            (
+                Export { path, error },
+                Export {
+                    path: other_path,
+                    error: other_error,
+                },
+            ) => path == other_path && error.kind() == other_error.kind(),
+            #[cfg(feature = "parallel")]
+            (ThreadPool(_), ThreadPool(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ConvertError {}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConvertError::OutOfBounds(index) => {
+                write!(f, "Triangle {} is outside of the grid", index)
+            }
+            ConvertError::NonFinite(index) => {
+                write!(f, "Triangle {} has non-finite coordinates", index)
+            }
+            ConvertError::Clipped(count) => {
+                write!(f, "{} triangles are clipped by the grid", count)
+            }
+            ConvertError::MalformedFace {
+                face_index,
+                vertex_index,
+            } => write!(
+                f,
+                "Face {} references missing vertex {}",
+                face_index, vertex_index
+            ),
+            ConvertError::GridTooLarge {
+                width,
+                height,
+                max_cells,
+            } => write!(
+                f,
+                "Grid {}x{}x{} has more than {} cells",
+                width, width, height, max_cells
+            ),
+            ConvertError::Export { path, error } => {
+                write!(f, "Failed to export grid to {}: {}", path.display(), error)
+            }
+            #[cfg(feature = "parallel")]
+            ConvertError::ThreadPool(error) => {
+                write!(f, "Failed to build thread pool: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for ConvertError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConvertError::Export { error, .. } => Some(error),
+            #[cfg(feature = "parallel")]
+            ConvertError::ThreadPool(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum GridError {
+    /// Grids have different width or height.
+    DimensionMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// Region isn't inside of the grid, its min is greater than max or it isn't square
+    /// on x and y axes.
+    InvalidRegion {
+        min: (u32, u32, u32),
+        max: (u32, u32, u32),
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::DimensionMismatch { expected, actual } => write!(
+                f,
+                "Grid dimensions {}x{} don't match {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            GridError::InvalidRegion { min, max } => write!(
+                f,
+                "Region from ({}, {}, {}) to ({}, {}, {}) isn't a square box inside of the grid",
+                min.0, min.1, min.2, max.0, max.1, max.2
+            ),
+        }
+    }
+}
+
+impl Error for GridError {}
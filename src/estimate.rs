@@ -0,0 +1,269 @@
+use crate::bounding_box;
+use crate::geometry::{BoundingBox, Triangle};
+use nalgebra::Vector3;
+
+/// Grid stores every cell as a single bit.
+const BITS_PER_CELL: u64 = 1;
+
+/// Summary of a conversion computed without voxelizing any triangle.
+#[derive(Debug)]
+pub struct Estimate {
+    pub triangles: usize,
+    pub bounding_box: BoundingBox,
+    pub width: u32,
+    pub height: u32,
+    pub cells: u64,
+    pub bytes: u64,
+    /// True if some triangle vertex lies outside of the grid and will be clamped.
+    pub outside: bool,
+}
+
+pub fn estimate(triangles: &[Triangle], center: Vector3<f32>, width: u32, height: u32) -> Estimate {
+    let mesh_bounding_box = bounding_box(triangles);
+    let grid_bounding_box = BoundingBox::from_grid(&center, width, height);
+
+    let outside = triangles
+        .iter()
+        .any(|t| !grid_bounding_box.contains_triangle(t));
+
+    let cells = width as u64 * width as u64 * height as u64;
+    let bytes = (cells * BITS_PER_CELL + 7) / 8;
+
+    Estimate {
+        triangles: triangles.len(),
+        bounding_box: mesh_bounding_box,
+        width,
+        height,
+        cells,
+        bytes,
+        outside,
+    }
+}
+
+/// Count of cells of given size covering the world extent, partial cells are rounded up.
+pub fn cells_for_extent(extent: f32, cell_size: f32) -> u32 {
+    (extent / cell_size).ceil() as u32
+}
+
+/// Dimensions of grid covering the bounding box with cells of given size. Box extents are divided
+/// by cell size and rounded up, so the grid always covers the whole box.
+pub fn dimensions_for_cell_size(bounding_box: &BoundingBox, cell_size: f32) -> (u32, u32) {
+    let width = cells_for_extent(bounding_box.width(), cell_size);
+    let height = cells_for_extent(bounding_box.height(), cell_size);
+
+    (width, height)
+}
+
+/// Smallest grid with cells of given size which contains every triangle, with `padding` free
+/// cells on every side. Returns width, height and center in world units. Cells are centered
+/// on multiples of cell size, so the center is aligned to them too.
+pub fn minimal_grid(
+    triangles: &[Triangle],
+    cell_size: f32,
+    padding: u32,
+) -> (u32, u32, Vector3<f32>) {
+    if triangles.is_empty() {
+        return (1, 1, Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    let vertices = triangles.iter().flat_map(|t| vec![t.a, t.b, t.c]);
+    let (mut min, mut max) = (triangles[0].a, triangles[0].a);
+
+    for vertex in vertices {
+        min = min.zip_map(&vertex, f32::min);
+        max = max.zip_map(&vertex, f32::max);
+    }
+
+    // First and last cell covering the mesh along every axis, in cell units.
+    let padding = padding as i32;
+    let first = (min / cell_size).map(|v| v.round() as i32 - padding);
+    let last = (max / cell_size).map(|v| v.round() as i32 + padding);
+    let cells = last - first + Vector3::new(1, 1, 1);
+
+    let width = cells.x.max(cells.y) as u32;
+    let height = cells.z as u32;
+
+    // Grid starts `width / 2` cells before its center.
+    let center = Vector3::new(
+        first.x + (width / 2) as i32,
+        first.y + (width / 2) as i32,
+        first.z + (height / 2) as i32,
+    );
+
+    (width, height, center.map(|v| v as f32 * cell_size))
+}
+
+/// Finds the finest cell size for which grid covering the bounding box has at most `max_cells`
+/// cells, returns cell size, width and height. Dimensions are box extents divided by cell size
+/// and rounded up, so the grid always covers the whole box and is at least one cell big.
+pub fn fit_max_cells(bounding_box: &BoundingBox, max_cells: u64) -> (f32, u32, u32) {
+    let width_extent = bounding_box.width();
+    let height_extent = bounding_box.height();
+    let max_cells = max_cells.max(1);
+
+    if width_extent <= 0.0 && height_extent <= 0.0 {
+        return (1.0, 1, 1);
+    }
+
+    let dimensions = |cell_size: f32| {
+        let (width, height) = dimensions_for_cell_size(bounding_box, cell_size);
+        (width.max(1), height.max(1))
+    };
+
+    // Exact solution without rounding, rounding up can only make the grid bigger.
+    let mut cell_size = if height_extent > 0.0 && width_extent > 0.0 {
+        (width_extent * width_extent * height_extent / max_cells as f32).cbrt()
+    } else if width_extent > 0.0 {
+        width_extent / (max_cells as f32).sqrt()
+    } else {
+        height_extent / max_cells as f32
+    };
+
+    loop {
+        let (width, height) = dimensions(cell_size);
+
+        if width as u64 * width as u64 * height as u64 <= max_cells {
+            return (cell_size, width, height);
+        }
+
+        // Grow cell size just enough to remove one cell from width or height.
+        let next_width = if width > 1 {
+            width_extent / (width - 1) as f32
+        } else {
+            f32::INFINITY
+        };
+
+        let next_height = if height > 1 {
+            height_extent / (height - 1) as f32
+        } else {
+            f32::INFINITY
+        };
+
+        cell_size = next_width
+            .min(next_height)
+            .max(cell_size * (1.0 + f32::EPSILON));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::estimate::{cells_for_extent, estimate, fit_max_cells, minimal_grid};
+    use crate::geometry::{BoundingBox, LocalVector, Triangle, DEFAULT_EPSILON};
+    use crate::{bounding_box, find_obstacles};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_estimate() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(5.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        let triangles = vec![Triangle::new(a, b, c)];
+
+        let bounding_box = bounding_box(&triangles);
+        let center = bounding_box.center();
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+
+        let estimate = estimate(&triangles, center, width, height);
+
+        assert_eq!(estimate.triangles, 1);
+        assert_eq!(estimate.width, 12);
+        assert_eq!(estimate.height, 2);
+        assert_eq!(estimate.cells, 288);
+        assert_eq!(estimate.bytes, 36);
+        assert!(!estimate.outside);
+
+        // Every obstacle produced by conversion must fit into estimated dimensions.
+        let obstacles: Vec<LocalVector> = triangles
+            .iter()
+            .flat_map(|t| find_obstacles(t, &center, width, height, DEFAULT_EPSILON, 0))
+            .collect();
+
+        assert!(!obstacles.is_empty());
+        assert!(obstacles
+            .iter()
+            .all(|o| o.x < estimate.width && o.y < estimate.width && o.z < estimate.height));
+    }
+
+    #[test]
+    fn test_estimate_outside() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(50.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        let triangles = vec![Triangle::new(a, b, c)];
+        let estimate = estimate(&triangles, Vector3::new(0.0, 0.0, 0.0), 20, 20);
+
+        assert!(estimate.outside);
+    }
+
+    #[test]
+    fn test_minimal_grid() {
+        let mesh = || {
+            vec![
+                Triangle::new(
+                    Vector3::new(-10.2, 3.0, 0.0),
+                    Vector3::new(7.6, 4.0, 0.0),
+                    Vector3::new(0.0, 12.0, 2.0),
+                ),
+                Triangle::new(
+                    Vector3::new(0.0, 0.0, -4.4),
+                    Vector3::new(1.0, 0.0, 5.0),
+                    Vector3::new(0.0, 1.0, 0.0),
+                ),
+            ]
+        };
+
+        let triangles = mesh();
+        let (width, height, center) = minimal_grid(&triangles, 1.0, 0);
+
+        assert_eq!((width, height), (19, 10));
+        assert!(triangles
+            .iter()
+            .all(|t| BoundingBox::from_grid(&center, width, height).contains_triangle(t)));
+
+        // Grid one cell narrower or lower clips the mesh.
+        for (width, height) in [(width - 1, height), (width, height - 1)].iter() {
+            let grid = BoundingBox::from_grid(&center, *width, *height);
+            assert!(!triangles.iter().all(|t| grid.contains_triangle(t)));
+        }
+
+        let (padded_width, padded_height, padded_center) = minimal_grid(&triangles, 1.0, 2);
+
+        assert_eq!((padded_width, padded_height), (23, 14));
+        assert_eq!(padded_center, center);
+
+        // Mesh scaled by inverse of cell size fits into grid with center scaled the same way.
+        let (width, height, center) = minimal_grid(&triangles, 0.5, 0);
+        let grid = BoundingBox::from_grid(&(center * 2.0), width, height);
+
+        assert_eq!((width, height), (36, 20));
+        assert!(mesh()
+            .into_iter()
+            .all(|t| grid.contains_triangle(&t.scale(2.0))));
+    }
+
+    #[test]
+    fn test_cells_for_extent() {
+        assert_eq!(cells_for_extent(100.0, 0.5), 200);
+        assert_eq!(cells_for_extent(100.2, 0.5), 201);
+        assert_eq!(cells_for_extent(3.0, 1.0), 3);
+    }
+
+    #[test]
+    fn test_fit_max_cells() {
+        let bounding_box =
+            BoundingBox::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(100.0, 50.0, 20.0));
+
+        // Exact solution is cell size 10 with 10x10x2 grid.
+        assert_eq!(fit_max_cells(&bounding_box, 200), (10.0, 10, 2));
+
+        // Cell size 100 / 9 gives 9x9x2 grid, 100 / 10 would need 200 cells.
+        let (cell_size, width, height) = fit_max_cells(&bounding_box, 199);
+        assert_eq!((width, height), (9, 2));
+        assert!((cell_size - 100.0 / 9.0).abs() < 1e-4);
+
+        assert_eq!(fit_max_cells(&bounding_box, 1), (100.0, 1, 1));
+    }
+}
@@ -0,0 +1,1120 @@
+use crate::distance::distance_field;
+use crate::geometry::{parse_vector, BoundingBox, LocalVector};
+use crate::occupancy::OccupancyGrid;
+use crate::{check_grid_size, DEFAULT_MAX_CELLS};
+use nalgebra::{Scalar, Vector3};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// First bytes of the occupancy format, followed by format version byte.
+const OCCUPANCY_MAGIC: [u8; 3] = *b"OPG";
+
+/// Version 1 has no layout byte and is always x-major.
+const OCCUPANCY_VERSION: u8 = 2;
+
+/// First bytes of the distance field format, followed by format version byte.
+const DISTANCE_MAGIC: [u8; 3] = *b"OPD";
+
+const DISTANCE_VERSION: u8 = 1;
+
+/// First bytes of numpy `.npy` files, followed by major and minor format version.
+const NPY_MAGIC: [u8; 6] = *b"\x93NUMPY";
+
+/// Length of `.npy` header including magic and padding is a multiple of this.
+const NPY_ALIGNMENT: usize = 64;
+
+/// Faces of a cube as quads of corners facing outwards, corner `i` is at offset
+/// `(i & 1, i >> 1 & 1, i >> 2 & 1)`.
+const CUBE_FACES: [[usize; 4]; 6] = [
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+];
+
+/// Order in which cells of the grid are stored.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Layout {
+    /// X changes fastest, then y, then z.
+    XMajor,
+    /// Z changes fastest, then y, then x.
+    ZMajor,
+}
+
+impl Layout {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Layout::XMajor),
+            1 => Some(Layout::ZMajor),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Layout::XMajor => 0,
+            Layout::ZMajor => 1,
+        }
+    }
+
+    /// Position of the cell stored at given index.
+    fn position(self, grid: &OccupancyGrid, index: usize) -> LocalVector {
+        match self {
+            Layout::XMajor => grid.position(index),
+            Layout::ZMajor => {
+                let width = grid.width() as usize;
+                let height = grid.height() as usize;
+
+                let z = index % height;
+                let y = index / height % width;
+                let x = index / (height * width);
+
+                LocalVector::new(x as u32, y as u32, z as u32)
+            }
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x-major" => Ok(Layout::XMajor),
+            "z-major" => Ok(Layout::ZMajor),
+            _ => Err(format!("Unknown layout: {}", s)),
+        }
+    }
+}
+
+/// Which cells are listed by the cells format.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Polarity {
+    Obstacles,
+    /// Free cells are listed, every other cell is an obstacle.
+    Free,
+}
+
+impl Polarity {
+    /// Polarity listing less cells of the grid.
+    pub fn smallest(grid: &OccupancyGrid) -> Self {
+        let obstacles = grid.obstacle_count();
+        let free = grid.len() - obstacles;
+
+        if free < obstacles {
+            Polarity::Free
+        } else {
+            Polarity::Obstacles
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Polarity::Obstacles => "obstacles",
+            Polarity::Free => "free",
+        }
+    }
+}
+
+impl FromStr for Polarity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "obstacles" => Ok(Polarity::Obstacles),
+            "free" => Ok(Polarity::Free),
+            _ => Err(format!("Unknown polarity: {}", s)),
+        }
+    }
+}
+
+/// Polarity of the cells format, `Auto` picks the smallest one for every grid.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CellsFormat {
+    Obstacles,
+    Free,
+    Auto,
+}
+
+impl CellsFormat {
+    pub fn polarity(self, grid: &OccupancyGrid) -> Polarity {
+        match self {
+            CellsFormat::Obstacles => Polarity::Obstacles,
+            CellsFormat::Free => Polarity::Free,
+            CellsFormat::Auto => Polarity::smallest(grid),
+        }
+    }
+}
+
+impl FromStr for CellsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "obstacles" => Ok(CellsFormat::Obstacles),
+            "free" => Ok(CellsFormat::Free),
+            "auto" => Ok(CellsFormat::Auto),
+            _ => Err(format!("Unknown cells format: {}", s)),
+        }
+    }
+}
+
+/// Writes obstacles as `x,y,z` rows with a header line.
+pub fn write_csv<W: Write, T: Scalar + Display>(
+    obstacles: &[Vector3<T>],
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(writer, "x,y,z")?;
+
+    for obstacle in obstacles {
+        writeln!(writer, "{},{},{}", obstacle.x, obstacle.y, obstacle.z)?;
+    }
+
+    Ok(())
+}
+
+/// Reads `x,y,z` rows of points, optionally after the header written by `write_csv`.
+pub fn read_points<R: BufRead>(reader: R) -> io::Result<Vec<Vector3<f32>>> {
+    let mut points = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() || (index == 0 && line.trim() == "x,y,z") {
+            continue;
+        }
+
+        points.push(parse_vector(&line).map_err(invalid_data)?);
+    }
+
+    Ok(points)
+}
+
+pub fn load_points<P: AsRef<Path>>(path: P) -> io::Result<Vec<Vector3<f32>>> {
+    read_points(BufReader::new(File::open(path)?))
+}
+
+/// Writes obstacles as JSON array of `[x, y, z]` arrays.
+pub fn write_json<W: Write, T: Scalar + Display>(
+    obstacles: &[Vector3<T>],
+    writer: &mut W,
+) -> io::Result<()> {
+    write!(writer, "[")?;
+
+    for (index, obstacle) in obstacles.iter().enumerate() {
+        if index > 0 {
+            write!(writer, ",")?;
+        }
+
+        write!(writer, "[{},{},{}]", obstacle.x, obstacle.y, obstacle.z)?;
+    }
+
+    writeln!(writer, "]")
+}
+
+/// Writes obstacles as Wavefront obj point cloud, one `v` line per obstacle.
+pub fn write_obj<W: Write, T: Scalar + Display>(
+    obstacles: &[Vector3<T>],
+    writer: &mut W,
+) -> io::Result<()> {
+    for obstacle in obstacles {
+        writeln!(writer, "v {} {} {}", obstacle.x, obstacle.y, obstacle.z)?;
+    }
+
+    Ok(())
+}
+
+/// Saves obstacles at their world positions as obj point cloud, which can be overlaid on
+/// the source mesh in a 3D viewer.
+pub fn export_obstacles_obj<P: AsRef<Path>>(
+    obstacles: &[LocalVector],
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    path: P,
+) -> io::Result<()> {
+    let obstacles: Vec<Vector3<i32>> = obstacles
+        .iter()
+        .map(|o| o.to_world_vector(center, width, height))
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write_obj(&obstacles, &mut writer)?;
+    writer.flush()
+}
+
+/// Writes every obstacle as cube of 12 triangles at its world position, cells are `cell_size`
+/// big. With `shared_vertices` cubes next to each other reuse corners, otherwise every cube
+/// has its own 8 vertices. Much larger than the point cloud of `write_obj`, but shows volumes.
+pub fn write_cubes_obj<W: Write>(
+    obstacles: &[LocalVector],
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    cell_size: f32,
+    shared_vertices: bool,
+    writer: &mut W,
+) -> io::Result<()> {
+    // Corners are kept in half cells, so they are exact and can be compared.
+    let mut indices: HashMap<Vector3<i64>, usize> = HashMap::new();
+    let mut count = 0;
+
+    for obstacle in obstacles {
+        let cell = obstacle
+            .to_world_vector(center, width, height)
+            .map(|c| i64::from(c) * 2);
+        let mut corners = [0; 8];
+
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let offset = Vector3::new(i & 1, i >> 1 & 1, i >> 2 & 1).map(|c| c as i64 * 2);
+            let position = cell + offset - Vector3::repeat(1);
+
+            if shared_vertices {
+                if let Some(&index) = indices.get(&position) {
+                    *corner = index;
+                    continue;
+                }
+
+                indices.insert(position, count + 1);
+            }
+
+            let position = position.map(|c| c as f32 * cell_size / 2.0);
+            writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+
+            count += 1;
+            *corner = count;
+        }
+
+        for [a, b, c, d] in &CUBE_FACES {
+            writeln!(writer, "f {} {} {}", corners[*a], corners[*b], corners[*c])?;
+            writeln!(writer, "f {} {} {}", corners[*a], corners[*c], corners[*d])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Saves obstacles as obj mesh of cubes, see `write_cubes_obj`.
+pub fn export_cubes_obj<P: AsRef<Path>>(
+    obstacles: &[LocalVector],
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    cell_size: f32,
+    shared_vertices: bool,
+    path: P,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write_cubes_obj(
+        obstacles,
+        center,
+        width,
+        height,
+        cell_size,
+        shared_vertices,
+        &mut writer,
+    )?;
+    writer.flush()
+}
+
+/// Writes grid cells in local coordinates. Header line contains width, height and polarity,
+/// followed by `x,y,z` rows of cells matching the polarity.
+pub fn write_cells<W: Write>(
+    grid: &OccupancyGrid,
+    polarity: Polarity,
+    writer: &mut W,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{} {} {}",
+        grid.width(),
+        grid.height(),
+        polarity.name()
+    )?;
+
+    let listed = polarity == Polarity::Obstacles;
+
+    for index in 0..grid.len() {
+        let cell = grid.position(index);
+
+        if grid.is_obstacle(cell.x, cell.y, cell.z) == listed {
+            writeln!(writer, "{},{},{}", cell.x, cell.y, cell.z)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads grid written by `write_cells`.
+pub fn read_cells<R: BufRead>(reader: R) -> io::Result<OccupancyGrid> {
+    let mut lines = reader.lines();
+
+    let header = lines
+        .next()
+        .ok_or_else(|| invalid_data("Missing header".to_string()))??;
+
+    let header: Vec<&str> = header.split_whitespace().collect();
+
+    if header.len() != 3 {
+        return Err(invalid_data(format!(
+            "Invalid header: {}",
+            header.join(" ")
+        )));
+    }
+
+    let width = parse_value::<u32>(header[0])?;
+    let height = parse_value::<u32>(header[1])?;
+    let polarity = header[2].parse::<Polarity>().map_err(invalid_data)?;
+
+    check_header_size(width, height)?;
+    let mut grid = OccupancyGrid::new(width, height);
+
+    for line in lines {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let cell = parse_vector::<u32>(&line).map_err(invalid_data)?;
+        let (x, y, z) = (cell.x, cell.y, cell.z);
+
+        if !grid.contains(x, y, z) {
+            return Err(invalid_data(format!("Cell outside of the grid: {}", line)));
+        }
+
+        grid.set_obstacle(x, y, z);
+    }
+
+    if polarity == Polarity::Free {
+        grid.invert();
+    }
+
+    Ok(grid)
+}
+
+/// Writes grid in portable binary occupancy format with x-major layout.
+pub fn write_occupancy<W: Write>(grid: &OccupancyGrid, writer: &mut W) -> io::Result<()> {
+    write_occupancy_with_layout(grid, Layout::XMajor, writer)
+}
+
+/// Writes grid in portable binary occupancy format: magic bytes, version, layout, width and
+/// height as little endian `u32`, then one bit per cell in order given by layout.
+pub fn write_occupancy_with_layout<W: Write>(
+    grid: &OccupancyGrid,
+    layout: Layout,
+    writer: &mut W,
+) -> io::Result<()> {
+    writer.write_all(&OCCUPANCY_MAGIC)?;
+    writer.write_all(&[OCCUPANCY_VERSION, layout.to_byte()])?;
+    writer.write_all(&grid.width().to_le_bytes())?;
+    writer.write_all(&grid.height().to_le_bytes())?;
+
+    let mut bytes = vec![0u8; (grid.len() + 7) / 8];
+
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        for bit in 0..8 {
+            let cell_index = index * 8 + bit;
+
+            if cell_index >= grid.len() {
+                break;
+            }
+
+            let cell = layout.position(grid, cell_index);
+
+            if grid.is_obstacle(cell.x, cell.y, cell.z) {
+                *byte |= 1 << bit;
+            }
+        }
+    }
+
+    writer.write_all(&bytes)
+}
+
+/// Reads grid written by `write_occupancy` in any layout.
+pub fn read_occupancy<R: Read>(reader: &mut R) -> io::Result<OccupancyGrid> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+
+    if magic[..3] != OCCUPANCY_MAGIC {
+        return Err(invalid_data("Not an occupancy grid file".to_string()));
+    }
+
+    let layout = match magic[3] {
+        1 => Layout::XMajor,
+        OCCUPANCY_VERSION => {
+            let mut layout = [0u8; 1];
+            reader.read_exact(&mut layout)?;
+
+            Layout::from_byte(layout[0])
+                .ok_or_else(|| invalid_data(format!("Unknown layout: {}", layout[0])))?
+        }
+        version => return Err(invalid_data(format!("Unsupported version: {}", version))),
+    };
+
+    let mut dimension = [0u8; 4];
+
+    reader.read_exact(&mut dimension)?;
+    let width = u32::from_le_bytes(dimension);
+
+    reader.read_exact(&mut dimension)?;
+    let height = u32::from_le_bytes(dimension);
+
+    check_header_size(width, height)?;
+
+    // Bits are read before the grid is allocated, so truncated file can't allocate a huge grid.
+    let length = (width as u64 * width as u64 * height as u64 + 7) / 8;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+
+    if (bytes.len() as u64) < length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Expected {} bytes of cells, found {}", length, bytes.len()),
+        ));
+    }
+
+    let mut grid = OccupancyGrid::new(width, height);
+
+    for (index, byte) in bytes.iter().enumerate() {
+        for bit in 0..8 {
+            let cell_index = index * 8 + bit;
+
+            if cell_index < grid.len() && byte & (1 << bit) != 0 {
+                let cell = layout.position(&grid, cell_index);
+                grid.set_obstacle(cell.x, cell.y, cell.z);
+            }
+        }
+    }
+
+    Ok(grid)
+}
+
+pub fn save_grid<P: AsRef<Path>>(grid: &OccupancyGrid, path: P) -> io::Result<()> {
+    save_grid_with_layout(grid, Layout::XMajor, path)
+}
+
+pub fn save_grid_with_layout<P: AsRef<Path>>(
+    grid: &OccupancyGrid,
+    layout: Layout,
+    path: P,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write_occupancy_with_layout(grid, layout, &mut writer)?;
+    writer.flush()
+}
+
+/// Writes distance field of grid with given dimensions: magic bytes, version, width and height
+/// as little endian `u32`, then distance of every cell as little endian `f32` in x-major order.
+/// Grids without obstacles have infinite distances.
+pub fn write_distance_field<W: Write>(
+    field: &[f32],
+    width: u32,
+    height: u32,
+    writer: &mut W,
+) -> io::Result<()> {
+    writer.write_all(&DISTANCE_MAGIC)?;
+    writer.write_all(&[DISTANCE_VERSION])?;
+    writer.write_all(&width.to_le_bytes())?;
+    writer.write_all(&height.to_le_bytes())?;
+
+    for distance in field {
+        writer.write_all(&distance.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Writes grid as numpy `.npy` array of booleans with shape (width, width, height) in C order,
+/// so `numpy.load` gives array indexed by `[x, y, z]`. Obstacles are true.
+pub fn write_npy<W: Write>(grid: &OccupancyGrid, writer: &mut W) -> io::Result<()> {
+    let (width, height) = (grid.width(), grid.height());
+
+    let mut header = format!(
+        "{{'descr': '|b1', 'fortran_order': False, 'shape': ({}, {}, {}), }}",
+        width, width, height
+    );
+
+    // Magic, two version bytes and header length come before the header, which ends with
+    // a newline after padding.
+    let prefix = NPY_MAGIC.len() + 4;
+    let padded = (prefix + header.len() + 1 + NPY_ALIGNMENT - 1) / NPY_ALIGNMENT * NPY_ALIGNMENT;
+
+    header.push_str(&" ".repeat(padded - prefix - header.len() - 1));
+    header.push('\n');
+
+    let header_length = u16::try_from(header.len())
+        .map_err(|_| invalid_data("Grid is too large for npy header".to_string()))?;
+
+    writer.write_all(&NPY_MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&header_length.to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    // Last index changes fastest in C order.
+    for x in 0..width {
+        for y in 0..width {
+            let column: Vec<u8> = (0..height)
+                .map(|z| grid.is_obstacle(x, y, z) as u8)
+                .collect();
+
+            writer.write_all(&column)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn save_npy<P: AsRef<Path>>(grid: &OccupancyGrid, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write_npy(grid, &mut writer)?;
+    writer.flush()
+}
+
+/// Computes distance field of the grid with `distance_field` and saves it.
+pub fn save_distance_field<P: AsRef<Path>>(grid: &OccupancyGrid, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let field = distance_field(grid);
+
+    write_distance_field(&field, grid.width(), grid.height(), &mut writer)?;
+    writer.flush()
+}
+
+/// Loads grid saved by `save_grid`. Files written by `Grid::export` can't be loaded.
+pub fn load_grid<P: AsRef<Path>>(path: P) -> io::Result<OccupancyGrid> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    read_occupancy(&mut reader)
+}
+
+/// Reads grid written by `write_occupancy` or `write_cells`, telling them apart by the magic
+/// bytes of the occupancy format.
+pub fn read_any_grid<R: BufRead>(mut reader: R) -> io::Result<OccupancyGrid> {
+    if reader.fill_buf()?.starts_with(&OCCUPANCY_MAGIC) {
+        read_occupancy(&mut reader)
+    } else {
+        read_cells(reader)
+    }
+}
+
+/// Loads grid saved by `save_grid` or cells written by `write_cells`.
+pub fn load_any_grid<P: AsRef<Path>>(path: P) -> io::Result<OccupancyGrid> {
+    read_any_grid(BufReader::new(File::open(path)?))
+}
+
+/// Parameters mapping cells of an exported grid back to world space, every field is in the
+/// same world frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridMetadata {
+    /// World position of the grid center.
+    pub center: Vector3<f32>,
+    pub width: u32,
+    pub height: u32,
+    /// Size of a single cell in world units.
+    pub cell_size: f32,
+    /// World position of center of cell (0, 0, 0).
+    pub origin: Vector3<f32>,
+    /// World direction of grid x, y and z axes, grid z is the height.
+    pub axes: [Vector3<f32>; 3],
+    /// World bounding box of the converted mesh, unlike the grid it doesn't depend on cell
+    /// size. Saved as optional `mesh_min` and `mesh_max` fields.
+    pub mesh_bounds: Option<BoundingBox>,
+}
+
+impl GridMetadata {
+    /// Metadata of grid which axes are world axes. Center is in world units, grid is centered
+    /// on it in cells of `cell_size` like in conversion.
+    pub fn new(center: Vector3<f32>, width: u32, height: u32, cell_size: f32) -> Self {
+        let origin =
+            LocalVector::new(0, 0, 0).to_world_vector(&(center / cell_size), width, height);
+
+        GridMetadata {
+            center,
+            width,
+            height,
+            cell_size,
+            origin: Vector3::new(origin.x as f32, origin.y as f32, origin.z as f32) * cell_size,
+            axes: [Vector3::x(), Vector3::y(), Vector3::z()],
+            mesh_bounds: None,
+        }
+    }
+
+    /// World position of center of the cell.
+    pub fn cell_to_world(&self, cell: &LocalVector) -> Vector3<f32> {
+        let offset = self.axes[0] * cell.x as f32
+            + self.axes[1] * cell.y as f32
+            + self.axes[2] * cell.z as f32;
+
+        self.origin + offset * self.cell_size
+    }
+}
+
+/// Path of metadata saved alongside the grid, `grid.dat` has `grid.json`.
+pub fn metadata_path<P: AsRef<Path>>(grid_path: P) -> PathBuf {
+    grid_path.as_ref().with_extension("json")
+}
+
+/// Writes metadata as JSON object.
+pub fn write_metadata<W: Write>(metadata: &GridMetadata, writer: &mut W) -> io::Result<()> {
+    let vector = |v: &Vector3<f32>| format!("[{}, {}, {}]", v.x, v.y, v.z);
+    let axes: Vec<String> = metadata.axes.iter().map(vector).collect();
+
+    writeln!(writer, "{{")?;
+    writeln!(writer, "  \"center\": {},", vector(&metadata.center))?;
+    writeln!(writer, "  \"width\": {},", metadata.width)?;
+    writeln!(writer, "  \"height\": {},", metadata.height)?;
+    writeln!(writer, "  \"cell_size\": {},", metadata.cell_size)?;
+    writeln!(writer, "  \"origin\": {},", vector(&metadata.origin))?;
+    write!(writer, "  \"axes\": [{}]", axes.join(", "))?;
+
+    if let Some(bounds) = &metadata.mesh_bounds {
+        writeln!(writer, ",")?;
+        writeln!(writer, "  \"mesh_min\": {},", vector(&bounds.min))?;
+        write!(writer, "  \"mesh_max\": {}", vector(&bounds.max))?;
+    }
+
+    writeln!(writer)?;
+    writeln!(writer, "}}")
+}
+
+/// Reads metadata written by `write_metadata`, fields may be in any order.
+pub fn read_metadata<R: Read>(reader: &mut R) -> io::Result<GridMetadata> {
+    let mut json = String::new();
+    reader.read_to_string(&mut json)?;
+
+    let vectors = |key: &str| -> io::Result<Vec<Vector3<f32>>> {
+        let numbers = json_field(&json, key)?
+            .split(&[',', '[', ']'][..])
+            .filter(|n| !n.trim().is_empty())
+            .map(parse_value::<f32>)
+            .collect::<io::Result<Vec<f32>>>()?;
+
+        if numbers.len() % 3 != 0 {
+            return Err(invalid_data(format!("Invalid vectors: {}", key)));
+        }
+
+        Ok(numbers
+            .chunks(3)
+            .map(|c| Vector3::new(c[0], c[1], c[2]))
+            .collect())
+    };
+
+    let vector = |key: &str| match vectors(key)?.as_slice() {
+        [vector] => Ok(*vector),
+        _ => Err(invalid_data(format!("Expected a single vector: {}", key))),
+    };
+
+    let axes = match vectors("axes")?.as_slice() {
+        [x, y, z] => [*x, *y, *z],
+        _ => return Err(invalid_data("Expected three axes".to_string())),
+    };
+
+    // Mesh bounds are missing in metadata of older versions.
+    let mesh_bounds = match (json_field(&json, "mesh_min"), json_field(&json, "mesh_max")) {
+        (Ok(_), Ok(_)) => Some(BoundingBox::new(vector("mesh_min")?, vector("mesh_max")?)),
+        _ => None,
+    };
+
+    Ok(GridMetadata {
+        center: vector("center")?,
+        width: parse_value(json_field(&json, "width")?)?,
+        height: parse_value(json_field(&json, "height")?)?,
+        cell_size: parse_value(json_field(&json, "cell_size")?)?,
+        origin: vector("origin")?,
+        axes,
+        mesh_bounds,
+    })
+}
+
+pub fn save_metadata<P: AsRef<Path>>(metadata: &GridMetadata, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write_metadata(metadata, &mut writer)?;
+    writer.flush()
+}
+
+pub fn load_metadata<P: AsRef<Path>>(path: P) -> io::Result<GridMetadata> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    read_metadata(&mut reader)
+}
+
+/// Raw value of a field of flat JSON object, arrays are returned with their brackets.
+fn json_field<'a>(json: &'a str, key: &str) -> io::Result<&'a str> {
+    let missing = || invalid_data(format!("Missing field: {}", key));
+
+    let start = json.find(&format!("\"{}\"", key)).ok_or_else(missing)? + key.len() + 2;
+    let value = json[start..].trim_start();
+    let value = value.strip_prefix(':').ok_or_else(missing)?.trim_start();
+
+    let mut depth = 0;
+
+    for (index, c) in value.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' | '}' if depth == 0 => return Ok(value[..index].trim()),
+            _ => {}
+        }
+    }
+
+    Err(missing())
+}
+
+fn parse_value<T: FromStr>(value: &str) -> io::Result<T> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data(format!("Invalid value: {}", value)))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Fails if grid read from a header has more than `DEFAULT_MAX_CELLS` cells, so corrupt file
+/// can't allocate a huge grid or overflow count of cells.
+fn check_header_size(width: u32, height: u32) -> io::Result<()> {
+    check_grid_size(width, height, DEFAULT_MAX_CELLS).map_err(|e| invalid_data(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::{
+        export_cubes_obj, export_obstacles_obj, load_grid, read_any_grid, read_cells,
+        read_occupancy, read_points, save_grid, write_cells, write_csv, write_distance_field,
+        write_json, write_npy, write_occupancy_with_layout, CellsFormat, Layout, Polarity,
+    };
+    use crate::export::{load_metadata, metadata_path, read_metadata, save_metadata, GridMetadata};
+    use crate::geometry::{BoundingBox, LocalVector};
+    use crate::occupancy::OccupancyGrid;
+    use crate::{parse_triangles, read_obj_checked};
+    use nalgebra::Vector3;
+    use std::io;
+
+    #[test]
+    fn test_write_csv() {
+        let obstacles = vec![Vector3::new(1, 2, 3), Vector3::new(-4, 5, -6)];
+        let mut output = Vec::new();
+
+        write_csv(&obstacles, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "x,y,z\n1,2,3\n-4,5,-6\n"
+        );
+    }
+
+    #[test]
+    fn test_read_points() {
+        let points = read_points("x,y,z\n1,2,3\n\n-4.5, 5,-6\n".as_bytes()).unwrap();
+
+        assert_eq!(
+            points,
+            vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(-4.5, 5.0, -6.0)]
+        );
+
+        assert!(read_points("1,2\n".as_bytes()).is_err());
+        assert!(read_points("1,2,3\nx,y,z\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_write_json() {
+        let obstacles = vec![Vector3::new(1, 2, 3), Vector3::new(-4, 5, -6)];
+        let mut output = Vec::new();
+
+        write_json(&obstacles, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "[[1,2,3],[-4,5,-6]]\n");
+    }
+
+    #[test]
+    fn test_export_obstacles_obj() {
+        let obstacles = vec![LocalVector::new(5, 5, 5), LocalVector::new(0, 9, 6)];
+        let center = Vector3::new(100.0, 0.0, -20.0);
+
+        let path = std::env::temp_dir().join("obj-to-pathfinding-grid-points.obj");
+
+        export_obstacles_obj(&obstacles, &center, 10, 10, &path).unwrap();
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, "v 100 0 -20\nv 95 4 -19\n");
+    }
+
+    #[test]
+    fn test_export_cubes_obj() {
+        let center = Vector3::new(100.0, 0.0, -20.0);
+        let path = std::env::temp_dir().join("obj-to-pathfinding-grid-cubes.obj");
+
+        let export = |obstacles: &[LocalVector], shared_vertices: bool| {
+            export_cubes_obj(obstacles, &center, 10, 10, 0.5, shared_vertices, &path).unwrap();
+            let output = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            output
+        };
+
+        let count =
+            |output: &str, prefix: &str| output.lines().filter(|l| l.starts_with(prefix)).count();
+
+        // Obstacle at world cell (100, 0, -20) spans half a cell around it in both directions.
+        let output = export(&[LocalVector::new(5, 5, 5)], true);
+
+        assert_eq!(count(&output, "v "), 8);
+        assert_eq!(count(&output, "f "), 12);
+        assert!(output.starts_with("v 49.75 -0.25 -10.25\n"));
+        assert!(output.contains("v 50.25 0.25 -9.75\n"));
+
+        let (obj, _) = read_obj_checked(output.as_bytes()).unwrap();
+        let triangles = parse_triangles(&obj);
+
+        assert_eq!(triangles.len(), 12);
+        assert!(triangles.iter().all(|t| t
+            .normal()
+            .dot(&(t.centroid() - Vector3::new(50.0, 0.0, -10.0)))
+            > 0.0));
+
+        // Neighbors share the face between them only with shared vertices.
+        let obstacles = [LocalVector::new(5, 5, 5), LocalVector::new(6, 5, 5)];
+
+        assert_eq!(count(&export(&obstacles, true), "v "), 12);
+        assert_eq!(count(&export(&obstacles, false), "v "), 16);
+    }
+
+    #[test]
+    fn test_cells_round_trip_free() {
+        let mut grid = OccupancyGrid::new(4, 3);
+        grid.invert();
+        grid.remove_obstacle(1, 2, 0);
+        grid.remove_obstacle(3, 3, 2);
+
+        let polarity = CellsFormat::Auto.polarity(&grid);
+        assert_eq!(polarity, Polarity::Free);
+
+        let mut output = Vec::new();
+        write_cells(&grid, polarity, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.clone()).unwrap(),
+            "4 3 free\n1,2,0\n3,3,2\n"
+        );
+
+        let decoded = read_cells(output.as_slice()).unwrap();
+
+        assert_eq!(decoded, grid);
+    }
+
+    #[test]
+    fn test_cells_round_trip_obstacles() {
+        let mut grid = OccupancyGrid::new(4, 3);
+        grid.set_obstacle(0, 1, 2);
+
+        let polarity = CellsFormat::Auto.polarity(&grid);
+        assert_eq!(polarity, Polarity::Obstacles);
+
+        let mut output = Vec::new();
+        write_cells(&grid, polarity, &mut output).unwrap();
+
+        let decoded = read_cells(output.as_slice()).unwrap();
+
+        assert_eq!(decoded, grid);
+    }
+
+    #[test]
+    fn test_read_cells_invalid() {
+        assert!(read_cells("4 3 solid\n".as_bytes()).is_err());
+        assert!(read_cells("4 3 free\n1,2\n".as_bytes()).is_err());
+        assert!(read_cells("4 3 free\n1,2,3\n".as_bytes()).is_err());
+
+        let error = read_cells("4294967295 4294967295 solid\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = read_cells("65536 65536 solid\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_read_any_grid() {
+        let mut grid = OccupancyGrid::new(4, 3);
+        grid.set_obstacle(0, 1, 2);
+        grid.set_obstacle(3, 3, 0);
+
+        let mut occupancy = Vec::new();
+        write_occupancy_with_layout(&grid, Layout::ZMajor, &mut occupancy).unwrap();
+
+        let mut cells = Vec::new();
+        write_cells(&grid, Polarity::Free, &mut cells).unwrap();
+
+        assert_eq!(read_any_grid(occupancy.as_slice()).unwrap(), grid);
+        assert_eq!(read_any_grid(cells.as_slice()).unwrap(), grid);
+        assert!(read_any_grid("OPG".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_occupancy_round_trip() {
+        let mut grid = OccupancyGrid::new(5, 3);
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(4, 2, 1);
+        grid.set_obstacle(4, 4, 2);
+
+        let path = std::env::temp_dir().join("obj-to-pathfinding-grid-round-trip.dat");
+
+        save_grid(&grid, &path).unwrap();
+        let loaded = load_grid(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, grid);
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let mut metadata = GridMetadata::new(Vector3::new(3.0, -1.5, 2.25), 12, 8, 0.5);
+
+        // Center is 6, -3, 4.5 cells, which is rounded to 6, -3, 5.
+        assert_eq!(metadata.origin, Vector3::new(0.0, -4.5, 0.5));
+        assert_eq!(
+            metadata.cell_to_world(&LocalVector::new(6, 6, 4)),
+            Vector3::new(3.0, -1.5, 2.5)
+        );
+
+        let path = metadata_path(std::env::temp_dir().join("obj-to-pathfinding-grid-meta.dat"));
+
+        assert_eq!(path.extension().unwrap(), "json");
+
+        save_metadata(&metadata, &path).unwrap();
+        let loaded = load_metadata(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, metadata);
+
+        metadata.mesh_bounds = Some(BoundingBox::new(
+            Vector3::new(-1.0, -2.5, 0.0),
+            Vector3::new(7.0, 0.5, 4.25),
+        ));
+
+        save_metadata(&metadata, &path).unwrap();
+        let loaded = load_metadata(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, metadata);
+
+        let reordered = r#"{"axes": [[-1, 0, 0], [0, 1, 0], [0, 0, 1]], "width": 4,
+            "height": 2, "cell_size": 1, "origin": [1, 2, 3], "center": [0, 0, 0]}"#;
+        let metadata = read_metadata(&mut reordered.as_bytes()).unwrap();
+
+        assert_eq!(metadata.axes[0], Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(metadata.height, 2);
+        assert_eq!(metadata.mesh_bounds, None);
+
+        assert!(read_metadata(&mut r#"{"width": 4}"#.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_occupancy_invalid() {
+        assert!(read_occupancy(&mut "OPG".as_bytes()).is_err());
+        assert!(read_occupancy(&mut "GRID\x01\0\0\0\x01\0\0\0\0".as_bytes()).is_err());
+
+        // One cell needs one byte.
+        assert!(read_occupancy(&mut "OPG\x01\x01\0\0\0\x01\0\0\0".as_bytes()).is_err());
+        assert!(read_occupancy(&mut "OPG\x01\x01\0\0\0\x01\0\0\0\x01".as_bytes()).is_ok());
+
+        assert!(read_occupancy(&mut "OPG\x02\x00\x01\0\0\0\x01\0\0\0\x01".as_bytes()).is_ok());
+        assert!(read_occupancy(&mut "OPG\x02\x02\x01\0\0\0\x01\0\0\0\x01".as_bytes()).is_err());
+        assert!(read_occupancy(&mut "OPG\x03\x00\x01\0\0\0\x01\0\0\0\x01".as_bytes()).is_err());
+
+        // Huge dimensions fail on the header, before cells are allocated.
+        let mut huge = b"OPG\x02\x00".to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+
+        let error = read_occupancy(&mut huge.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Allowed dimensions still need all the bits.
+        let mut truncated = b"OPG\x02\x00".to_vec();
+        truncated.extend_from_slice(&1024u32.to_le_bytes());
+        truncated.extend_from_slice(&1024u32.to_le_bytes());
+        truncated.push(0);
+
+        let error = read_occupancy(&mut truncated.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_write_distance_field() {
+        let mut output = Vec::new();
+
+        write_distance_field(&[0.0, 1.5], 1, 2, &mut output).unwrap();
+
+        assert_eq!(&output[..4], b"OPD\x01");
+        assert_eq!(&output[4..8], &1u32.to_le_bytes());
+        assert_eq!(&output[8..12], &2u32.to_le_bytes());
+        assert_eq!(&output[12..16], &0f32.to_le_bytes());
+        assert_eq!(&output[16..], &1.5f32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_write_npy() {
+        let mut grid = OccupancyGrid::new(2, 3);
+        grid.set_obstacle(0, 0, 1);
+        grid.set_obstacle(1, 0, 2);
+
+        let mut output = Vec::new();
+        write_npy(&grid, &mut output).unwrap();
+
+        assert_eq!(&output[..8], b"\x93NUMPY\x01\x00");
+
+        let header_length = u16::from_le_bytes([output[8], output[9]]) as usize;
+        let header = std::str::from_utf8(&output[10..10 + header_length]).unwrap();
+
+        assert_eq!((10 + header_length) % 64, 0);
+        assert!(header.starts_with("{'descr': '|b1', 'fortran_order': False, "));
+        assert!(header.contains("'shape': (2, 2, 3)"));
+        assert!(header.ends_with('\n'));
+
+        // Cells of x 0 come first, z changes fastest.
+        let data = &output[10 + header_length..];
+
+        assert_eq!(data.len(), 2 * 2 * 3);
+        assert_eq!(&data[..3], &[0, 1, 0]);
+        assert_eq!(&data[6..9], &[0, 0, 1]);
+        assert_eq!(data.iter().filter(|b| **b == 1).count(), 2);
+    }
+
+    #[test]
+    fn test_occupancy_layouts() {
+        let mut grid = OccupancyGrid::new(3, 2);
+        grid.set_obstacle(1, 0, 0);
+        grid.set_obstacle(0, 0, 1);
+        grid.set_obstacle(2, 1, 1);
+
+        let write = |layout: Layout| {
+            let mut output = Vec::new();
+            write_occupancy_with_layout(&grid, layout, &mut output).unwrap();
+            output
+        };
+
+        let x_major = write(Layout::XMajor);
+        let z_major = write(Layout::ZMajor);
+
+        // Header is magic, version, layout and dimensions, 18 cells fit into 3 bytes.
+        assert_eq!(x_major.len(), 4 + 1 + 8 + 3);
+        assert_eq!(x_major[13..], [0b0000_0010, 0b0100_0010, 0b0000_0000]);
+        assert_eq!(z_major[13..], [0b0100_0010, 0b1000_0000, 0b0000_0000]);
+
+        assert_eq!(read_occupancy(&mut x_major.as_slice()).unwrap(), grid);
+        assert_eq!(read_occupancy(&mut z_major.as_slice()).unwrap(), grid);
+    }
+}
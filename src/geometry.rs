@@ -1,7 +1,21 @@
-use nalgebra::Vector3;
+use nalgebra::{Matrix4, Point3, Rotation3, Scalar, Vector3, U3};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Default tolerance of the triangle-box overlap test, in cell units.
+/// Small enough to keep results unchanged except for float noise on cell boundaries.
+pub const DEFAULT_EPSILON: f32 = 1e-5;
+
+/// Distance from the face shared by two layers within which an axis-aligned triangle is
+/// assigned only to the lower layer. Fixed, so it doesn't grow with the overlap epsilon.
+const FACE_TOLERANCE: f32 = 1e-5;
 
 /// Local vector represents grid coordinates from 0 to width/height.
-#[derive(Debug, Eq, PartialEq)]
+/// Grid center always lands at `floor(width / 2)` and `floor(height / 2)`, so for odd
+/// dimensions both sides of the center have the same count of cells and for even
+/// dimensions the side below the center has one cell more.
+/// Local vectors are ordered by x, then y, then z.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct LocalVector {
     pub x: u32,
     pub y: u32,
@@ -15,28 +29,47 @@ impl LocalVector {
 
     /// Converts world vector to grid local vector.
     /// If world vector out of grid bounds will be set border coordinates.
+    /// Center is rounded like in `to_world_vector` and subtracted in f64, so meshes far from
+    /// the origin land in the same cells as they would near it.
     pub fn from_world_vector(
         vector: &Vector3<f32>,
         center: &Vector3<f32>,
         width: u32,
         height: u32,
     ) -> Self {
-        let diff = center - vector;
+        let local = unclamped_local(vector, center, width, height);
+        let clamp = |local: i64, max: u32| local.max(0).min(i64::from(max)) as u32;
 
-        let half_width = width / 2;
-        let half_height = height / 2;
-
-        let mx = (half_width as i32 - diff.x.round() as i32).max(0) as u32;
-        let my = (half_width as i32 - diff.y.round() as i32).max(0) as u32;
-        let mz = (half_height as i32 - diff.z.round() as i32).max(0) as u32;
-
-        let x = mx.min(width) as u32;
-        let y = my.min(width) as u32;
-        let z = mz.min(height) as u32;
+        let x = clamp(local.x, width);
+        let y = clamp(local.y, width);
+        let z = clamp(local.z, height);
 
         LocalVector { x, y, z }
     }
 
+    /// Same as `from_world_vector`, but `None` if world vector is out of grid bounds.
+    pub fn try_from_world_vector(
+        vector: &Vector3<f32>,
+        center: &Vector3<f32>,
+        width: u32,
+        height: u32,
+    ) -> Option<Self> {
+        let local = unclamped_local(vector, center, width, height);
+        let inside = |local: i64, max: u32| (0..i64::from(max)).contains(&local);
+
+        if inside(local.x, width) && inside(local.y, width) && inside(local.z, height) {
+            Some(LocalVector::new(
+                local.x as u32,
+                local.y as u32,
+                local.z as u32,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Converts grid local vector to world vector.
+    /// Inverse of `from_world_vector` for every vector inside grid bounds.
     pub fn to_world_vector(&self, center: &Vector3<f32>, width: u32, height: u32) -> Vector3<i32> {
         let half_width = width / 2;
         let half_height = height / 2;
@@ -53,7 +86,54 @@ impl LocalVector {
     }
 }
 
-#[derive(Debug)]
+/// Parses vector given as `x,y,z`, shared by arguments and text files of points and cells.
+pub fn parse_vector<T: Scalar + FromStr>(s: &str) -> Result<Vector3<T>, String>
+where
+    T::Err: Display,
+{
+    let components = s
+        .split(',')
+        .map(|c| c.trim().parse::<T>())
+        .collect::<Result<Vec<T>, _>>()
+        .map_err(|e| format!("Invalid vector {}: {}", s, e))?;
+
+    if components.len() != 3 {
+        return Err(format!("Vector must have 3 components: {}", s));
+    }
+
+    Ok(Vector3::new(components[0], components[1], components[2]))
+}
+
+/// Local coordinates of world vector, which may be outside of the grid.
+fn unclamped_local(
+    vector: &Vector3<f32>,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+) -> Vector3<i64> {
+    let center = center.map(|c| f64::from(c.round()));
+    let diff = vector.map(f64::from) - center;
+
+    let half_width = i64::from(width / 2);
+    let half_height = i64::from(height / 2);
+
+    Vector3::new(
+        half_width + diff.x.round() as i64,
+        half_width + diff.y.round() as i64,
+        half_height + diff.z.round() as i64,
+    )
+}
+
+/// Returns grid center for which local (0, 0, 0) is at given world origin.
+/// Cells are centered around integer coordinates, so origin is rounded to the nearest one.
+pub fn center_from_origin(origin: &Vector3<f32>, width: u32, height: u32) -> Vector3<f32> {
+    let half_width = (width / 2) as f32;
+    let half_height = (height / 2) as f32;
+
+    origin.map(f32::round) + Vector3::new(half_width, half_width, half_height)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BoundingBox {
     pub min: Vector3<f32>,
     pub max: Vector3<f32>,
@@ -78,6 +158,115 @@ impl BoundingBox {
     pub fn height(&self) -> f32 {
         self.max.z - self.min.z
     }
+
+    /// Returns world space bounding box covered by grid cells.
+    /// Cells are centered around integer coordinates, so box starts half a cell
+    /// before the first cell center and ends half a cell after the last one.
+    pub fn from_grid(center: &Vector3<f32>, width: u32, height: u32) -> Self {
+        let min = LocalVector::new(0, 0, 0).to_world_vector(center, width, height);
+        let max = LocalVector::new(width, width, height).to_world_vector(center, width, height);
+
+        let min = Vector3::new(min.x as f32, min.y as f32, min.z as f32).add_scalar(-0.5);
+        let max = Vector3::new(max.x as f32, max.y as f32, max.z as f32).add_scalar(-0.5);
+
+        BoundingBox::new(min, max)
+    }
+
+    pub fn contains(&self, vector: &Vector3<f32>) -> bool {
+        vector.x >= self.min.x
+            && vector.y >= self.min.y
+            && vector.z >= self.min.z
+            && vector.x <= self.max.x
+            && vector.y <= self.max.y
+            && vector.z <= self.max.z
+    }
+
+    pub fn contains_triangle(&self, triangle: &Triangle) -> bool {
+        self.contains(&triangle.a) && self.contains(&triangle.b) && self.contains(&triangle.c)
+    }
+
+    /// Boxes touching only at their borders intersect too.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x" => Ok(Axis::X),
+            "y" => Ok(Axis::Y),
+            "z" => Ok(Axis::Z),
+            _ => Err(format!("Unknown axis: {}", s)),
+        }
+    }
+}
+
+impl Axis {
+    /// Index of the axis component in a vector.
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// Transforms of the whole mesh, applied in order of fields: scaling, rotation around z axis,
+/// mirroring and translation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshTransform {
+    pub scale: f32,
+    /// Point which stays in place when scaling.
+    pub pivot: Vector3<f32>,
+    /// Counter-clockwise angle in radians.
+    pub rotate_z: f32,
+    /// Mirror across planes perpendicular to x, y and z axes.
+    pub mirror: [bool; 3],
+    pub translate: Vector3<f32>,
+}
+
+impl Default for MeshTransform {
+    fn default() -> Self {
+        MeshTransform {
+            scale: 1.0,
+            pivot: Vector3::zeros(),
+            rotate_z: 0.0,
+            mirror: [false; 3],
+            translate: Vector3::zeros(),
+        }
+    }
+}
+
+impl MeshTransform {
+    /// Homogeneous matrix of all transforms, for aligning other data with the mesh.
+    pub fn matrix(&self) -> Matrix4<f32> {
+        let sign = |mirror: bool| if mirror { -1.0 } else { 1.0 };
+        let mirror = Vector3::new(
+            sign(self.mirror[0]),
+            sign(self.mirror[1]),
+            sign(self.mirror[2]),
+        );
+
+        let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), self.rotate_z);
+
+        Matrix4::new_translation(&self.translate)
+            * Matrix4::new_nonuniform_scaling(&mirror)
+            * rotation.to_homogeneous()
+            * Matrix4::new_translation(&self.pivot)
+            * Matrix4::new_scaling(self.scale)
+            * Matrix4::new_translation(&-self.pivot)
+    }
 }
 
 #[derive(Debug)]
@@ -95,9 +284,114 @@ impl Triangle {
 
 impl Triangle {
     pub fn is_inside(&self, vector: &Vector3<i32>) -> bool {
+        self.is_inside_with_epsilon(vector, DEFAULT_EPSILON)
+    }
+
+    /// Slow reference for `is_inside`, checks if any of points sampled on triangle, with
+    /// `samples` steps along every edge, is inside of the cell grown by `margin` on every side.
+    /// Intersections thinner than the sample spacing can be missed.
+    pub fn is_inside_reference(&self, vector: &Vector3<i32>, samples: u32, margin: f32) -> bool {
+        let center = Vector3::new(vector.x as f32, vector.y as f32, vector.z as f32);
+        let half_size = 0.5 + margin;
+        let samples = samples.max(1);
+
+        let ab = (self.b - self.a) / samples as f32;
+        let ac = (self.c - self.a) / samples as f32;
+
+        (0..=samples).any(|i| {
+            (0..=samples - i).any(|j| {
+                let point = self.a + ab * i as f32 + ac * j as f32;
+                (point - center).iter().all(|c| c.abs() <= half_size)
+            })
+        })
+    }
+
+    /// Cells near the triangle for which `is_inside` disagrees with `is_inside_reference`.
+    /// Cells reference finds a sample in must be inside, other cells can be inside only if
+    /// reference finds a sample within sample spacing of them.
+    pub fn is_inside_mismatches(&self, samples: u32) -> Vec<Vector3<i32>> {
+        let bounding_box = self.bounding_box();
+        let spacing = [self.b - self.a, self.c - self.b, self.a - self.c]
+            .iter()
+            .map(|edge| edge.norm())
+            .fold(0.0, f32::max)
+            / samples.max(1) as f32;
+
+        let mut mismatches = Vec::new();
+
+        for x in bounding_box.min.x.ceil() as i32..=bounding_box.max.x.floor() as i32 {
+            for y in bounding_box.min.y.ceil() as i32..=bounding_box.max.y.floor() as i32 {
+                for z in bounding_box.min.z.ceil() as i32..=bounding_box.max.z.floor() as i32 {
+                    let vector = Vector3::new(x, y, z);
+                    let inside = self.is_inside(&vector);
+
+                    let mismatch = if inside {
+                        !self.is_inside_reference(&vector, samples, spacing + DEFAULT_EPSILON)
+                    } else {
+                        self.is_inside_reference(&vector, samples, 0.0)
+                    };
+
+                    if mismatch {
+                        mismatches.push(vector);
+                    }
+                }
+            }
+        }
+
+        mismatches
+    }
+
+    /// Checks if cell center is at most `max_distance` from triangle plane and its projection
+    /// onto the plane is inside of the triangle. Degenerate triangles contain no points.
+    pub fn contains_projection(&self, vector: &Vector3<i32>, max_distance: f32) -> bool {
+        let point = Vector3::new(vector.x as f32, vector.y as f32, vector.z as f32);
+        let normal = self.normal();
+        let length = normal.norm();
+
+        if length == 0.0 {
+            return false;
+        }
+
+        let distance = normal.dot(&(point - self.a)) / length;
+
+        if distance.abs() > max_distance {
+            return false;
+        }
+
+        // Point is on the inner side of every edge, the test doesn't depend on distance to plane.
+        let edges = [(self.a, self.b), (self.b, self.c), (self.c, self.a)];
+
+        edges
+            .iter()
+            .all(|(start, end)| (end - start).cross(&(point - start)).dot(&normal) >= 0.0)
+    }
+
+    /// Checks if triangle overlaps cell centered at given vector.
+    /// Separation along any axis must exceed epsilon for cell to be rejected,
+    /// so larger epsilon is more conservative and marks cells the triangle only grazes.
+    /// Negative epsilon makes the test stricter.
+    /// Axis-aligned triangle lying on the face shared by two layers overlaps both of them,
+    /// so it is assigned only to the lower layer.
+    pub fn is_inside_with_epsilon(&self, vector: &Vector3<i32>, epsilon: f32) -> bool {
         let box_center = Vector3::new(vector.x as f32, vector.y as f32, vector.z as f32);
         let box_half_size = Vector3::new(0.5, 0.5, 0.5);
 
+        for axis in 0..3 {
+            let value = self.a[axis];
+
+            if value != self.b[axis] || value != self.c[axis] {
+                continue;
+            }
+
+            let lower_layer = (value - 0.5).round();
+
+            let on_face = (value - lower_layer - 0.5).abs() <= FACE_TOLERANCE;
+
+            if on_face && box_center[axis] != lower_layer {
+                return false;
+            }
+        }
+
         // Move the triangle so that the box is centered around the origin.
         let v0 = self.a - &box_center;
         let v1 = self.b - &box_center;
@@ -109,15 +403,15 @@ impl Triangle {
         let e2 = v0 - &v2;
 
         // 1. Test the AABB against the minimal AABB around the triangle.
-        if min_max_overlaps(box_half_size.x, v0.x, v1.x, v2.x) {
+        if min_max_overlaps(box_half_size.x + epsilon, v0.x, v1.x, v2.x) {
             return false;
         }
 
-        if min_max_overlaps(box_half_size.y, v0.y, v1.y, v2.y) {
+        if min_max_overlaps(box_half_size.y + epsilon, v0.y, v1.y, v2.y) {
             return false;
         }
 
-        if min_max_overlaps(box_half_size.z, v0.z, v1.z, v2.z) {
+        if min_max_overlaps(box_half_size.z + epsilon, v0.z, v1.z, v2.z) {
             return false;
         }
 
@@ -152,52 +446,55 @@ impl Triangle {
             v_max.z -= box_half_size.z;
         }
 
-        if normal.dot(&v_min) + d > 0.0 {
+        // Normal is not normalized, so scale tolerance to keep it in world units.
+        let plane_epsilon = epsilon * normal.norm();
+
+        if normal.dot(&v_min) + d > plane_epsilon {
             return false;
         }
 
-        if normal.dot(&v_max) + d < 0.0 {
+        if normal.dot(&v_max) + d < -plane_epsilon {
             return false;
         }
 
         // 3. Axis test
 
         // Edge #1
-        if !axis_test_zy(&v0, &v2, &box_half_size, &e0) {
+        if !axis_test_zy(&v0, &v2, &box_half_size, &e0, epsilon) {
             return false;
         }
 
-        if !axis_test_mzx(&v0, &v2, &box_half_size, &e0) {
+        if !axis_test_mzx(&v0, &v2, &box_half_size, &e0, epsilon) {
             return false;
         }
 
-        if !axis_test_yx(&v1, &v2, &box_half_size, &e0) {
+        if !axis_test_yx(&v1, &v2, &box_half_size, &e0, epsilon) {
             return false;
         }
 
         // Edge #2
-        if !axis_test_zy(&v0, &v2, &box_half_size, &e1) {
+        if !axis_test_zy(&v0, &v2, &box_half_size, &e1, epsilon) {
             return false;
         }
 
-        if !axis_test_mzx(&v0, &v2, &box_half_size, &e1) {
+        if !axis_test_mzx(&v0, &v2, &box_half_size, &e1, epsilon) {
             return false;
         }
 
-        if !axis_test_yx(&v0, &v1, &box_half_size, &e1) {
+        if !axis_test_yx(&v0, &v1, &box_half_size, &e1, epsilon) {
             return false;
         }
 
         // Edge #3
-        if !axis_test_zy(&v0, &v1, &box_half_size, &e2) {
+        if !axis_test_zy(&v0, &v1, &box_half_size, &e2, epsilon) {
             return false;
         }
 
-        if !axis_test_mzx(&v0, &v1, &box_half_size, &e2) {
+        if !axis_test_mzx(&v0, &v1, &box_half_size, &e2, epsilon) {
             return false;
         }
 
-        if !axis_test_yx(&v1, &v2, &box_half_size, &e2) {
+        if !axis_test_yx(&v1, &v2, &box_half_size, &e2, epsilon) {
             return false;
         }
 
@@ -212,6 +509,89 @@ impl Triangle {
         Triangle::new(a, b, c)
     }
 
+    /// Scales triangle relative to pivot, which stays in place, unlike with `scale`, which
+    /// scales relative to the origin.
+    pub fn scale_about(self, pivot: Vector3<f32>, factor: f32) -> Triangle {
+        let scale = |v: Vector3<f32>| pivot + (v - pivot) * factor;
+
+        Triangle::new(scale(self.a), scale(self.b), scale(self.c))
+    }
+
+    /// Rotates triangle counter-clockwise around z axis by angle in radians.
+    pub fn rotate_z(self, angle: f32) -> Triangle {
+        let (sin, cos) = angle.sin_cos();
+        let rotate =
+            |v: Vector3<f32>| Vector3::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos, v.z);
+
+        Triangle::new(rotate(self.a), rotate(self.b), rotate(self.c))
+    }
+
+    pub fn translate(self, offset: &Vector3<f32>) -> Triangle {
+        Triangle::new(self.a + offset, self.b + offset, self.c + offset)
+    }
+
+    /// Applies homogeneous transform matrix, like `MeshTransform::matrix`. Matrix which turns
+    /// space inside out, like a mirror, also swaps `b` and `c` to keep the normal outside.
+    pub fn transform(self, matrix: &Matrix4<f32>) -> Triangle {
+        let transform = |v: Vector3<f32>| matrix.transform_point(&Point3::from(v)).coords;
+        let (a, b, c) = (transform(self.a), transform(self.b), transform(self.c));
+
+        if matrix.fixed_slice::<U3, U3>(0, 0).determinant() < 0.0 {
+            Triangle::new(a, c, b)
+        } else {
+            Triangle::new(a, b, c)
+        }
+    }
+
+    /// Mirrors triangle across plane perpendicular to given axis.
+    /// Negating one component flips the winding, so `b` and `c` are swapped to keep
+    /// the normal facing away from the same side of the surface.
+    pub fn mirror(self, axis: Axis) -> Triangle {
+        let mirror = |mut vector: Vector3<f32>| {
+            match axis {
+                Axis::X => vector.x = -vector.x,
+                Axis::Y => vector.y = -vector.y,
+                Axis::Z => vector.z = -vector.z,
+            }
+
+            vector
+        };
+
+        Triangle::new(mirror(self.a), mirror(self.c), mirror(self.b))
+    }
+
+    /// Returns not normalized normal, its direction follows counter-clockwise winding.
+    pub fn normal(&self) -> Vector3<f32> {
+        (self.b - self.a).cross(&(self.c - self.a))
+    }
+
+    /// Average of the three vertices.
+    pub fn centroid(&self) -> Vector3<f32> {
+        (self.a + self.b + self.c) / 3.0
+    }
+
+    /// Checks that no coordinate is NaN or infinite.
+    pub fn is_finite(&self) -> bool {
+        [self.a, self.b, self.c]
+            .iter()
+            .all(|v| v.iter().all(|c| c.is_finite()))
+    }
+
+    /// Axis along which normal has the largest component, `None` for degenerate triangle.
+    pub fn dominant_axis(&self) -> Option<Axis> {
+        let normal = self.normal().abs();
+
+        if normal.x == 0.0 && normal.y == 0.0 && normal.z == 0.0 {
+            None
+        } else if normal.x >= normal.y && normal.x >= normal.z {
+            Some(Axis::X)
+        } else if normal.y >= normal.z {
+            Some(Axis::Y)
+        } else {
+            Some(Axis::Z)
+        }
+    }
+
     pub fn bounding_box(&self) -> BoundingBox {
         let min_x = self.a.x.min(self.b.x).min(self.c.x) - 1.0;
         let min_y = self.a.y.min(self.b.y).min(self.c.y) - 1.0;
@@ -228,6 +608,34 @@ impl Triangle {
     }
 }
 
+/// Expands triangle strip, where every vertex after the first two adds a triangle with the two
+/// previous ones. Every second triangle is flipped, so all of them keep winding of the first.
+pub fn triangles_from_strip(vertices: &[Vector3<f32>]) -> Vec<Triangle> {
+    vertices
+        .windows(3)
+        .enumerate()
+        .map(|(i, v)| {
+            if i % 2 == 0 {
+                Triangle::new(v[0], v[1], v[2])
+            } else {
+                Triangle::new(v[1], v[0], v[2])
+            }
+        })
+        .collect()
+}
+
+/// Expands triangle fan, where every vertex after the first two adds a triangle with the
+/// previous one and the first vertex.
+pub fn triangles_from_fan(vertices: &[Vector3<f32>]) -> Vec<Triangle> {
+    match vertices.split_first() {
+        Some((first, rest)) => rest
+            .windows(2)
+            .map(|v| Triangle::new(*first, v[0], v[1]))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
 fn min_max_overlaps(box_half_size: f32, v0: f32, v1: f32, v2: f32) -> bool {
     let min = v0.min(v1).min(v2);
     let max = v0.max(v1).max(v2);
@@ -240,6 +648,7 @@ fn axis_test_zy(
     point2: &Vector3<f32>,
     box_half_size: &Vector3<f32>,
     edge: &Vector3<f32>,
+    epsilon: f32,
 ) -> bool {
     axis_test(
         edge.z,
@@ -251,6 +660,7 @@ fn axis_test_zy(
         box_half_size.y,
         box_half_size.z,
         false,
+        epsilon,
     )
 }
 
@@ -259,6 +669,7 @@ fn axis_test_mzx(
     point2: &Vector3<f32>,
     box_half_size: &Vector3<f32>,
     edge: &Vector3<f32>,
+    epsilon: f32,
 ) -> bool {
     axis_test(
         -edge.z,
@@ -270,6 +681,7 @@ fn axis_test_mzx(
         box_half_size.x,
         box_half_size.z,
         true,
+        epsilon,
     )
 }
 
@@ -278,6 +690,7 @@ fn axis_test_yx(
     point2: &Vector3<f32>,
     box_half_size: &Vector3<f32>,
     edge: &Vector3<f32>,
+    epsilon: f32,
 ) -> bool {
     axis_test(
         edge.y,
@@ -289,6 +702,7 @@ fn axis_test_yx(
         box_half_size.x,
         box_half_size.y,
         false,
+        epsilon,
     )
 }
 
@@ -302,6 +716,7 @@ fn axis_test(
     box_half_size_axis1: f32,
     box_half_size_axis2: f32,
     sign: bool,
+    epsilon: f32,
 ) -> bool {
     let p1 = if sign {
         edge_axis1 * point1_axis1 + edge_axis2 * point1_axis2
@@ -319,6 +734,7 @@ fn axis_test(
     let max = p1.max(p2);
 
     let radius = edge_axis1.abs() * box_half_size_axis1 + edge_axis2.abs() * box_half_size_axis2;
+    let radius = radius + epsilon * (edge_axis1 * edge_axis1 + edge_axis2 * edge_axis2).sqrt();
 
     if min > radius || max < -radius {
         return false;
@@ -329,7 +745,11 @@ fn axis_test(
 
 #[cfg(test)]
 mod tests {
-    use crate::geometry::{BoundingBox, LocalVector, Triangle};
+    use crate::geometry::{
+        center_from_origin, parse_vector, triangles_from_fan, triangles_from_strip, Axis,
+        BoundingBox, LocalVector, MeshTransform, Triangle, DEFAULT_EPSILON,
+    };
+    use crate::testutil::Lcg;
     use nalgebra::Vector3;
 
     #[test]
@@ -348,6 +768,81 @@ mod tests {
         assert!(!triangle.is_inside(&Vector3::new(-3, 6, -2)));
     }
 
+    #[test]
+    fn test_is_inside_with_epsilon() {
+        // Triangle lies in plane x = 0.5 + 0.01, just past the face shared by cells 0 and 1.
+        let a = Vector3::new(0.51, -5.0, -5.0);
+        let b = Vector3::new(0.51, 5.0, -5.0);
+        let c = Vector3::new(0.51, 0.0, 5.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let vector = Vector3::new(0, 0, 0);
+
+        assert!(!triangle.is_inside(&vector));
+        assert!(!triangle.is_inside_with_epsilon(&vector, 0.0));
+        assert!(triangle.is_inside_with_epsilon(&vector, 0.02));
+        assert!(triangle.is_inside_with_epsilon(&Vector3::new(1, 0, 0), 0.0));
+        assert!(!triangle.is_inside_with_epsilon(&Vector3::new(1, 0, 0), -0.5));
+    }
+
+    #[test]
+    fn test_is_inside_coincident_plane() {
+        let layers_with_epsilon = |z: f32, epsilon: f32| {
+            let a = Vector3::new(-5.0, -5.0, z);
+            let b = Vector3::new(5.0, -5.0, z);
+            let c = Vector3::new(0.0, 5.0, z);
+
+            let triangle = Triangle::new(a, b, c);
+
+            (0..10)
+                .filter(|&layer| {
+                    triangle.is_inside_with_epsilon(&Vector3::new(0, 0, layer), epsilon)
+                })
+                .collect::<Vec<i32>>()
+        };
+        let layers = |z: f32| layers_with_epsilon(z, DEFAULT_EPSILON);
+
+        assert_eq!(layers(5.0), vec![5]);
+        assert_eq!(layers(5.5), vec![5]);
+        assert_eq!(layers(5.5 + 1e-6), vec![5]);
+        assert_eq!(layers(5.5 - 1e-6), vec![5]);
+        assert_eq!(layers(5.6), vec![6]);
+
+        // Larger epsilon doesn't widen the face, plane near it grazes both layers.
+        assert_eq!(layers_with_epsilon(5.5, 0.1), vec![5]);
+        assert_eq!(layers_with_epsilon(5.45, 0.1), vec![5, 6]);
+    }
+
+    #[test]
+    fn test_is_inside_reference() {
+        let mut lcg = Lcg::new(0x9e37_79b9_7f4a_7c15);
+
+        // Coordinates between -4 and 4 with fractional part.
+        let mut next = move || ((lcg.next_u64() >> 40) % 8000) as f32 / 1000.0 - 4.0;
+
+        for _ in 0..50 {
+            let a = Vector3::new(next(), next(), next());
+            let b = Vector3::new(next(), next(), next());
+            let c = Vector3::new(next(), next(), next());
+
+            let triangle = Triangle::new(a, b, c);
+
+            assert_eq!(triangle.is_inside_mismatches(32), vec![], "{:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn test_centroid() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(6.0, 0.0, 3.0);
+        let c = Vector3::new(0.0, 3.0, -6.0);
+
+        assert_eq!(
+            Triangle::new(a, b, c).centroid(),
+            Vector3::new(2.0, 1.0, -1.0)
+        );
+    }
+
     #[test]
     fn test_scale() {
         let a = Vector3::new(0.0, 0.0, 0.0);
@@ -366,6 +861,152 @@ mod tests {
         assert_eq!(scaled_triangle.c, scaled_c);
     }
 
+    #[test]
+    fn test_mirror() {
+        let a = Vector3::new(1.0, 0.0, 0.0);
+        let b = Vector3::new(3.0, 0.0, 0.0);
+        let c = Vector3::new(1.0, 2.0, 0.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let normal = triangle.normal();
+        let mirrored_triangle = triangle.mirror(Axis::X);
+
+        assert_eq!(mirrored_triangle.a, Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(mirrored_triangle.b, Vector3::new(-1.0, 2.0, 0.0));
+        assert_eq!(mirrored_triangle.c, Vector3::new(-3.0, 0.0, 0.0));
+
+        // Triangle facing up must still face up, so back-face culling keeps working.
+        assert!(normal.z > 0.0);
+        assert_eq!(mirrored_triangle.normal(), normal);
+    }
+
+    #[test]
+    fn test_rotate_z() {
+        let triangle = Triangle::new(
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+            Vector3::new(0.0, 0.0, 3.0),
+        );
+
+        let rotated = triangle.rotate_z(std::f32::consts::FRAC_PI_2);
+
+        assert!((rotated.a - Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-6);
+        assert!((rotated.b - Vector3::new(-2.0, 0.0, 0.0)).norm() < 1e-6);
+        assert_eq!(rotated.c, Vector3::new(0.0, 0.0, 3.0));
+
+        let moved = rotated.translate(&Vector3::new(1.0, 1.0, 1.0));
+
+        assert_eq!(moved.c, Vector3::new(1.0, 1.0, 4.0));
+    }
+
+    #[test]
+    fn test_mesh_transform() {
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(1.0, 2.0, 3.0),
+                Vector3::new(-4.0, 0.5, 2.0),
+                Vector3::new(0.0, -3.0, -1.0),
+            )
+        };
+
+        let transform = MeshTransform {
+            scale: 2.5,
+            pivot: Vector3::new(1.0, 1.0, -2.0),
+            rotate_z: 0.7,
+            mirror: [true, false, true],
+            translate: Vector3::new(10.0, -20.0, 5.0),
+        };
+
+        let transformed = triangle().transform(&transform.matrix());
+        let chained = triangle()
+            .scale_about(Vector3::new(1.0, 1.0, -2.0), 2.5)
+            .rotate_z(0.7)
+            .mirror(Axis::X)
+            .mirror(Axis::Z)
+            .translate(&Vector3::new(10.0, -20.0, 5.0));
+
+        assert!((transformed.a - chained.a).norm() < 1e-4);
+        assert!((transformed.b - chained.b).norm() < 1e-4);
+        assert!((transformed.c - chained.c).norm() < 1e-4);
+
+        // Single mirror turns the triangle inside out, the normal is kept facing outside.
+        let mirrored = MeshTransform {
+            mirror: [false, true, false],
+            ..MeshTransform::default()
+        };
+
+        let chained = triangle().mirror(Axis::Y);
+        let transformed = triangle().transform(&mirrored.matrix());
+
+        assert_eq!((transformed.b, transformed.c), (chained.b, chained.c));
+
+        let identity = triangle().transform(&MeshTransform::default().matrix());
+        assert_eq!(identity.a, triangle().a);
+    }
+
+    #[test]
+    fn test_scale_about() {
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(10.0, 20.0, 3.0),
+                Vector3::new(14.0, 20.0, 3.0),
+                Vector3::new(12.0, 26.0, 6.0),
+            )
+        };
+        let centroid = triangle().centroid();
+
+        let scaled = triangle().scale_about(centroid, 3.0);
+
+        assert!((scaled.centroid() - centroid).norm() < 1e-4);
+        assert_eq!(scaled.b - scaled.a, Vector3::new(12.0, 0.0, 0.0));
+
+        // Scaling about the origin moves the centroid too.
+        let scaled = triangle().scale_about(Vector3::zeros(), 3.0);
+
+        assert_eq!(scaled.a, triangle().scale(3.0).a);
+        assert!((scaled.centroid() - centroid * 3.0).norm() < 1e-4);
+    }
+
+    #[test]
+    fn test_triangles_from_strip() {
+        let v: Vec<Vector3<f32>> = (0..5).map(|i| Vector3::new(i as f32, 0.0, 0.0)).collect();
+        let triangles = triangles_from_strip(&v);
+
+        let corners: Vec<_> = triangles.iter().map(|t| (t.a, t.b, t.c)).collect();
+
+        assert_eq!(
+            corners,
+            vec![(v[0], v[1], v[2]), (v[2], v[1], v[3]), (v[2], v[3], v[4])]
+        );
+
+        // Zigzag strip in xy plane, all triangles face the same way.
+        let zigzag: Vec<Vector3<f32>> = (0..5)
+            .map(|i| Vector3::new((i / 2) as f32, (i % 2) as f32, 0.0))
+            .collect();
+
+        assert!(triangles_from_strip(&zigzag)
+            .iter()
+            .all(|t| t.normal().z < 0.0));
+
+        assert!(triangles_from_strip(&v[..2]).is_empty());
+    }
+
+    #[test]
+    fn test_triangles_from_fan() {
+        let v: Vec<Vector3<f32>> = (0..5).map(|i| Vector3::new(0.0, i as f32, 0.0)).collect();
+        let triangles = triangles_from_fan(&v);
+
+        let corners: Vec<_> = triangles.iter().map(|t| (t.a, t.b, t.c)).collect();
+
+        assert_eq!(
+            corners,
+            vec![(v[0], v[1], v[2]), (v[0], v[2], v[3]), (v[0], v[3], v[4])]
+        );
+
+        assert!(triangles_from_fan(&v[..2]).is_empty());
+        assert!(triangles_from_fan(&[]).is_empty());
+    }
+
     #[test]
     fn test_bounding_box() {
         let a = Vector3::new(0.0, 0.0, 0.0);
@@ -421,6 +1062,84 @@ mod tests {
         assert_eq!(local7, LocalVector::new(500, 500, 500));
     }
 
+    #[test]
+    fn test_from_world_vector_odd() {
+        fn to_local_vector(vector: &Vector3<f32>) -> LocalVector {
+            LocalVector::from_world_vector(vector, &Vector3::new(0.0, 0.0, 0.0), 501, 501)
+        }
+
+        let local1 = to_local_vector(&Vector3::new(0.0, 0.0, 0.0));
+        let local2 = to_local_vector(&Vector3::new(-250.0, -250.0, -250.0));
+        let local3 = to_local_vector(&Vector3::new(250.0, 250.0, 250.0));
+        let local4 = to_local_vector(&Vector3::new(-251.0, -251.0, -251.0));
+        let local5 = to_local_vector(&Vector3::new(252.0, 252.0, 252.0));
+
+        assert_eq!(local1, LocalVector::new(250, 250, 250));
+        assert_eq!(local2, LocalVector::new(0, 0, 0));
+        assert_eq!(local3, LocalVector::new(500, 500, 500));
+        assert_eq!(local4, LocalVector::new(0, 0, 0));
+        assert_eq!(local5, LocalVector::new(501, 501, 501));
+    }
+
+    #[test]
+    fn test_parse_vector() {
+        assert_eq!(parse_vector("1, -2.5,3"), Ok(Vector3::new(1.0, -2.5, 3.0)));
+        assert_eq!(parse_vector("4,0,7"), Ok(Vector3::new(4u32, 0, 7)));
+        assert!(parse_vector::<u32>("1,-2,3").is_err());
+        assert!(parse_vector::<f32>("1,2").is_err());
+        assert!(parse_vector::<f32>("1,2,3,4").is_err());
+    }
+
+    #[test]
+    fn test_try_from_world_vector() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let to_local_vector = |x: f32, z: f32| {
+            LocalVector::try_from_world_vector(&Vector3::new(x, 0.0, z), &center, 500, 10)
+        };
+
+        assert_eq!(
+            to_local_vector(0.0, 0.0),
+            Some(LocalVector::new(250, 250, 5))
+        );
+        assert_eq!(
+            to_local_vector(-250.0, -5.0),
+            Some(LocalVector::new(0, 250, 0))
+        );
+        assert_eq!(
+            to_local_vector(249.0, 4.0),
+            Some(LocalVector::new(499, 250, 9))
+        );
+        assert_eq!(to_local_vector(250.0, 0.0), None);
+        assert_eq!(to_local_vector(-251.0, 0.0), None);
+        assert_eq!(to_local_vector(0.0, 5.0), None);
+    }
+
+    #[test]
+    fn test_to_world_vector_odd() {
+        fn from_local_vector(vector: &LocalVector) -> Vector3<i32> {
+            vector.to_world_vector(&Vector3::new(0.0, 0.0, 0.0), 501, 501)
+        }
+
+        let local1 = from_local_vector(&LocalVector::new(250, 250, 250));
+        let local2 = from_local_vector(&LocalVector::new(0, 0, 0));
+        let local3 = from_local_vector(&LocalVector::new(500, 500, 500));
+
+        assert_eq!(local1, Vector3::new(0, 0, 0));
+        assert_eq!(local2, Vector3::new(-250, -250, -250));
+        assert_eq!(local3, Vector3::new(250, 250, 250));
+
+        // Every cell must map back to itself.
+        for i in 0..501 {
+            let local = LocalVector::new(i, 500 - i, i);
+            let world = from_local_vector(&local);
+            let world = Vector3::new(world.x as f32, world.y as f32, world.z as f32);
+            let round_trip =
+                LocalVector::from_world_vector(&world, &Vector3::new(0.0, 0.0, 0.0), 501, 501);
+
+            assert_eq!(round_trip, local);
+        }
+    }
+
     #[test]
     fn test_from_world_vector2() {
         fn to_local_vector(vector: &Vector3<f32>) -> LocalVector {
@@ -435,8 +1154,28 @@ mod tests {
         let local1 = to_local_vector(&Vector3::new(366.666656, -13866.666016, 84.290909));
         let local2 = to_local_vector(&Vector3::new(404.166656, -14024.999023, 22.343037));
 
+        // Cell 287 is centered at world x 404, nearest to 404.17.
         assert_eq!(local1, LocalVector::new(250, 250, 250));
-        assert_eq!(local2, LocalVector::new(288, 92, 188));
+        assert_eq!(local2, LocalVector::new(287, 92, 188));
+    }
+
+    #[test]
+    fn test_from_world_vector_far() {
+        let near = Vector3::new(0.25, 0.25, 0.25);
+        let far = near + Vector3::new(1e6, -1e6, 1e6);
+
+        for &(x, y, z) in &[(0.6, -3.4, 7.5), (-12.0, 0.5, -0.6), (15.9, -16.1, 0.0)] {
+            let offset = Vector3::new(x, y, z);
+
+            let local = LocalVector::from_world_vector(&(near + offset), &near, 32, 32);
+            let far_local = LocalVector::from_world_vector(&(far + offset), &far, 32, 32);
+
+            assert_eq!(local, far_local);
+            assert_eq!(
+                local.to_world_vector(&near, 32, 32),
+                (near + offset).map(|c| c.round() as i32)
+            );
+        }
     }
 
     #[test]
@@ -508,6 +1247,33 @@ mod tests {
         assert_eq!(bounding_box.width(), 400.0);
     }
 
+    #[test]
+    fn test_center_from_origin() {
+        let origin = Vector3::new(-32.0, 64.0, 8.0);
+
+        for &(width, height) in &[(16, 4), (15, 5), (1, 1)] {
+            let center = center_from_origin(&origin, width, height);
+
+            let first = LocalVector::new(0, 0, 0).to_world_vector(&center, width, height);
+            assert_eq!(first, Vector3::new(-32, 64, 8));
+
+            let world = Vector3::new(-32.0, 64.0, 8.0);
+            let local = LocalVector::from_world_vector(&world, &center, width, height);
+            assert_eq!(local, LocalVector::new(0, 0, 0));
+        }
+    }
+
+    #[test]
+    fn test_bounding_box_from_grid() {
+        let center = Vector3::new(200.0, 200.0, 75.0);
+        let bounding_box = BoundingBox::from_grid(&center, 400, 150);
+
+        assert_eq!(bounding_box.min, Vector3::new(-0.5, -0.5, -0.5));
+        assert_eq!(bounding_box.max, Vector3::new(399.5, 399.5, 149.5));
+        assert!(bounding_box.contains(&Vector3::new(10.0, 399.0, 75.0)));
+        assert!(!bounding_box.contains(&Vector3::new(10.0, 400.0, 75.0)));
+    }
+
     #[test]
     fn test_bounding_box_height() {
         let min = Vector3::new(-100.0, -200.0, -200.0);
@@ -1,15 +1,147 @@
-use crate::geometry::{BoundingBox, LocalVector, Triangle};
+use crate::bvh::Bvh;
+use crate::error::ConvertError;
+use crate::geometry::{
+    center_from_origin, parse_vector, Axis, BoundingBox, LocalVector, Triangle, DEFAULT_EPSILON,
+};
+use crate::occupancy::OccupancyGrid;
+use crate::octree::OctreeGrid;
+use crate::sink::GridSink;
+use crate::stats::{timed, ConversionStats, EmptyTriangleCounter, EmptyTriangles, Timings};
+use flate2::read::GzDecoder;
 use flying_pathfinding::Grid;
 use nalgebra::Vector3;
-use obj::Obj;
+use obj::{Obj, ObjData, ObjError};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::cell::Cell;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::iter::Enumerate;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::vec::IntoIter;
 
+pub mod analysis;
+pub mod bvh;
+pub mod cache;
+pub mod color;
+pub mod connectivity;
+pub mod distance;
+pub mod error;
+pub mod estimate;
+pub mod export;
 pub mod geometry;
+pub mod merge;
+pub mod occupancy;
+pub mod octree;
+pub mod preprocessors;
+pub mod render;
+pub mod sink;
+pub mod stats;
+pub mod surface;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
 
 pub trait Progress {
     fn update_progress(&self, percent: f32);
 }
 
+impl<P: Progress + ?Sized> Progress for &P {
+    fn update_progress(&self, percent: f32) {
+        (**self).update_progress(percent)
+    }
+}
+
+/// Progress update sent by `ChannelProgress`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressInfo {
+    pub percent: f32,
+}
+
+/// Sends every progress update over a channel, so another thread, for example of a GUI,
+/// can receive them. Conversion doesn't stop if the receiver is dropped.
+pub struct ChannelProgress {
+    sender: Sender<ProgressInfo>,
+}
+
+impl ChannelProgress {
+    pub fn new(sender: Sender<ProgressInfo>) -> Self {
+        ChannelProgress { sender }
+    }
+}
+
+impl Progress for ChannelProgress {
+    fn update_progress(&self, percent: f32) {
+        let _ = self.sender.send(ProgressInfo { percent });
+    }
+}
+
 pub trait Preprocessor {
+    /// Returns triangles to voxelize instead of given one, empty if it should be skipped.
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        width: u32,
+        height: u32,
+        center: Vector3<f32>,
+    ) -> Vec<Triangle>;
+}
+
+impl<P: Preprocessor + ?Sized> Preprocessor for &P {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        width: u32,
+        height: u32,
+        center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        (**self).pre_process(triangle, width, height, center)
+    }
+}
+
+impl<P: Preprocessor + ?Sized> Preprocessor for Box<P> {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        width: u32,
+        height: u32,
+        center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        (**self).pre_process(triangle, width, height, center)
+    }
+}
+
+/// Applies preprocessors one after another to every produced triangle.
+impl<P: Preprocessor> Preprocessor for Vec<P> {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        width: u32,
+        height: u32,
+        center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        let mut triangles = vec![triangle];
+
+        for preprocessor in self {
+            triangles = triangles
+                .into_iter()
+                .flat_map(|t| preprocessor.pre_process(t, width, height, center))
+                .collect();
+        }
+
+        triangles
+    }
+}
+
+/// Preprocessor which maps every triangle to at most one triangle.
+/// Wrap it into `SingleTriangle` to use it as `Preprocessor`.
+pub trait TrianglePreprocessor {
     fn pre_process(
         &self,
         triangle: Triangle,
@@ -19,6 +151,23 @@ pub trait Preprocessor {
     ) -> Option<Triangle>;
 }
 
+pub struct SingleTriangle<P: TrianglePreprocessor>(pub P);
+
+impl<P: TrianglePreprocessor> Preprocessor for SingleTriangle<P> {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        width: u32,
+        height: u32,
+        center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        self.0
+            .pre_process(triangle, width, height, center)
+            .into_iter()
+            .collect()
+    }
+}
+
 pub struct NoOpPreprocessor {}
 
 impl Preprocessor for NoOpPreprocessor {
@@ -28,217 +177,3678 @@ impl Preprocessor for NoOpPreprocessor {
         _width: u32,
         _height: u32,
         _center: Vector3<f32>,
-    ) -> Option<Triangle> {
-        Some(triangle)
+    ) -> Vec<Triangle> {
+        vec![triangle]
     }
 }
 
-pub fn convert<Prg: Progress, Pre: Preprocessor>(
-    triangles: Vec<Triangle>,
-    center: Vector3<f32>,
-    width: u32,
-    height: u32,
-    progress: Prg,
-    preprocessor: Pre,
-) -> Grid {
-    let mut obstacles = Vec::new();
-    let length = triangles.len();
-    let mut current = 0;
+/// Count of triangles voxelized between progress updates.
+const CHUNK_SIZE: usize = 128;
 
-    for triangle in triangles {
-        let processed_triangle_opt = preprocessor.pre_process(triangle, width, height, center);
+/// Smallest change of progress in percent passed to `Progress`, except the final 100%.
+const PROGRESS_STEP: f32 = 0.5;
 
-        if let Some(processed_triangle) = processed_triangle_opt {
-            obstacles.extend(find_obstacles(&processed_triangle, &center, width, height));
-        }
+/// Default of `ConvertOptions::max_cells`, a dense grid of this size takes 4 GiB.
+pub const DEFAULT_MAX_CELLS: u64 = 1 << 32;
 
-        current += 1;
+/// Reports share of processed work. Updates are sent from the converting thread only,
+/// never decrease and end with exactly 100%, even for empty meshes.
+struct ProgressReporter<Prg: Progress> {
+    progress: Prg,
+    total: u64,
+    reported: Option<f32>,
+}
 
-        let percent = current as f32 * 100.0 / length as f32;
-        progress.update_progress(percent);
+impl<Prg: Progress> ProgressReporter<Prg> {
+    fn new(progress: Prg, total: u64) -> Self {
+        ProgressReporter {
+            progress,
+            total,
+            reported: None,
+        }
     }
 
-    let mut grid = Grid::new(width, height);
+    fn update(&mut self, processed: u64) {
+        // Division may round, so all work processed is always reported as 100%.
+        let percent = if processed >= self.total {
+            100.0
+        } else {
+            (processed as f64 * 100.0 / self.total as f64) as f32
+        };
 
-    for obstacle in obstacles {
-        grid.set_obstacle(obstacle.x, obstacle.y, obstacle.z);
+        let report = match self.reported {
+            Some(reported) => {
+                percent - reported >= PROGRESS_STEP || (percent == 100.0 && reported < 100.0)
+            }
+            None => true,
+        };
+
+        if report {
+            self.progress.update_progress(percent);
+            self.reported = Some(percent);
+        }
     }
 
-    grid
+    fn finish(&mut self) {
+        self.update(self.total);
+    }
 }
 
-pub fn parse_triangles(obj: &Obj) -> Vec<Triangle> {
-    let data = &obj.data;
-    let positions = &data.position;
+/// Work of a single triangle, progress is the share of processed work.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ProgressWeight {
+    /// Every triangle is the same amount of work.
+    Count,
+    /// Work is count of grid cells in triangle bounding box, which are tested for obstacles,
+    /// so progress follows conversion time even if triangle sizes differ a lot.
+    Cells,
+}
 
-    data.objects
-        .iter()
-        .flat_map(|obj| &obj.groups)
-        .flat_map(|group| &group.polys)
-        .map(|poly| &poly.0)
-        .filter(|indices| indices.len() == 3)
-        .map(|indices| {
-            let position1 = positions[indices[0].0];
-            let position2 = positions[indices[1].0];
-            let position3 = positions[indices[2].0];
-
-            let a = Vector3::new(position1[0], position1[1], position1[2]);
-            let b = Vector3::new(position2[0], position2[1], position2[2]);
-            let c = Vector3::new(position3[0], position3[1], position3[2]);
-
-            Triangle::new(a, b, c)
-        })
-        .collect()
+impl FromStr for ProgressWeight {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "count" => Ok(ProgressWeight::Count),
+            "cells" => Ok(ProgressWeight::Cells),
+            _ => Err(format!("Unknown progress weight: {}", s)),
+        }
+    }
 }
 
-/// To find obstacles we check every point in triangle bounding box.
-fn find_obstacles(
-    triangle: &Triangle,
-    center: &Vector3<f32>,
-    width: u32,
-    height: u32,
-) -> Vec<LocalVector> {
-    let bounding_box = triangle.bounding_box();
+impl ProgressWeight {
+    /// Work of the triangle before preprocessing, at least 1.
+    fn work(self, triangle: &Triangle, width: u32, height: u32) -> u64 {
+        match self {
+            ProgressWeight::Count => 1,
+            ProgressWeight::Cells => {
+                let bounding_box = triangle.bounding_box();
+                let size = bounding_box.max - bounding_box.min;
 
-    // Convert bounding box to local coordinates.
-    let min = LocalVector::from_world_vector(&bounding_box.min, &center, width, height);
-    let max = LocalVector::from_world_vector(&bounding_box.max, &center, width, height);
+                // Cells outside of the grid are never tested.
+                let cells = |extent: f32, limit: u32| (extent.ceil() as u64).min(limit as u64);
 
-    let mut obstacles = Vec::new();
+                let work = cells(size.x, width) * cells(size.y, width) * cells(size.z, height);
+                work.max(1)
+            }
+        }
+    }
+}
 
-    for x in min.x..max.x {
-        for y in min.y..max.y {
-            for z in min.z..max.z {
-                let local_vector = LocalVector::new(x, y, z);
+/// Handling of triangles which aren't fully inside of the grid after preprocessing.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum OutOfBounds {
+    /// Parts outside of the grid are clamped to its borders.
+    Clamp,
+    /// Triangle is skipped.
+    Drop,
+    /// Conversion fails with `ConvertError::OutOfBounds`.
+    Error,
+    /// Same as `Clamp`, but conversion fails with `ConvertError::Clipped` at the end, so the
+    /// error reports every triangle that was clamped.
+    Strict,
+}
 
-                // Triangle coordinates are global.
-                let global_vector = local_vector.to_world_vector(&center, width, height);
+impl FromStr for OutOfBounds {
+    type Err = String;
 
-                if triangle.is_inside(&global_vector) {
-                    obstacles.push(local_vector);
-                }
-            }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(OutOfBounds::Clamp),
+            "drop" => Ok(OutOfBounds::Drop),
+            "error" => Ok(OutOfBounds::Error),
+            "strict" => Ok(OutOfBounds::Strict),
+            _ => Err(format!("Unknown out of bounds mode: {}", s)),
         }
     }
+}
 
-    obstacles
+/// Test deciding which cells are marked as obstacles of a triangle.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum VoxelMode {
+    /// Exact triangle-box overlap test, every cell the triangle touches is marked.
+    Precise,
+    /// Marks cell only if its center is within half a cell of triangle plane and projects
+    /// inside of the triangle. Faster, but cells the triangle only crosses at their edges are
+    /// missed, so meshes can get holes and triangles thinner than a cell can disappear.
+    /// Epsilon is ignored.
+    CenterSample,
 }
 
-pub fn bounding_box(triangles: &Vec<Triangle>) -> BoundingBox {
-    let bounding_boxes: Vec<BoundingBox> = triangles.iter().map(|t| t.bounding_box()).collect();
+impl FromStr for VoxelMode {
+    type Err = String;
 
-    let min_x = bounding_boxes
-        .iter()
-        .map(|b| b.min)
-        .map(|m| m.x.round() as i32)
-        .min()
-        .unwrap_or(0) as f32;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "precise" => Ok(VoxelMode::Precise),
+            "center-sample" => Ok(VoxelMode::CenterSample),
+            _ => Err(format!("Unknown voxel mode: {}", s)),
+        }
+    }
+}
 
-    let min_y = bounding_boxes
-        .iter()
-        .map(|b| b.min)
-        .map(|m| m.y.round() as i32)
-        .min()
-        .unwrap_or(0) as f32;
+/// Handling of triangles with NaN or infinite coordinates.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NonFinite {
+    Skip,
+    /// Parsing fails with `ConvertError::NonFinite`.
+    Error,
+}
 
-    let min_z = bounding_boxes
-        .iter()
-        .map(|b| b.min)
-        .map(|m| m.z.round() as i32)
-        .min()
-        .unwrap_or(0) as f32;
+impl FromStr for NonFinite {
+    type Err = String;
 
-    let max_x = bounding_boxes
-        .iter()
-        .map(|b| b.max)
-        .map(|m| m.x.round() as i32)
-        .max()
-        .unwrap_or(0) as f32;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(NonFinite::Skip),
+            "error" => Ok(NonFinite::Error),
+            _ => Err(format!("Unknown non-finite mode: {}", s)),
+        }
+    }
+}
 
-    let max_y = bounding_boxes
-        .iter()
-        .map(|b| b.max)
-        .map(|m| m.y.round() as i32)
-        .max()
-        .unwrap_or(0) as f32;
+/// Offset added to vertices of object or group with given name. Wavefront obj has no object
+/// transforms, so vertices are expected to be in world space unless placement is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub name: String,
+    pub offset: Vector3<f32>,
+}
 
-    let max_z = bounding_boxes
-        .iter()
-        .map(|b| b.max)
-        .map(|m| m.z.round() as i32)
-        .max()
-        .unwrap_or(0) as f32;
+impl FromStr for Placement {
+    type Err = String;
 
-    let min = Vector3::new(min_x, min_y, min_z);
-    let max = Vector3::new(max_x, max_y, max_z);
+    /// Parses placement given as `name=x,y,z`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, offset) = match s.rfind('=') {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => return Err(format!("Placement must be name=x,y,z: {}", s)),
+        };
 
-    BoundingBox { min, max }
+        let offset =
+            parse_vector(offset).map_err(|e| format!("Invalid placement offset: {}", e))?;
+
+        Ok(Placement {
+            name: name.to_string(),
+            offset,
+        })
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::geometry::{LocalVector, Triangle};
-    use crate::{bounding_box, find_obstacles};
-    use nalgebra::Vector3;
+/// Count of cells added on both sides of every surface cell, along axis closest to
+/// triangle normal. Thickens walls without filling the mesh.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct WallThickness(pub u32);
 
-    #[test]
-    fn test_find_obstacles() {
-        let a = Vector3::new(0.0, 0.0, 0.0);
-        let b = Vector3::new(5.0, 5.0, 0.0);
-        let c = Vector3::new(-5.0, -5.0, 0.0);
+/// Decides if cell in grid local coordinates may become an obstacle.
+pub type ObstacleFilter = dyn Fn(LocalVector) -> bool + Sync;
 
-        let triangle = Triangle::new(a, b, c);
-        let center = Vector3::new(0.0, 0.0, 0.0);
+pub struct ConvertOptions<'a> {
+    /// Tolerance of the triangle-box overlap test, see `Triangle::is_inside_with_epsilon`.
+    /// Triangles are tested after `to_cells`, so it is in cell units, not in world units.
+    pub epsilon: f32,
+    /// Count of threads used for voxelization, `None` or zero uses all cores.
+    /// Ignored if crate is built without `parallel` feature.
+    pub threads: Option<usize>,
+    pub out_of_bounds: OutOfBounds,
+    pub mode: VoxelMode,
+    pub wall_thickness: WallThickness,
+    /// World position of local (0, 0, 0), overrides center passed to conversion.
+    /// `None` keeps the grid centered.
+    pub origin: Option<Vector3<f32>>,
+    /// Called for every cell found by voxelization, cells it returns false for aren't set.
+    pub obstacle_filter: Option<&'a ObstacleFilter>,
+    /// Marks cell of the centroid of every non-degenerate triangle which has no obstacles,
+    /// for example small triangle grazing cells with negative epsilon.
+    pub ensure_nonempty: bool,
+    pub progress_weight: ProgressWeight,
+    /// Cells added on every side of the range scanned for obstacles of a triangle, so cells
+    /// grazed with large epsilon aren't missed. Every cell makes conversion slower.
+    pub scan_padding: u32,
+    /// Conversion fails with `ConvertError::GridTooLarge` if the grid has more cells,
+    /// so a typo in dimensions doesn't exhaust memory.
+    pub max_cells: u64,
+    /// Incremented for every cell tested against a triangle, like by `Triangle::is_inside`.
+    /// Many more tests than obstacles mean bounding boxes are too large for their triangles.
+    pub inside_tests: Option<&'a AtomicU64>,
+    /// Counts triangles which didn't mark any cell by reason. Triangles are counted after
+    /// preprocessing, so a triangle split in parts may be counted more than once.
+    pub empty_triangles: Option<&'a EmptyTriangleCounter>,
+    /// Size of a cell along x and y in units of triangles, center and `origin`.
+    pub cell_size_xy: f32,
+    /// Size of a cell along z, so cells twice as tall as wide take half of the layers.
+    pub cell_size_z: f32,
+}
 
-        let obstacles = find_obstacles(&triangle, &center, 10, 10);
+impl Default for ConvertOptions<'_> {
+    fn default() -> Self {
+        ConvertOptions {
+            epsilon: DEFAULT_EPSILON,
+            threads: None,
+            out_of_bounds: OutOfBounds::Clamp,
+            mode: VoxelMode::Precise,
+            wall_thickness: WallThickness::default(),
+            origin: None,
+            obstacle_filter: None,
+            ensure_nonempty: false,
+            progress_weight: ProgressWeight::Count,
+            scan_padding: 0,
+            max_cells: DEFAULT_MAX_CELLS,
+            inside_tests: None,
+            empty_triangles: None,
+            cell_size_xy: 1.0,
+            cell_size_z: 1.0,
+        }
+    }
+}
 
-        let expected_obstacles = vec![
-            LocalVector::new(0, 0, 5),
-            LocalVector::new(0, 1, 5),
-            LocalVector::new(1, 0, 5),
-            LocalVector::new(1, 1, 5),
-            LocalVector::new(1, 2, 5),
-            LocalVector::new(2, 1, 5),
-            LocalVector::new(2, 2, 5),
-            LocalVector::new(2, 3, 5),
-            LocalVector::new(3, 2, 5),
-            LocalVector::new(3, 3, 5),
-            LocalVector::new(3, 4, 5),
-            LocalVector::new(4, 3, 5),
-            LocalVector::new(4, 4, 5),
-            LocalVector::new(4, 5, 5),
-            LocalVector::new(5, 4, 5),
-            LocalVector::new(5, 5, 5),
-            LocalVector::new(5, 6, 5),
-            LocalVector::new(6, 5, 5),
-            LocalVector::new(6, 6, 5),
-            LocalVector::new(6, 7, 5),
-            LocalVector::new(7, 6, 5),
-            LocalVector::new(7, 7, 5),
-            LocalVector::new(7, 8, 5),
-            LocalVector::new(8, 7, 5),
-            LocalVector::new(8, 8, 5),
-            LocalVector::new(8, 9, 5),
-            LocalVector::new(9, 8, 5),
-            LocalVector::new(9, 9, 5),
-        ];
+impl ConvertOptions<'_> {
+    /// Center of the grid in cells, taking `origin` and cell sizes into account.
+    pub fn grid_center(&self, center: Vector3<f32>, width: u32, height: u32) -> Vector3<f32> {
+        let scale = self.cell_scale();
 
-        assert_eq!(expected_obstacles, obstacles)
+        match &self.origin {
+            Some(origin) => center_from_origin(&origin.component_mul(&scale), width, height),
+            None => center.component_mul(&scale),
+        }
     }
 
-    #[test]
-    fn test_bounding_box() {
-        let a = Vector3::new(0.0, 0.0, 0.0);
-        let b = Vector3::new(5.0, 5.0, 0.0);
-        let c = Vector3::new(-5.0, -5.0, 0.0);
+    /// Count of cells per unit of triangles on every axis.
+    pub fn cell_scale(&self) -> Vector3<f32> {
+        Vector3::new(
+            1.0 / self.cell_size_xy,
+            1.0 / self.cell_size_xy,
+            1.0 / self.cell_size_z,
+        )
+    }
 
-        let triangle = Triangle::new(a, b, c);
-        let bounding_box = bounding_box(&vec![triangle]);
+    /// Obstacles mapped back to world positions, scaled by cell sizes on every axis.
+    pub fn world_positions(
+        &self,
+        obstacles: &[LocalVector],
+        center: Vector3<f32>,
+        width: u32,
+        height: u32,
+    ) -> Vec<Vector3<f32>> {
+        let center = self.grid_center(center, width, height);
+        let size = Vector3::new(self.cell_size_xy, self.cell_size_xy, self.cell_size_z);
 
-        let min = Vector3::new(-6.0, -6.0, -1.0);
-        let max = Vector3::new(6.0, 6.0, 1.0);
+        to_world_vectors(obstacles, &center, width, height)
+            .iter()
+            .map(|v| v.map(|c| c as f32).component_mul(&size))
+            .collect()
+    }
 
-        assert_eq!(bounding_box.min, min);
-        assert_eq!(bounding_box.max, max);
+    /// Triangle scaled to cells, so the unit cube overlap test of `Triangle::is_inside` checks
+    /// a box of cell sizes. Scaling on any axis keeps the triangle overlapping the same boxes.
+    pub fn to_cells(&self, triangle: &Triangle) -> Triangle {
+        let scale = self.cell_scale();
+
+        Triangle::new(
+            triangle.a.component_mul(&scale),
+            triangle.b.component_mul(&scale),
+            triangle.c.component_mul(&scale),
+        )
+    }
+}
+
+pub fn convert<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+) -> Grid {
+    convert_with_options(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        &ConvertOptions::default(),
+    )
+    .expect("Default options never fail")
+}
+
+pub fn convert_with_options<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<Grid, ConvertError> {
+    convert_into(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )
+}
+
+/// Same as `convert_with_options`, but fills a grid of any backend.
+pub fn convert_into<S: GridSink, Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<S, ConvertError> {
+    let occupancy = voxelize_occupancy(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )?;
+
+    Ok(occupancy.to_sink())
+}
+
+/// Same as `convert_with_options`, but also measures time spent in every phase and counts
+/// triangles which didn't mark any cell. Parse and export timings are left for the caller to fill.
+/// Cell tests and empty triangles are always counted, counters of `options` are replaced.
+pub fn convert_with_stats<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<(Grid, ConversionStats), ConvertError> {
+    let (obstacles, mut stats) = voxelize_with_stats(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )?;
+
+    let ((grid, clamped_obstacles), populate_duration) =
+        timed(|| create_grid_checked(&obstacles, width, height));
+
+    stats.clamped_obstacles = clamped_obstacles;
+    stats.timings.populate = populate_duration;
+
+    Ok((grid, stats))
+}
+
+/// First phases of `convert_with_stats`: voxelizes and deduplicates obstacles, for callers
+/// which change obstacles before filling a grid. Only voxelize and dedup timings are filled.
+pub fn voxelize_with_stats<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<(Vec<LocalVector>, ConversionStats), ConvertError> {
+    let length = triangles.len();
+
+    let inside_tests = AtomicU64::new(0);
+    let empty_triangles = EmptyTriangleCounter::default();
+    let counted_options = ConvertOptions {
+        inside_tests: Some(&inside_tests),
+        empty_triangles: Some(&empty_triangles),
+        ..*options
+    };
+
+    let (obstacles, voxelize_duration) = timed(|| {
+        voxelize(
+            triangles,
+            center,
+            width,
+            height,
+            progress,
+            preprocessor,
+            &counted_options,
+        )
+    });
+
+    let obstacles = obstacles?;
+
+    let (obstacles, dedup_duration) = timed(|| dedup_obstacles(obstacles));
+
+    let stats = ConversionStats {
+        triangles: length,
+        obstacles: obstacles.len(),
+        empty_triangles: empty_triangles.counts(),
+        inside_tests: Some(inside_tests.into_inner()),
+        timings: Timings {
+            voxelize: voxelize_duration,
+            dedup: dedup_duration,
+            ..Timings::default()
+        },
+        ..ConversionStats::default()
+    };
+
+    Ok((obstacles, stats))
+}
+
+/// Counts triangles which don't mark any cell by reason, to explain empty or sparse grids,
+/// without converting them. Triangles are in grid units like in `voxelize`, preprocessors
+/// aren't applied. Cells tested here aren't added to `ConvertOptions::inside_tests`.
+pub fn empty_triangles(
+    triangles: &[Triangle],
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> EmptyTriangles {
+    let empty = EmptyTriangleCounter::default();
+    let options = &ConvertOptions {
+        inside_tests: None,
+        empty_triangles: Some(&empty),
+        ..*options
+    };
+    let center = options.grid_center(center, width, height);
+
+    for triangle in triangles {
+        find_obstacles_with_options(&options.to_cells(triangle), &center, width, height, options);
+    }
+
+    empty.counts()
+}
+
+/// Same as `convert_with_options`, but stores obstacles in sparse `OctreeGrid`.
+/// Obstacles are found lazily by `obstacle_iter`, so they are never all held in a list.
+pub fn convert_octree<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<OctreeGrid, ConvertError> {
+    let mut grid = OctreeGrid::new(width, height);
+
+    // Grid without cells has no last cell to move obstacles to.
+    if width == 0 || height == 0 {
+        return Ok(grid);
+    }
+
+    for obstacle in obstacle_iter(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    ) {
+        let obstacle = obstacle?;
+
+        // Same as `create_grid`, obstacles outside of the grid are moved to the last cell.
+        grid.set_obstacle(
+            obstacle.x.min(width - 1),
+            obstacle.y.min(width - 1),
+            obstacle.z.min(height - 1),
+        );
+    }
+
+    Ok(grid)
+}
+
+/// Same as `convert_with_options`, but returns obstacles mapped back to world coordinates,
+/// scaled by `cell_size_xy` and `cell_size_z`.
+pub fn obstacles_world<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<Vec<Vector3<f32>>, ConvertError> {
+    let obstacles = voxelize(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )?;
+
+    let obstacles = dedup_obstacles(obstacles);
+
+    Ok(options.world_positions(&obstacles, center, width, height))
+}
+
+/// Voxelizes triangles into existing grid, keeping its obstacles.
+pub fn add_triangles<Prg: Progress, Pre: Preprocessor>(
+    grid: &mut OccupancyGrid,
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<(), ConvertError> {
+    let obstacles = voxelize(
+        triangles,
+        center,
+        grid.width(),
+        grid.height(),
+        progress,
+        preprocessor,
+        options,
+    )?;
+
+    for obstacle in obstacles {
+        grid.set_obstacle(obstacle.x, obstacle.y, obstacle.z);
+    }
+
+    Ok(())
+}
+
+/// Finds obstacles of all triangles in grid local coordinates.
+pub fn voxelize<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<Vec<LocalVector>, ConvertError> {
+    voxelize_into(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )
+}
+
+/// Same as `voxelize`, but threads set obstacles in a bitset shared by all of them, so obstacles
+/// are never collected, sorted and deduplicated. The bitset takes `width * width * height / 8`
+/// bytes next to the returned grid. Obstacles outside of the grid are moved to the last cell,
+/// same as in `create_grid`.
+pub fn voxelize_occupancy<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<OccupancyGrid, ConvertError> {
+    let bitset: SharedBitset = voxelize_into(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )?;
+
+    Ok(bitset.into_grid())
+}
+
+/// Quick approximate conversion for previews, the grid has `factor` times less cells on every
+/// axis, rounded up. Every preview cell covers `factor` cells of the full grid on every axis
+/// starting from its first cell, like `OccupancyGrid::resample` of the full grid. Triangles and
+/// center are in cells of the full grid. Thin geometry may disappear or get thicker.
+pub fn preview<Prg: Progress>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    factor: u32,
+    progress: Prg,
+    options: &ConvertOptions,
+) -> Result<OccupancyGrid, ConvertError> {
+    let factor = factor.max(1);
+    let scale = 1.0 / factor as f32;
+    let center = options.grid_center(center, width, height);
+
+    // Corner of the full grid becomes corner of the first preview cell at the origin.
+    let corner = BoundingBox::from_grid(&center, width, height).min;
+    let half_cell = Vector3::new(0.5, 0.5, 0.5);
+
+    let triangles = triangles
+        .iter()
+        .map(|t| {
+            options
+                .to_cells(t)
+                .translate(&-corner)
+                .scale(scale)
+                .translate(&-half_cell)
+        })
+        .collect();
+
+    let options = ConvertOptions {
+        origin: Some(Vector3::zeros()),
+        cell_size_xy: 1.0,
+        cell_size_z: 1.0,
+        ..*options
+    };
+
+    voxelize_occupancy(
+        triangles,
+        Vector3::zeros(),
+        (width + factor - 1) / factor,
+        (height + factor - 1) / factor,
+        progress,
+        NoOpPreprocessor {},
+        &options,
+    )
+}
+
+/// Same as `voxelize_occupancy`, but takes triangles from the hierarchy, so the same mesh can
+/// be converted at different centers and resolutions without building it again. Triangles and
+/// center are in world units, cells are `cell_size` big. Triangles which don't overlap the
+/// grid are skipped without being voxelized, like with `triangles_in_region`.
+#[allow(clippy::too_many_arguments)]
+pub fn convert_with_index<Prg: Progress, Pre: Preprocessor>(
+    bvh: &Bvh,
+    center: Vector3<f32>,
+    cell_size: f32,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<OccupancyGrid, ConvertError> {
+    let scale = 1.0 / cell_size;
+    let center = center * scale;
+
+    let grid_box =
+        BoundingBox::from_grid(&options.grid_center(center, width, height), width, height);
+    let cell_scale = options.cell_scale();
+    let region = BoundingBox::new(
+        grid_box.min.component_div(&cell_scale) * cell_size,
+        grid_box.max.component_div(&cell_scale) * cell_size,
+    );
+
+    let triangles = bvh
+        .query(&region)
+        .into_iter()
+        .map(|t| Triangle::new(t.a * scale, t.b * scale, t.c * scale))
+        .collect();
+
+    voxelize_occupancy(
+        triangles,
+        center,
+        width,
+        height,
+        progress,
+        preprocessor,
+        options,
+    )
+}
+
+/// Collects obstacles of chunks of triangles voxelized by `voxelize_into`.
+trait ObstacleSink: Send {
+    fn new(width: u32, height: u32) -> Self;
+
+    /// Called in the conversion thread pool.
+    fn add_chunk(
+        &mut self,
+        chunk: &[Triangle],
+        center: &Vector3<f32>,
+        width: u32,
+        height: u32,
+        options: &ConvertOptions,
+    );
+}
+
+impl ObstacleSink for Vec<LocalVector> {
+    fn new(_width: u32, _height: u32) -> Self {
+        Vec::new()
+    }
+
+    fn add_chunk(
+        &mut self,
+        chunk: &[Triangle],
+        center: &Vector3<f32>,
+        width: u32,
+        height: u32,
+        options: &ConvertOptions,
+    ) {
+        self.extend(find_chunk_obstacles(chunk, center, width, height, options));
+    }
+}
+
+/// Occupancy of all threads in one bitset of `width * width * height` bits, laid out like
+/// cells of `OccupancyGrid`. Bits are set with atomic `fetch_or`, so threads never lock.
+struct SharedBitset {
+    width: u32,
+    height: u32,
+    words: Vec<AtomicU64>,
+}
+
+impl SharedBitset {
+    fn into_grid(self) -> OccupancyGrid {
+        let width = self.width as usize;
+        let mut grid = OccupancyGrid::new(self.width, self.height);
+
+        for (word_index, word) in self.words.into_iter().enumerate() {
+            let mut bits = word.into_inner();
+
+            while bits != 0 {
+                let index = word_index * 64 + bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+
+                grid.set_obstacle(
+                    (index % width) as u32,
+                    (index / width % width) as u32,
+                    (index / (width * width)) as u32,
+                );
+            }
+        }
+
+        grid
+    }
+
+    fn set_obstacles(&self, obstacles: Vec<LocalVector>) {
+        // Grid without cells has no last cell to move obstacles to.
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let width = self.width as usize;
+
+        for obstacle in obstacles {
+            let x = obstacle.x.min(self.width - 1) as usize;
+            let y = obstacle.y.min(self.width - 1) as usize;
+            let z = obstacle.z.min(self.height - 1) as usize;
+
+            let index = x + y * width + z * width * width;
+            self.words[index / 64].fetch_or(1 << (index % 64), Ordering::Relaxed);
+        }
+    }
+}
+
+impl ObstacleSink for SharedBitset {
+    fn new(width: u32, height: u32) -> Self {
+        let cells = width as usize * width as usize * height as usize;
+
+        SharedBitset {
+            width,
+            height,
+            words: (0..(cells + 63) / 64).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn add_chunk(
+        &mut self,
+        chunk: &[Triangle],
+        center: &Vector3<f32>,
+        width: u32,
+        height: u32,
+        options: &ConvertOptions,
+    ) {
+        let bitset = &*self;
+
+        chunk.par_iter().for_each(|t| {
+            bitset.set_obstacles(find_obstacles_with_options(
+                t, center, width, height, options,
+            ));
+        });
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn add_chunk(
+        &mut self,
+        chunk: &[Triangle],
+        center: &Vector3<f32>,
+        width: u32,
+        height: u32,
+        options: &ConvertOptions,
+    ) {
+        for t in chunk {
+            self.set_obstacles(find_obstacles_with_options(
+                t, center, width, height, options,
+            ));
+        }
+    }
+}
+
+/// Preprocesses triangles and voxelizes them in chunks, reporting progress after every chunk.
+fn voxelize_into<Prg: Progress, Pre: Preprocessor, S: ObstacleSink>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<S, ConvertError> {
+    check_grid_size(width, height, options.max_cells)?;
+
+    let center = options.grid_center(center, width, height);
+
+    let mut sink = S::new(width, height);
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    let length = triangles.len();
+    let mut current = 0;
+
+    let weight = options.progress_weight;
+    let total = triangles
+        .iter()
+        .map(|t| weight.work(&options.to_cells(t), width, height))
+        .sum();
+    let mut processed = 0;
+    let mut reporter = ProgressReporter::new(progress, total);
+    let mut clipped = 0;
+
+    let grid_bounding_box = BoundingBox::from_grid(&center, width, height);
+
+    #[cfg(feature = "parallel")]
+    let pool = thread_pool(options.threads)?;
+
+    for (index, triangle) in triangles.into_iter().enumerate() {
+        let triangle = options.to_cells(&triangle);
+        processed += weight.work(&triangle, width, height);
+
+        let processed_triangles = preprocessor.pre_process(triangle, width, height, center);
+        let outside = bounded_triangles(
+            processed_triangles,
+            index,
+            &grid_bounding_box,
+            options.out_of_bounds,
+            |triangle| chunk.push(triangle),
+        )?;
+
+        if outside {
+            clipped += 1;
+        }
+
+        current += 1;
+
+        if current % CHUNK_SIZE != 0 && current != length {
+            continue;
+        }
+
+        #[cfg(feature = "parallel")]
+        match &pool {
+            Some(pool) => pool.install(|| sink.add_chunk(&chunk, &center, width, height, options)),
+            None => sink.add_chunk(&chunk, &center, width, height, options),
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        sink.add_chunk(&chunk, &center, width, height, options);
+
+        chunk.clear();
+
+        reporter.update(processed);
+    }
+
+    reporter.finish();
+
+    if clipped > 0 {
+        return Err(ConvertError::Clipped(clipped));
+    }
+
+    Ok(sink)
+}
+
+/// Lazy version of `voxelize`, which finds obstacles of the next triangle only when obstacles
+/// of the previous one are consumed, so they never have to be stored all at once.
+/// Progress is updated while iterating. Voxelization is always done on the calling thread.
+pub fn obstacle_iter<'a, Prg: Progress + 'a, Pre: Preprocessor + 'a>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &'a ConvertOptions<'a>,
+) -> impl Iterator<Item = Result<LocalVector, ConvertError>> + 'a {
+    let center = options.grid_center(center, width, height);
+
+    let total = triangles
+        .iter()
+        .map(|t| {
+            options
+                .progress_weight
+                .work(&options.to_cells(t), width, height)
+        })
+        .sum();
+
+    ObstacleIter {
+        reporter: ProgressReporter::new(progress, total),
+        processed: 0,
+        clipped: 0,
+        triangles: triangles.into_iter().enumerate(),
+        obstacles: Vec::new().into_iter(),
+        grid_bounding_box: BoundingBox::from_grid(&center, width, height),
+        center,
+        width,
+        height,
+        preprocessor,
+        options,
+        failed: false,
+    }
+}
+
+struct ObstacleIter<'a, Prg: Progress, Pre: Preprocessor> {
+    triangles: Enumerate<IntoIter<Triangle>>,
+    obstacles: IntoIter<LocalVector>,
+    reporter: ProgressReporter<Prg>,
+    /// Work of triangles taken so far.
+    processed: u64,
+    /// Count of triangles outside of the grid in `OutOfBounds::Strict` mode.
+    clipped: usize,
+    grid_bounding_box: BoundingBox,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    preprocessor: Pre,
+    options: &'a ConvertOptions<'a>,
+    failed: bool,
+}
+
+impl<'a, Prg: Progress, Pre: Preprocessor> Iterator for ObstacleIter<'a, Prg, Pre> {
+    type Item = Result<LocalVector, ConvertError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        loop {
+            if let Some(obstacle) = self.obstacles.next() {
+                return Some(Ok(obstacle));
+            }
+
+            let (index, triangle) = match self.triangles.next() {
+                Some(next) => next,
+                None => {
+                    self.reporter.finish();
+
+                    if self.clipped > 0 {
+                        self.failed = true;
+                        return Some(Err(ConvertError::Clipped(self.clipped)));
+                    }
+
+                    return None;
+                }
+            };
+
+            let triangle = self.options.to_cells(&triangle);
+            self.processed += self
+                .options
+                .progress_weight
+                .work(&triangle, self.width, self.height);
+
+            let processed_triangles =
+                self.preprocessor
+                    .pre_process(triangle, self.width, self.height, self.center);
+
+            let mut obstacles = Vec::new();
+            let outside = match bounded_triangles(
+                processed_triangles,
+                index,
+                &self.grid_bounding_box,
+                self.options.out_of_bounds,
+                |triangle| {
+                    obstacles.extend(find_obstacles_with_options(
+                        &triangle,
+                        &self.center,
+                        self.width,
+                        self.height,
+                        self.options,
+                    ))
+                },
+            ) {
+                Ok(outside) => outside,
+                Err(error) => {
+                    self.failed = true;
+                    return Some(Err(error));
+                }
+            };
+
+            if outside {
+                self.clipped += 1;
+            }
+
+            self.obstacles = obstacles.into_iter();
+            self.reporter.update(self.processed);
+        }
+    }
+}
+
+/// Applies `out_of_bounds` to preprocessed parts of the triangle with given index, passes
+/// parts which should be voxelized to `add` and returns true if some of them are outside of
+/// the grid, which only happens in `OutOfBounds::Strict` mode.
+fn bounded_triangles(
+    triangles: Vec<Triangle>,
+    index: usize,
+    grid_bounding_box: &BoundingBox,
+    out_of_bounds: OutOfBounds,
+    mut add: impl FnMut(Triangle),
+) -> Result<bool, ConvertError> {
+    let mut outside = false;
+
+    for triangle in triangles {
+        let inside =
+            out_of_bounds == OutOfBounds::Clamp || grid_bounding_box.contains_triangle(&triangle);
+
+        if inside || out_of_bounds == OutOfBounds::Strict {
+            outside |= !inside;
+            add(triangle);
+        } else if out_of_bounds == OutOfBounds::Error {
+            return Err(ConvertError::OutOfBounds(index));
+        }
+    }
+
+    Ok(outside)
+}
+
+/// Builds dedicated thread pool if count of threads is set, otherwise global pool is used.
+#[cfg(feature = "parallel")]
+fn thread_pool(threads: Option<usize>) -> Result<Option<ThreadPool>, ConvertError> {
+    match threads {
+        Some(threads) if threads > 0 => {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(ConvertError::ThreadPool)?;
+
+            Ok(Some(pool))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(feature = "parallel")]
+fn find_chunk_obstacles(
+    triangles: &[Triangle],
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Vec<LocalVector> {
+    let obstacles: Vec<Vec<LocalVector>> = triangles
+        .par_iter()
+        .map(|t| find_obstacles_with_options(t, center, width, height, options))
+        .collect();
+
+    obstacles.into_iter().flatten().collect()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn find_chunk_obstacles(
+    triangles: &[Triangle],
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Vec<LocalVector> {
+    triangles
+        .iter()
+        .flat_map(|t| find_obstacles_with_options(t, center, width, height, options))
+        .collect()
+}
+
+/// Keeps triangles which may have obstacles inside of the world space region. Triangles are
+/// rejected by their bounding box only, so parts outside of the region have to be clipped,
+/// for example by `ClipPreprocessor` with grid covering the region.
+pub fn triangles_in_region(triangles: Vec<Triangle>, region: &BoundingBox) -> Vec<Triangle> {
+    triangles
+        .into_iter()
+        .filter(|t| t.bounding_box().intersects(region))
+        .collect()
+}
+
+/// Removes obstacles found by more than one triangle and sorts them by x, then y, then z,
+/// so result doesn't depend on the order triangles were processed in.
+pub fn dedup_obstacles(mut obstacles: Vec<LocalVector>) -> Vec<LocalVector> {
+    obstacles.sort_unstable();
+    obstacles.dedup();
+
+    obstacles
+}
+
+/// Adds obstacles placed by hand, like invisible walls missing in the mesh, to voxelized ones.
+/// Obstacles outside of the grid are kept, so `create_grid_checked` clamps and counts them.
+pub fn add_extra_obstacles(
+    mut obstacles: Vec<LocalVector>,
+    extra_obstacles: Vec<LocalVector>,
+) -> Vec<LocalVector> {
+    obstacles.extend(extra_obstacles);
+
+    dedup_obstacles(obstacles)
+}
+
+pub fn create_grid(obstacles: &[LocalVector], width: u32, height: u32) -> Grid {
+    let (grid, _) = create_grid_checked(obstacles, width, height);
+    grid
+}
+
+/// Fails if grid of given dimensions has more than `max_cells` cells or more than can be
+/// indexed on this platform.
+pub fn check_grid_size(width: u32, height: u32, max_cells: u64) -> Result<(), ConvertError> {
+    let cells = (width as u64)
+        .checked_mul(width as u64)
+        .and_then(|cells| cells.checked_mul(height as u64));
+
+    match cells {
+        Some(cells) if cells <= max_cells && cells <= usize::MAX as u64 => Ok(()),
+        _ => Err(ConvertError::GridTooLarge {
+            width,
+            height,
+            max_cells,
+        }),
+    }
+}
+
+/// Same as `create_grid`, but also returns count of obstacles outside of the grid.
+/// `LocalVector::from_world_vector` clamps coordinates to width and height, which are one past
+/// the last cell, so such obstacles are moved to the last cell instead of being set out of bounds.
+pub fn create_grid_checked(obstacles: &[LocalVector], width: u32, height: u32) -> (Grid, usize) {
+    create_sink_checked(obstacles, width, height)
+}
+
+/// Same as `create_grid_checked`, but fills a grid of any backend.
+pub fn create_sink_checked<S: GridSink>(
+    obstacles: &[LocalVector],
+    width: u32,
+    height: u32,
+) -> (S, usize) {
+    let mut grid = S::new(width, height);
+
+    let clamped = clamp_obstacles(
+        obstacles.iter().map(|o| (*o, ())),
+        width,
+        height,
+        |cell, _| grid.set_obstacle(cell.x, cell.y, cell.z),
+    );
+
+    (grid, clamped)
+}
+
+/// Moves obstacles outside of the grid to its last cell, passes every one to `set` with its
+/// data and returns count of moved obstacles.
+fn clamp_obstacles<T>(
+    obstacles: impl Iterator<Item = (LocalVector, T)>,
+    width: u32,
+    height: u32,
+    mut set: impl FnMut(LocalVector, T),
+) -> usize {
+    let mut clamped = 0;
+
+    for (obstacle, data) in obstacles {
+        if obstacle.x >= width || obstacle.y >= width || obstacle.z >= height {
+            clamped += 1;
+        }
+
+        // Grid without cells has no last cell to move obstacles to.
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        let x = obstacle.x.min(width - 1);
+        let y = obstacle.y.min(width - 1);
+        let z = obstacle.z.min(height - 1);
+
+        set(LocalVector::new(x, y, z), data);
+    }
+
+    clamped
+}
+
+/// Exports grid like `Grid::export`, but a failed export, for example on a full disk, doesn't
+/// leave a partially written file behind. Grid is written to a temporary file next to `path`,
+/// which replaces `path` only once it is complete, so a previous export is kept on failure.
+pub fn export_grid<P: AsRef<Path>>(grid: &Grid, path: P) -> Result<(), ConvertError> {
+    let path = path.as_ref();
+    let temporary = temporary_path(path);
+
+    grid.export(&temporary)
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|error| {
+            // Export may fail before the temporary file is created, then there is nothing to
+            // remove.
+            if temporary.is_file() {
+                let _ = fs::remove_file(&temporary);
+            }
+
+            ConvertError::Export {
+                path: path.to_path_buf(),
+                error,
+            }
+        })
+}
+
+/// Hidden sibling of `path`, unique to this process.
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Same as `create_sink_checked`, but every obstacle has a weight, see
+/// `GridSink::set_weighted_obstacle`. Cell listed more than once gets the highest weight.
+pub fn create_weighted_sink_checked<S: GridSink>(
+    obstacles: &[(LocalVector, u8)],
+    width: u32,
+    height: u32,
+) -> (S, usize) {
+    let mut grid = S::new(width, height);
+    let mut cells = Vec::with_capacity(obstacles.len());
+
+    let clamped = clamp_obstacles(obstacles.iter().copied(), width, height, |cell, weight| {
+        cells.push((cell, weight))
+    });
+
+    // Highest weight of every cell goes first and is kept.
+    cells.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    cells.dedup_by_key(|(cell, _)| *cell);
+
+    for (cell, weight) in cells {
+        grid.set_weighted_obstacle(cell.x, cell.y, cell.z, weight);
+    }
+
+    (grid, clamped)
+}
+
+pub fn to_world_vectors(
+    obstacles: &[LocalVector],
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+) -> Vec<Vector3<i32>> {
+    obstacles
+        .iter()
+        .map(|o| o.to_world_vector(center, width, height))
+        .collect()
+}
+
+/// First bytes of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Loads obj file, gzip compressed files are decompressed transparently.
+/// Compression is detected by magic bytes, so file extension doesn't matter.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Obj, ObjError> {
+    let (obj, _) = load_obj_checked(path)?;
+    Ok(obj)
+}
+
+/// Same as `load_obj`, but also returns count of skipped line and point elements, which
+/// produce no triangles. `l` and `p` statements are removed before parsing, so they can't
+/// confuse the parser, faces with less than three vertices are counted by `short_faces`.
+pub fn load_obj_checked<P: AsRef<Path>>(path: P) -> Result<(Obj, usize), ObjError> {
+    let path = path.as_ref();
+    let (mut obj, skipped) = read_obj_checked(BufReader::new(File::open(path)?))?;
+    obj.path = path.to_path_buf();
+
+    Ok((obj, skipped))
+}
+
+/// Same as `load_obj_checked`, but reads obj from any reader, for example stdin.
+/// Path of the returned obj is empty.
+pub fn read_obj_checked<R: Read>(mut reader: R) -> Result<(Obj, usize), ObjError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let (text, stripped) = if bytes.starts_with(&GZIP_MAGIC) {
+        strip_primitives(BufReader::new(GzDecoder::new(bytes.as_slice())))?
+    } else {
+        strip_primitives(bytes.as_slice())?
+    };
+
+    let obj = Obj {
+        data: ObjData::load_buf(text.as_slice())?,
+        path: PathBuf::new(),
+    };
+    let skipped = stripped + short_faces(&obj);
+
+    Ok((obj, skipped))
+}
+
+/// Removes `l` and `p` statements, returns remaining text and count of removed statements.
+fn strip_primitives<R: BufRead>(reader: R) -> io::Result<(Vec<u8>, usize)> {
+    let mut text = Vec::new();
+    let mut stripped = 0;
+
+    for line in reader.split(b'\n') {
+        let line = line?;
+        let keyword = line
+            .split(|b| b.is_ascii_whitespace())
+            .find(|word| !word.is_empty());
+
+        if keyword == Some(&b"l"[..]) || keyword == Some(&b"p"[..]) {
+            stripped += 1;
+            continue;
+        }
+
+        text.extend_from_slice(&line);
+        text.push(b'\n');
+    }
+
+    Ok((text, stripped))
+}
+
+/// Count of faces with less than three vertices, like line and point elements stored as faces,
+/// which all parsing functions skip.
+pub fn short_faces(obj: &Obj) -> usize {
+    obj.data
+        .objects
+        .iter()
+        .flat_map(|object| object.groups.iter())
+        .flat_map(|group| group.polys.iter())
+        .filter(|poly| poly.0.len() < 3)
+        .count()
+}
+
+/// Same as `parse_triangles`, but loads obj file with `load_obj` first.
+pub fn parse_triangles_auto<P: AsRef<Path>>(path: P) -> Result<Vec<Triangle>, ObjError> {
+    let obj = load_obj(path)?;
+    Ok(parse_triangles(&obj))
+}
+
+/// Only position index of every face vertex is used, so texture and normal indices,
+/// smoothing groups and materials don't affect the result. Vertices are used as they are,
+/// see `Placement`.
+pub fn parse_triangles(obj: &Obj) -> Vec<Triangle> {
+    triangles_iter(obj).collect()
+}
+
+/// Lazy version of `parse_triangles`, which doesn't keep all triangles in memory.
+/// Faces with more than three vertices are triangulated as a fan.
+pub fn triangles_iter(obj: &Obj) -> impl Iterator<Item = Triangle> + '_ {
+    face_triangles(obj, &[], &[]).filter(|t| t.is_finite())
+}
+
+/// Same as `parse_triangles`, but keeps only triangles of objects or groups with given names.
+/// Empty list keeps all triangles.
+pub fn parse_triangles_for(obj: &Obj, object_names: &[String]) -> Vec<Triangle> {
+    let (triangles, _) = parse_triangles_checked(obj, object_names, NonFinite::Skip)
+        .expect("Skipping non-finite triangles never fails");
+
+    triangles
+}
+
+/// Same as `parse_triangles_for`, but also returns count of triangles skipped because some of
+/// their coordinates are NaN or infinite. Fails on the first such triangle with `NonFinite::Error`.
+pub fn parse_triangles_checked(
+    obj: &Obj,
+    object_names: &[String],
+    non_finite: NonFinite,
+) -> Result<(Vec<Triangle>, usize), ConvertError> {
+    parse_triangles_placed(obj, object_names, &[], non_finite)
+}
+
+/// Same as `parse_triangles_checked`, but moves vertices of objects and groups by offsets of
+/// their placements. Offsets of both object and its group are applied.
+pub fn parse_triangles_placed(
+    obj: &Obj,
+    object_names: &[String],
+    placements: &[Placement],
+    non_finite: NonFinite,
+) -> Result<(Vec<Triangle>, usize), ConvertError> {
+    let mut finite_triangles = Vec::new();
+    let mut skipped = 0;
+
+    for (index, triangle) in face_triangles(obj, object_names, placements).enumerate() {
+        if triangle.is_finite() {
+            finite_triangles.push(triangle);
+        } else if non_finite == NonFinite::Error {
+            return Err(ConvertError::NonFinite(index));
+        } else {
+            skipped += 1;
+        }
+    }
+
+    Ok((finite_triangles, skipped))
+}
+
+/// Finds faces referencing vertices which don't exist, all parsing functions skip such faces.
+/// Faces are indexed in order of the file, every one is reported by its first missing vertex
+/// as `ConvertError::MalformedFace`.
+pub fn malformed_faces(obj: &Obj) -> Vec<ConvertError> {
+    let positions = &obj.data.position;
+
+    obj.data
+        .objects
+        .iter()
+        .flat_map(|object| object.groups.iter())
+        .flat_map(|group| group.polys.iter())
+        .enumerate()
+        .filter_map(|(face_index, poly)| {
+            poly.0
+                .iter()
+                .find(|i| i.0 >= positions.len())
+                .map(|i| ConvertError::MalformedFace {
+                    face_index,
+                    vertex_index: i.0,
+                })
+        })
+        .collect()
+}
+
+/// Triangles of all faces of objects or groups with given names, empty list keeps all faces.
+/// Faces without `o` or `g` statements are placed by the parser into a default object and group,
+/// so they are collected too.
+fn face_triangles<'a>(
+    obj: &'a Obj,
+    object_names: &'a [String],
+    placements: &'a [Placement],
+) -> impl Iterator<Item = Triangle> + 'a {
+    face_corners(obj, object_names, placements).map(|(_, triangle)| triangle)
+}
+
+/// Same as `face_triangles`, but every triangle comes with indices of its three vertices,
+/// so data stored per vertex can be carried alongside.
+pub(crate) fn face_corners<'a>(
+    obj: &'a Obj,
+    object_names: &'a [String],
+    placements: &'a [Placement],
+) -> impl Iterator<Item = ([usize; 3], Triangle)> + 'a {
+    let data = &obj.data;
+    let positions = &data.position;
+
+    let is_included =
+        move |name: &str| object_names.is_empty() || object_names.iter().any(|n| n == name);
+
+    let offset = move |object_name: &str, group_name: &str| {
+        placements
+            .iter()
+            .filter(|p| p.name == object_name || p.name == group_name)
+            .fold(Vector3::new(0.0, 0.0, 0.0), |offset, p| offset + p.offset)
+    };
+
+    data.objects
+        .iter()
+        .flat_map(move |object| {
+            let object_included = is_included(&object.name);
+
+            object
+                .groups
+                .iter()
+                .filter(move |group| object_included || is_included(&group.name))
+                .map(move |group| (group, offset(&object.name, &group.name)))
+        })
+        .flat_map(|(group, offset)| group.polys.iter().map(move |poly| (&poly.0, offset)))
+        .filter(move |(indices, _)| indices.iter().all(|i| i.0 < positions.len()))
+        .flat_map(move |(indices, offset)| {
+            let position = move |index: usize| {
+                let position = positions[index];
+                Vector3::new(position[0], position[1], position[2]) + offset
+            };
+
+            // Faces with less than three vertices produce no triangles.
+            (1..indices.len().saturating_sub(1)).map(move |i| {
+                let corners = [indices[0].0, indices[i].0, indices[i + 1].0];
+                let triangle = Triangle::new(
+                    position(corners[0]),
+                    position(corners[1]),
+                    position(corners[2]),
+                );
+
+                (corners, triangle)
+            })
+        })
+}
+
+fn find_obstacles_with_options(
+    triangle: &Triangle,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    options: &ConvertOptions,
+) -> Vec<LocalVector> {
+    let padding = options.scan_padding;
+
+    let tests = Cell::new(0);
+    let counted = |is_obstacle: bool| {
+        tests.set(tests.get() + 1);
+        is_obstacle
+    };
+
+    let mut obstacles = match options.mode {
+        VoxelMode::Precise => find_obstacles_by(triangle, center, width, height, padding, |v| {
+            counted(triangle.is_inside_with_epsilon(v, options.epsilon))
+        }),
+        VoxelMode::CenterSample => {
+            find_obstacles_by(triangle, center, width, height, padding, |v| {
+                counted(triangle.contains_projection(v, 0.5))
+            })
+        }
+    };
+
+    // Counter is updated once per triangle, so threads rarely touch it.
+    if let Some(inside_tests) = options.inside_tests {
+        inside_tests.fetch_add(tests.get(), Ordering::Relaxed);
+    }
+
+    if obstacles.is_empty() && options.ensure_nonempty && triangle.dominant_axis().is_some() {
+        let centroid = triangle.centroid();
+        obstacles.push(LocalVector::from_world_vector(
+            &centroid, center, width, height,
+        ));
+    }
+
+    let obstacles = match triangle.dominant_axis() {
+        Some(axis) if options.wall_thickness.0 > 0 => {
+            thicken_walls(obstacles, axis, options.wall_thickness, width, height)
+        }
+        _ => obstacles,
+    };
+
+    let obstacles = match options.obstacle_filter {
+        Some(filter) => obstacles.into_iter().filter(|o| filter(*o)).collect(),
+        None => obstacles,
+    };
+
+    if let Some(empty) = options.empty_triangles {
+        if obstacles.is_empty() {
+            empty.add(triangle, &BoundingBox::from_grid(center, width, height));
+        }
+    }
+
+    obstacles
+}
+
+/// Adds cells up to thickness away from every surface cell in both directions along axis.
+/// Cells outside of the grid are skipped.
+fn thicken_walls(
+    obstacles: Vec<LocalVector>,
+    axis: Axis,
+    thickness: WallThickness,
+    width: u32,
+    height: u32,
+) -> Vec<LocalVector> {
+    let index = axis.index();
+    let limit = if axis == Axis::Z { height } else { width };
+
+    let mut thickened = Vec::with_capacity(obstacles.len() * (2 * thickness.0 as usize + 1));
+
+    for obstacle in obstacles {
+        let coordinates = [obstacle.x, obstacle.y, obstacle.z];
+        let start = coordinates[index].saturating_sub(thickness.0);
+        let end = coordinates[index]
+            .saturating_add(thickness.0)
+            .min(limit.saturating_sub(1));
+
+        for value in start..=end {
+            let mut coordinates = coordinates;
+            coordinates[index] = value;
+
+            thickened.push(LocalVector::new(
+                coordinates[0],
+                coordinates[1],
+                coordinates[2],
+            ));
+        }
+    }
+
+    thickened
+}
+
+/// Passes every obstacle of a single triangle to `out`, without collecting them, so callers
+/// can store them in any structure. Triangle and center are in world units, cells are
+/// `cell_size` big. Uses the precise test with `DEFAULT_EPSILON`, same as `convert`.
+pub fn voxelize_triangle(
+    triangle: &Triangle,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    cell_size: f32,
+    out: &mut impl FnMut(LocalVector),
+) {
+    let scale = 1.0 / cell_size;
+    let triangle = Triangle::new(triangle.a * scale, triangle.b * scale, triangle.c * scale);
+    let center = center * scale;
+
+    visit_obstacles_by(
+        &triangle,
+        &center,
+        width,
+        height,
+        0,
+        |v| triangle.is_inside_with_epsilon(v, DEFAULT_EPSILON),
+        out,
+    );
+}
+
+/// Precise obstacles of a triangle without other options, which tests compare against.
+#[cfg(test)]
+fn find_obstacles(
+    triangle: &Triangle,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    epsilon: f32,
+    padding: u32,
+) -> Vec<LocalVector> {
+    find_obstacles_by(triangle, center, width, height, padding, |v| {
+        triangle.is_inside_with_epsilon(v, epsilon)
+    })
+}
+
+fn find_obstacles_by<F: Fn(&Vector3<i32>) -> bool>(
+    triangle: &Triangle,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    padding: u32,
+    is_obstacle: F,
+) -> Vec<LocalVector> {
+    let mut obstacles = Vec::new();
+
+    visit_obstacles_by(
+        triangle,
+        center,
+        width,
+        height,
+        padding,
+        is_obstacle,
+        &mut |o| obstacles.push(o),
+    );
+
+    obstacles
+}
+
+/// To find obstacles we check every point in triangle bounding box,
+/// extended by `padding` cells and clamped to the grid.
+fn visit_obstacles_by<F: Fn(&Vector3<i32>) -> bool>(
+    triangle: &Triangle,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    padding: u32,
+    is_obstacle: F,
+    out: &mut impl FnMut(LocalVector),
+) {
+    let (min, max) = scan_range(triangle, center, width, height, padding);
+
+    for x in min.x..max.x {
+        for y in min.y..max.y {
+            for z in min.z..max.z {
+                let local_vector = LocalVector::new(x, y, z);
+
+                // Triangle coordinates are global.
+                let global_vector = local_vector.to_world_vector(&center, width, height);
+
+                if is_obstacle(&global_vector) {
+                    out(local_vector);
+                }
+            }
+        }
+    }
+}
+
+/// Cells scanned for obstacles of triangle, from min inclusive to max exclusive.
+fn scan_range(
+    triangle: &Triangle,
+    center: &Vector3<f32>,
+    width: u32,
+    height: u32,
+    padding: u32,
+) -> (LocalVector, LocalVector) {
+    let bounding_box = triangle.bounding_box();
+
+    // Convert bounding box to local coordinates.
+    let min = LocalVector::from_world_vector(&bounding_box.min, &center, width, height);
+    let max = LocalVector::from_world_vector(&bounding_box.max, &center, width, height);
+
+    let min = LocalVector::new(
+        min.x.saturating_sub(padding),
+        min.y.saturating_sub(padding),
+        min.z.saturating_sub(padding),
+    );
+    let max = LocalVector::new(
+        max.x.saturating_add(padding).min(width),
+        max.y.saturating_add(padding).min(width),
+        max.z.saturating_add(padding).min(height),
+    );
+
+    (min, max)
+}
+
+pub fn bounding_box(triangles: &[Triangle]) -> BoundingBox {
+    let bounding_boxes: Vec<BoundingBox> = triangles.iter().map(|t| t.bounding_box()).collect();
+
+    let min_x = bounding_boxes
+        .iter()
+        .map(|b| b.min)
+        .map(|m| m.x.round() as i32)
+        .min()
+        .unwrap_or(0) as f32;
+
+    let min_y = bounding_boxes
+        .iter()
+        .map(|b| b.min)
+        .map(|m| m.y.round() as i32)
+        .min()
+        .unwrap_or(0) as f32;
+
+    let min_z = bounding_boxes
+        .iter()
+        .map(|b| b.min)
+        .map(|m| m.z.round() as i32)
+        .min()
+        .unwrap_or(0) as f32;
+
+    let max_x = bounding_boxes
+        .iter()
+        .map(|b| b.max)
+        .map(|m| m.x.round() as i32)
+        .max()
+        .unwrap_or(0) as f32;
+
+    let max_y = bounding_boxes
+        .iter()
+        .map(|b| b.max)
+        .map(|m| m.y.round() as i32)
+        .max()
+        .unwrap_or(0) as f32;
+
+    let max_z = bounding_boxes
+        .iter()
+        .map(|b| b.max)
+        .map(|m| m.z.round() as i32)
+        .max()
+        .unwrap_or(0) as f32;
+
+    let min = Vector3::new(min_x, min_y, min_z);
+    let max = Vector3::new(max_x, max_y, max_z);
+
+    BoundingBox { min, max }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bvh::Bvh;
+    use crate::error::ConvertError;
+    use crate::geometry::{BoundingBox, LocalVector, Triangle, DEFAULT_EPSILON};
+    use crate::occupancy::OccupancyGrid;
+    use crate::preprocessors::ClipPreprocessor;
+    use crate::stats::EmptyTriangles;
+    use crate::testutil::{generate_grid_plane, generate_sphere, NoOpProgress};
+    use crate::{
+        add_extra_obstacles, add_triangles, bounding_box, check_grid_size, convert_octree,
+        convert_with_index, convert_with_stats, create_grid_checked, dedup_obstacles,
+        empty_triangles, export_grid, find_obstacles, load_obj_checked, malformed_faces,
+        obstacle_iter, obstacles_world, parse_triangles, parse_triangles_auto,
+        parse_triangles_checked, parse_triangles_for, parse_triangles_placed, preview, short_faces,
+        to_world_vectors, triangles_in_region, triangles_iter, voxelize, voxelize_occupancy,
+        voxelize_triangle, ChannelProgress, ConvertOptions, NoOpPreprocessor, NonFinite,
+        OutOfBounds, Placement, Preprocessor, Progress, ProgressWeight, SingleTriangle,
+        TrianglePreprocessor, VoxelMode, WallThickness, CHUNK_SIZE, DEFAULT_MAX_CELLS,
+        PROGRESS_STEP,
+    };
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use nalgebra::Vector3;
+    use obj::{IndexTuple, Obj, SimplePolygon};
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::io::Write;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    struct MidpointPreprocessor {}
+
+    impl Preprocessor for MidpointPreprocessor {
+        fn pre_process(
+            &self,
+            triangle: Triangle,
+            _width: u32,
+            _height: u32,
+            _center: Vector3<f32>,
+        ) -> Vec<Triangle> {
+            let ab = (triangle.a + triangle.b) / 2.0;
+            let bc = (triangle.b + triangle.c) / 2.0;
+            let ca = (triangle.c + triangle.a) / 2.0;
+
+            vec![
+                Triangle::new(triangle.a, ab, ca),
+                Triangle::new(ab, triangle.b, bc),
+                Triangle::new(ca, bc, triangle.c),
+                Triangle::new(ab, bc, ca),
+            ]
+        }
+    }
+
+    struct SkipPreprocessor {}
+
+    impl TrianglePreprocessor for SkipPreprocessor {
+        fn pre_process(
+            &self,
+            triangle: Triangle,
+            _width: u32,
+            _height: u32,
+            _center: Vector3<f32>,
+        ) -> Option<Triangle> {
+            if triangle.a.z > 0.0 {
+                None
+            } else {
+                Some(triangle)
+            }
+        }
+    }
+
+    #[test]
+    fn test_winding_independent() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        for triangle in generate_sphere(6.0, 2) {
+            let back_face = Triangle::new(triangle.a, triangle.c, triangle.b);
+
+            assert_eq!(
+                find_obstacles(&triangle, &center, 16, 16, DEFAULT_EPSILON, 0),
+                find_obstacles(&back_face, &center, 16, 16, DEFAULT_EPSILON, 0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_far_from_origin() {
+        // Vertices are snapped to 1/16, which is still exact in f32 around a million.
+        let snap = |v: Vector3<f32>| v.map(|c| (c * 16.0).round() / 16.0);
+        let sphere: Vec<Triangle> = generate_sphere(10.0, 3)
+            .into_iter()
+            .map(|t| Triangle::new(snap(t.a), snap(t.b), snap(t.c)))
+            .collect();
+
+        let convert = |offset: Vector3<f32>| {
+            let triangles = sphere
+                .iter()
+                .map(|t| Triangle::new(t.a + offset, t.b + offset, t.c + offset))
+                .collect();
+            let center = Vector3::new(0.25, 0.75, 0.4) + offset;
+
+            let obstacles = voxelize(
+                triangles,
+                center,
+                32,
+                32,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &ConvertOptions::default(),
+            )
+            .unwrap();
+
+            dedup_obstacles(obstacles)
+        };
+
+        let expected = convert(Vector3::new(0.0, 0.0, 0.0));
+
+        assert!(!expected.is_empty());
+        assert_eq!(convert(Vector3::new(1e6, -1e6, 5e5)), expected);
+    }
+
+    #[test]
+    fn test_find_obstacles() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(5.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let obstacles = find_obstacles(&triangle, &center, 10, 10, DEFAULT_EPSILON, 0);
+
+        let expected_obstacles = vec![
+            LocalVector::new(0, 0, 5),
+            LocalVector::new(0, 1, 5),
+            LocalVector::new(1, 0, 5),
+            LocalVector::new(1, 1, 5),
+            LocalVector::new(1, 2, 5),
+            LocalVector::new(2, 1, 5),
+            LocalVector::new(2, 2, 5),
+            LocalVector::new(2, 3, 5),
+            LocalVector::new(3, 2, 5),
+            LocalVector::new(3, 3, 5),
+            LocalVector::new(3, 4, 5),
+            LocalVector::new(4, 3, 5),
+            LocalVector::new(4, 4, 5),
+            LocalVector::new(4, 5, 5),
+            LocalVector::new(5, 4, 5),
+            LocalVector::new(5, 5, 5),
+            LocalVector::new(5, 6, 5),
+            LocalVector::new(6, 5, 5),
+            LocalVector::new(6, 6, 5),
+            LocalVector::new(6, 7, 5),
+            LocalVector::new(7, 6, 5),
+            LocalVector::new(7, 7, 5),
+            LocalVector::new(7, 8, 5),
+            LocalVector::new(8, 7, 5),
+            LocalVector::new(8, 8, 5),
+            LocalVector::new(8, 9, 5),
+            LocalVector::new(9, 8, 5),
+            LocalVector::new(9, 9, 5),
+        ];
+
+        assert_eq!(expected_obstacles, obstacles)
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(5.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let bounding_box = bounding_box(&vec![triangle]);
+
+        let min = Vector3::new(-6.0, -6.0, -1.0);
+        let max = Vector3::new(6.0, 6.0, 1.0);
+
+        assert_eq!(bounding_box.min, min);
+        assert_eq!(bounding_box.max, max);
+    }
+
+    #[test]
+    fn test_obstacles_world() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(5.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        let triangles = vec![Triangle::new(a, b, c)];
+        let center = Vector3::new(100.0, 200.0, 0.0);
+
+        // Move triangle to the grid center, so local coordinates match `test_find_obstacles`.
+        let triangles = triangles
+            .into_iter()
+            .map(|t| Triangle::new(t.a + center, t.b + center, t.c + center))
+            .collect();
+
+        let obstacles = obstacles_world(
+            triangles,
+            center,
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        // Local (0, 0, 5) and (9, 9, 5).
+        assert_eq!(obstacles[0], Vector3::new(95.0, 195.0, 0.0));
+        assert_eq!(
+            obstacles[obstacles.len() - 1],
+            Vector3::new(104.0, 204.0, 0.0)
+        );
+        assert_eq!(obstacles.len(), 28);
+    }
+
+    #[test]
+    fn test_voxelize_single_thread() {
+        let obj = Obj::load("tests/teapot.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        let bounding_box = bounding_box(&triangles);
+        let center = bounding_box.center();
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+
+        let single_thread_options = ConvertOptions {
+            threads: Some(1),
+            ..ConvertOptions::default()
+        };
+
+        let single_thread_obstacles = voxelize(
+            parse_triangles(&obj),
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &single_thread_options,
+        )
+        .unwrap();
+
+        let obstacles = voxelize(
+            triangles,
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        assert!(!obstacles.is_empty());
+        assert_eq!(single_thread_obstacles, obstacles);
+    }
+
+    #[test]
+    fn test_grid_too_large() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        let result = voxelize_occupancy(
+            Vec::new(),
+            center,
+            100_000,
+            100_000,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        );
+
+        assert_eq!(
+            result.err(),
+            Some(ConvertError::GridTooLarge {
+                width: 100_000,
+                height: 100_000,
+                max_cells: DEFAULT_MAX_CELLS,
+            })
+        );
+
+        // Cell count doesn't fit into 64 bits.
+        assert!(check_grid_size(u32::MAX, u32::MAX, u64::MAX).is_err());
+
+        let options = ConvertOptions {
+            max_cells: 999,
+            ..options
+        };
+
+        let convert = |width| {
+            voxelize(
+                Vec::new(),
+                center,
+                width,
+                10,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &options,
+            )
+        };
+
+        assert!(convert(9).is_ok());
+        assert!(convert(10).is_err());
+    }
+
+    #[test]
+    fn test_export_grid_failure() {
+        let (grid, _) = create_grid_checked(&[LocalVector::new(1, 1, 1)], 4, 4);
+
+        let folder = std::env::temp_dir().join(format!(
+            "obj-to-pathfinding-grid-export-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&folder).unwrap();
+
+        // Directory can't be replaced by a file and must not be removed.
+        let directory = folder.join("grid-dir");
+        std::fs::create_dir_all(&directory).unwrap();
+
+        match export_grid(&grid, &directory) {
+            Err(ConvertError::Export { path, .. }) => assert_eq!(path, directory),
+            result => panic!("Expected export error, got {:?}", result),
+        }
+
+        assert!(directory.is_dir());
+        std::fs::remove_dir(&directory).unwrap();
+
+        // Previous export is replaced only on success.
+        let previous = folder.join("grid.bin");
+        std::fs::write(&previous, "previous").unwrap();
+        export_grid(&grid, &previous).unwrap();
+
+        assert_ne!(std::fs::read(&previous).unwrap(), b"previous");
+
+        // Temporary files never stay behind.
+        let names: Vec<_> = std::fs::read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+
+        assert_eq!(names, vec![std::ffi::OsString::from("grid.bin")]);
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        let missing = folder.join("grid.bin");
+        let error = export_grid(&grid, &missing).unwrap_err();
+
+        assert!(error.to_string().contains("grid.bin"));
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn test_create_grid_checked() {
+        let obstacles = vec![
+            LocalVector::new(0, 0, 0),
+            LocalVector::new(3, 1, 2),
+            LocalVector::new(4, 4, 4),
+        ];
+
+        let (_, clamped) = create_grid_checked(&obstacles, 4, 4);
+        assert_eq!(clamped, 1);
+
+        let (_, clamped) = create_grid_checked(&obstacles, 0, 0);
+        assert_eq!(clamped, 3);
+    }
+
+    #[test]
+    fn test_center_sample_mode() {
+        let a = Vector3::new(-6.0, -6.0, 0.0);
+        let b = Vector3::new(6.0, -6.0, 0.0);
+        let c = Vector3::new(0.0, 6.0, 0.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let voxelize_with_mode = |mode: VoxelMode| {
+            let options = ConvertOptions {
+                mode,
+                ..ConvertOptions::default()
+            };
+
+            let obstacles = voxelize(
+                vec![Triangle::new(a, b, c)],
+                center,
+                20,
+                20,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &options,
+            )
+            .unwrap();
+
+            dedup_obstacles(obstacles)
+        };
+
+        let precise_obstacles = voxelize_with_mode(VoxelMode::Precise);
+        let sampled_obstacles = voxelize_with_mode(VoxelMode::CenterSample);
+
+        // Every sampled cell is touched by the triangle, only cells at its edges can be missed.
+        assert!(sampled_obstacles
+            .iter()
+            .all(|o| precise_obstacles.binary_search(o).is_ok()));
+        assert!(sampled_obstacles.len() * 10 >= precise_obstacles.len() * 7);
+        assert!(sampled_obstacles.iter().all(|o| o.z == 10));
+    }
+
+    #[test]
+    fn test_add_triangles() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let mut grid = OccupancyGrid::new(10, 10);
+        grid.set_obstacle(0, 0, 0);
+
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(2.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 2.0, 0.0);
+
+        add_triangles(
+            &mut grid,
+            vec![Triangle::new(a, b, c)],
+            center,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let triangle = Triangle::new(a, b, c);
+        let mut expected_obstacles = find_obstacles(&triangle, &center, 10, 10, DEFAULT_EPSILON, 0);
+        expected_obstacles.insert(0, LocalVector::new(0, 0, 0));
+
+        assert_eq!(grid.obstacles(), expected_obstacles);
+    }
+
+    #[test]
+    fn test_wall_thickness() {
+        let a = Vector3::new(-4.0, -4.0, 0.0);
+        let b = Vector3::new(4.0, -4.0, 0.0);
+        let c = Vector3::new(0.0, 4.0, 0.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let options = ConvertOptions {
+            wall_thickness: WallThickness(1),
+            ..ConvertOptions::default()
+        };
+
+        let obstacles = voxelize(
+            vec![Triangle::new(a, b, c)],
+            center,
+            20,
+            20,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let obstacles = dedup_obstacles(obstacles);
+
+        let triangle = Triangle::new(a, b, c);
+        let surface_obstacles = find_obstacles(&triangle, &center, 20, 20, DEFAULT_EPSILON, 0);
+
+        // Plane is in a single layer, one layer is added below and above it.
+        assert!(surface_obstacles.iter().all(|o| o.z == 10));
+        assert_eq!(obstacles.len(), surface_obstacles.len() * 3);
+
+        for layer in 9..=11 {
+            let layer_obstacles = obstacles.iter().filter(|o| o.z == layer).count();
+            assert_eq!(layer_obstacles, surface_obstacles.len());
+        }
+    }
+
+    #[test]
+    fn test_scan_padding() {
+        // Large epsilon grazes cells at x = 2, which are outside of the scanned bounding box.
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.3, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            )
+        };
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let grazed = LocalVector::from_world_vector(&Vector3::new(2.0, 0.0, 0.0), &center, 10, 10);
+
+        let options = ConvertOptions {
+            epsilon: 0.3,
+            ..ConvertOptions::default()
+        };
+
+        let voxelize_with = |options: &ConvertOptions| {
+            voxelize(
+                vec![triangle()],
+                center,
+                10,
+                10,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                options,
+            )
+            .unwrap()
+        };
+
+        assert!(!voxelize_with(&options).contains(&grazed));
+
+        let options = ConvertOptions {
+            scan_padding: 1,
+            ..options
+        };
+
+        assert!(voxelize_with(&options).contains(&grazed));
+    }
+
+    #[test]
+    fn test_ensure_nonempty() {
+        // Small triangle between cells 0 and 1, which strict epsilon doesn't mark at all.
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(0.45, 0.0, 0.0),
+                Vector3::new(0.46, 0.1, 0.0),
+                Vector3::new(0.45, 0.1, 0.1),
+            )
+        };
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let options = ConvertOptions {
+            epsilon: -0.1,
+            ..ConvertOptions::default()
+        };
+
+        let obstacles = voxelize(
+            vec![triangle()],
+            center,
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert!(obstacles.is_empty());
+
+        let options = ConvertOptions {
+            ensure_nonempty: true,
+            ..options
+        };
+
+        let obstacles = voxelize(
+            vec![triangle()],
+            center,
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(obstacles, vec![LocalVector::new(5, 5, 5)]);
+    }
+
+    #[test]
+    fn test_obstacle_filter() {
+        // Vertical triangle spanning all layers of the grid.
+        let a = Vector3::new(-4.0, 0.0, -5.0);
+        let b = Vector3::new(4.0, 0.0, -5.0);
+        let c = Vector3::new(0.0, 0.0, 5.0);
+
+        let filter = |obstacle: LocalVector| obstacle.z <= 6;
+
+        let options = ConvertOptions {
+            obstacle_filter: Some(&filter),
+            ..ConvertOptions::default()
+        };
+
+        let mut grid = OccupancyGrid::new(10, 10);
+
+        add_triangles(
+            &mut grid,
+            vec![Triangle::new(a, b, c)],
+            Vector3::new(0.0, 0.0, 0.0),
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert!(grid.obstacle_count() > 0);
+
+        for x in 0..10 {
+            for y in 0..10 {
+                assert!((7..10).all(|z| !grid.is_obstacle(x, y, z)));
+            }
+        }
+
+        assert!(grid.is_obstacle(5, 5, 6));
+    }
+
+    #[test]
+    fn test_anisotropic_cells() {
+        // Wall at y = 0.2 from x -3.3 to 3.3 and z -0.4 to 6.9 takes 8 layers of unit cells,
+        // 4 of cells twice as tall, which span from z -1 to 7.
+        let wall = || {
+            vec![
+                Triangle::new(
+                    Vector3::new(-3.3, 0.2, -0.4),
+                    Vector3::new(3.3, 0.2, -0.4),
+                    Vector3::new(3.3, 0.2, 6.9),
+                ),
+                Triangle::new(
+                    Vector3::new(-3.3, 0.2, -0.4),
+                    Vector3::new(3.3, 0.2, 6.9),
+                    Vector3::new(-3.3, 0.2, 6.9),
+                ),
+            ]
+        };
+
+        let cells = |options: &ConvertOptions| {
+            let obstacles = voxelize(
+                wall(),
+                Vector3::zeros(),
+                16,
+                16,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                options,
+            )
+            .unwrap();
+
+            let columns: HashSet<_> = obstacles.iter().map(|o| (o.x, o.y)).collect();
+            let layers: HashSet<_> = obstacles.iter().map(|o| o.z).collect();
+
+            (columns, layers.len())
+        };
+
+        let (columns, layers) = cells(&ConvertOptions::default());
+
+        let (tall_columns, tall_layers) = cells(&ConvertOptions {
+            cell_size_xy: 1.0,
+            cell_size_z: 2.0,
+            ..ConvertOptions::default()
+        });
+
+        assert_eq!(columns.len(), 7);
+        assert_eq!(layers, 8);
+        assert_eq!(tall_columns, columns);
+        assert_eq!(tall_layers, layers / 2);
+
+        // Halving cells along x and y keeps layers, wall from x -6.6 to 6.6 takes 15 columns.
+        let (fine_columns, fine_layers) = cells(&ConvertOptions {
+            cell_size_xy: 0.5,
+            cell_size_z: 1.0,
+            ..ConvertOptions::default()
+        });
+
+        assert_eq!(fine_columns.len(), 15);
+        assert_eq!(fine_layers, layers);
+    }
+
+    #[test]
+    fn test_origin() {
+        // Triangle covers world tile from (16, 32) to (31, 47).
+        let a = Vector3::new(16.0, 32.0, 2.0);
+        let b = Vector3::new(31.0, 32.0, 2.0);
+        let c = Vector3::new(16.0, 47.0, 2.0);
+
+        let options = ConvertOptions {
+            origin: Some(Vector3::new(16.0, 32.0, 0.0)),
+            ..ConvertOptions::default()
+        };
+
+        // Center is ignored if origin is set.
+        let center = Vector3::new(1000.0, 1000.0, 1000.0);
+
+        let obstacles = voxelize(
+            vec![Triangle::new(a, b, c)],
+            center,
+            16,
+            4,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let obstacles = dedup_obstacles(obstacles);
+
+        assert_eq!(obstacles[0], LocalVector::new(0, 0, 2));
+        assert!(obstacles.contains(&LocalVector::new(15, 0, 2)));
+        assert!(obstacles.contains(&LocalVector::new(0, 15, 2)));
+        assert!(obstacles.iter().all(|o| o.x < 16 && o.y < 16 && o.z == 2));
+
+        let world_obstacles = obstacles_world(
+            vec![Triangle::new(a, b, c)],
+            center,
+            16,
+            4,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(world_obstacles[0], Vector3::new(16.0, 32.0, 2.0));
+        assert!(world_obstacles.contains(&Vector3::new(31.0, 32.0, 2.0)));
+        assert!(world_obstacles.contains(&Vector3::new(16.0, 47.0, 2.0)));
+    }
+
+    #[test]
+    fn test_obstacles_world_cell_size() {
+        // Triangle covers world tile from (16, 32) to (23.5, 39.5) at height 4.
+        let a = Vector3::new(16.0, 32.0, 4.0);
+        let b = Vector3::new(23.5, 32.0, 4.0);
+        let c = Vector3::new(16.0, 39.5, 4.0);
+
+        let options = ConvertOptions {
+            origin: Some(Vector3::new(16.0, 32.0, 0.0)),
+            cell_size_xy: 0.5,
+            cell_size_z: 2.0,
+            ..ConvertOptions::default()
+        };
+
+        let world_obstacles = obstacles_world(
+            vec![Triangle::new(a, b, c)],
+            Vector3::new(0.0, 0.0, 0.0),
+            16,
+            4,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        // Local (0, 0, 2), (15, 0, 2) and (0, 15, 2).
+        assert_eq!(world_obstacles[0], Vector3::new(16.0, 32.0, 4.0));
+        assert!(world_obstacles.contains(&Vector3::new(23.5, 32.0, 4.0)));
+        assert!(world_obstacles.contains(&Vector3::new(16.0, 39.5, 4.0)));
+        assert!(world_obstacles.iter().all(|o| o.z == 4.0));
+    }
+
+    #[test]
+    fn test_dedup_obstacles_deterministic() {
+        let obj = Obj::load("tests/teapot.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        let bounding_box = bounding_box(&triangles);
+        let center = bounding_box.center();
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+
+        let run = |triangles: Vec<Triangle>| {
+            let obstacles = voxelize(
+                triangles,
+                center,
+                width,
+                height,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &ConvertOptions::default(),
+            )
+            .unwrap();
+
+            dedup_obstacles(obstacles)
+        };
+
+        let obstacles = run(parse_triangles(&obj));
+        let reversed_obstacles = run(triangles.into_iter().rev().collect());
+
+        assert!(obstacles.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(obstacles, reversed_obstacles);
+    }
+
+    #[test]
+    fn test_multiple_triangles_preprocessor() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(8.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 8.0, 0.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let triangles = MidpointPreprocessor {}.pre_process(Triangle::new(a, b, c), 20, 20, center);
+
+        assert_eq!(triangles.len(), 4);
+        assert_eq!(triangles[3].a, Vector3::new(4.0, 0.0, 0.0));
+        assert_eq!(triangles[3].b, Vector3::new(4.0, 4.0, 0.0));
+        assert_eq!(triangles[3].c, Vector3::new(0.0, 4.0, 0.0));
+
+        let mut subdivided_obstacles = voxelize(
+            vec![Triangle::new(a, b, c)],
+            center,
+            20,
+            20,
+            NoOpProgress {},
+            MidpointPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let obstacles = voxelize(
+            vec![Triangle::new(a, b, c)],
+            center,
+            20,
+            20,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        // Sub-triangles share edges, so some cells are found more than once.
+        subdivided_obstacles.sort_by_key(|o| (o.x, o.y, o.z));
+        subdivided_obstacles.dedup();
+
+        assert_eq!(subdivided_obstacles, obstacles);
+    }
+
+    #[test]
+    fn test_single_triangle_preprocessor() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(8.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 8.0, 0.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let preprocessor = SingleTriangle(SkipPreprocessor {});
+
+        let triangles = preprocessor.pre_process(Triangle::new(a, b, c), 20, 20, center);
+
+        assert_eq!(triangles.len(), 1);
+
+        let a = Vector3::new(0.0, 0.0, 1.0);
+        let triangles = preprocessor.pre_process(Triangle::new(a, b, c), 20, 20, center);
+
+        assert!(triangles.is_empty());
+    }
+
+    fn voxelize_out_of_bounds(
+        out_of_bounds: OutOfBounds,
+    ) -> Result<Vec<LocalVector>, ConvertError> {
+        let inside_triangle = Triangle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+
+        let outside_triangle = Triangle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(20.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+
+        let options = ConvertOptions {
+            out_of_bounds,
+            ..ConvertOptions::default()
+        };
+
+        voxelize(
+            vec![inside_triangle, outside_triangle],
+            Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+    }
+
+    #[test]
+    fn test_out_of_bounds_clamp() {
+        let obstacles = voxelize_out_of_bounds(OutOfBounds::Clamp).unwrap();
+
+        // Part of the second triangle outside of the grid is clamped to the last column.
+        assert!(obstacles.contains(&LocalVector::new(9, 5, 5)));
+    }
+
+    #[test]
+    fn test_out_of_bounds_drop() {
+        let obstacles = voxelize_out_of_bounds(OutOfBounds::Drop).unwrap();
+
+        let inside_triangle = Triangle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let expected_obstacles =
+            find_obstacles(&inside_triangle, &center, 10, 10, DEFAULT_EPSILON, 0);
+
+        assert_eq!(obstacles, expected_obstacles);
+    }
+
+    #[test]
+    fn test_out_of_bounds_error() {
+        let result = voxelize_out_of_bounds(OutOfBounds::Error);
+
+        assert_eq!(result, Err(ConvertError::OutOfBounds(1)));
+    }
+
+    #[test]
+    fn test_out_of_bounds_strict() {
+        let result = voxelize_out_of_bounds(OutOfBounds::Strict);
+
+        assert_eq!(result, Err(ConvertError::Clipped(1)));
+
+        let inside_triangle = || {
+            Triangle::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(0.0, 2.0, 0.0),
+            )
+        };
+
+        let options = ConvertOptions {
+            out_of_bounds: OutOfBounds::Strict,
+            ..ConvertOptions::default()
+        };
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let expected_obstacles =
+            find_obstacles(&inside_triangle(), &center, 10, 10, DEFAULT_EPSILON, 0);
+
+        let obstacles = voxelize(
+            vec![inside_triangle()],
+            center,
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        );
+
+        assert_eq!(obstacles, Ok(expected_obstacles));
+
+        // Lazy conversion fails after all obstacles are returned.
+        let outside_triangle = Triangle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(20.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+
+        let results: Vec<_> = obstacle_iter(
+            vec![inside_triangle(), outside_triangle],
+            center,
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .collect();
+
+        assert!(results[..results.len() - 1].iter().all(|r| r.is_ok()));
+        assert_eq!(results.last(), Some(&Err(ConvertError::Clipped(1))));
+    }
+
+    #[derive(Default)]
+    struct RecordingProgress {
+        updates: RefCell<Vec<f32>>,
+    }
+
+    impl Progress for RecordingProgress {
+        fn update_progress(&self, percent: f32) {
+            self.updates.borrow_mut().push(percent);
+        }
+    }
+
+    #[test]
+    fn test_channel_progress() {
+        let triangles = || generate_grid_plane(40);
+
+        let recording = RecordingProgress::default();
+        let options = ConvertOptions::default();
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        voxelize(
+            triangles(),
+            center,
+            50,
+            4,
+            &recording,
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let (sender, receiver) = channel();
+        let progress = ChannelProgress::new(sender);
+
+        thread::spawn(move || {
+            voxelize(
+                triangles(),
+                center,
+                50,
+                4,
+                progress,
+                NoOpPreprocessor {},
+                &options,
+            )
+            .unwrap();
+        });
+
+        // Channel is closed when conversion thread drops the progress.
+        let updates: Vec<f32> = receiver.iter().map(|info| info.percent).collect();
+
+        assert!(updates.len() > 1);
+        assert_eq!(updates.last(), Some(&100.0));
+        assert_eq!(updates, recording.updates.into_inner());
+    }
+
+    #[test]
+    fn test_voxelize_progress() {
+        // Enough chunks for progress to be throttled.
+        let triangles = generate_grid_plane(120);
+        assert!(triangles.len() > 200 * CHUNK_SIZE);
+
+        let progress = RecordingProgress::default();
+
+        voxelize(
+            triangles,
+            Vector3::new(0.0, 0.0, 0.0),
+            130,
+            4,
+            &progress,
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let updates = progress.updates.into_inner();
+
+        assert_eq!(updates.last(), Some(&100.0));
+        assert!(updates.len() <= 201);
+        assert!(updates
+            .windows(2)
+            .all(|w| w[1] - w[0] >= PROGRESS_STEP || w[1] == 100.0));
+
+        let progress = RecordingProgress::default();
+
+        voxelize(
+            Vec::new(),
+            Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            &progress,
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(progress.updates.into_inner(), vec![100.0]);
+    }
+
+    #[test]
+    fn test_voxelize_progress_weight() {
+        // One huge triangle followed by tiny ones, so the first chunk is almost all of the work.
+        let huge = || {
+            Triangle::new(
+                Vector3::new(-30.0, -30.0, -9.0),
+                Vector3::new(30.0, -30.0, 9.0),
+                Vector3::new(0.0, 30.0, 0.0),
+            )
+        };
+
+        let tiny = |index: usize| {
+            let position = Vector3::new(index as f32 % 16.0, index as f32 / 16.0, 0.0);
+            Triangle::new(
+                position,
+                position + Vector3::new(0.1, 0.0, 0.0),
+                position + Vector3::new(0.0, 0.1, 0.0),
+            )
+        };
+
+        let first_update = |weight: ProgressWeight| {
+            let triangles: Vec<Triangle> = std::iter::once(huge())
+                .chain((1..2 * CHUNK_SIZE).map(tiny))
+                .collect();
+
+            let progress = RecordingProgress::default();
+            let options = ConvertOptions {
+                progress_weight: weight,
+                ..ConvertOptions::default()
+            };
+
+            voxelize(
+                triangles,
+                Vector3::new(0.0, 0.0, 0.0),
+                64,
+                20,
+                &progress,
+                NoOpPreprocessor {},
+                &options,
+            )
+            .unwrap();
+
+            let updates = progress.updates.into_inner();
+            assert_eq!(updates.last(), Some(&100.0));
+
+            updates[0]
+        };
+
+        assert_eq!(first_update(ProgressWeight::Count), 50.0);
+        assert!(first_update(ProgressWeight::Cells) > 90.0);
+    }
+
+    #[test]
+    fn test_convert_octree() {
+        let obj = Obj::load("tests/teapot.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        let bounding_box = bounding_box(&triangles);
+        let center = bounding_box.center();
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+
+        let octree = convert_octree(
+            parse_triangles(&obj),
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let obstacles = voxelize(
+            triangles,
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let clamped_obstacles: Vec<LocalVector> = obstacles
+            .iter()
+            .map(|o| LocalVector::new(o.x.min(width - 1), o.y.min(width - 1), o.z.min(height - 1)))
+            .collect();
+
+        let expected = OccupancyGrid::from_obstacles(&clamped_obstacles, width, height);
+        let mut octree_obstacles = octree.obstacles();
+        octree_obstacles.sort_unstable();
+
+        assert_eq!(octree.obstacle_count(), expected.obstacle_count());
+        assert_eq!(octree_obstacles, expected.obstacles());
+    }
+
+    #[test]
+    fn test_voxelize_triangle() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        for triangle in generate_sphere(6.0, 1) {
+            let mut obstacles = Vec::new();
+            voxelize_triangle(&triangle, &center, 14, 14, 1.0, &mut |o| obstacles.push(o));
+
+            let expected = find_obstacles(&triangle, &center, 14, 14, DEFAULT_EPSILON, 0);
+
+            assert!(!obstacles.is_empty());
+            assert_eq!(obstacles, expected);
+
+            // Cells of half size are the same as triangle scaled twice.
+            let mut obstacles = Vec::new();
+            voxelize_triangle(&triangle, &center, 28, 28, 0.5, &mut |o| obstacles.push(o));
+
+            let expected =
+                find_obstacles(&triangle.scale(2.0), &center, 28, 28, DEFAULT_EPSILON, 0);
+
+            assert_eq!(obstacles, expected);
+        }
+    }
+
+    #[test]
+    fn test_triangles_in_region() {
+        // Plane of unit quads in z = 0 from -10 to 10, region is its quarter.
+        let triangles = generate_grid_plane(20);
+        let region = BoundingBox::new(
+            Vector3::new(-10.0, -10.0, -2.0),
+            Vector3::new(0.0, 0.0, 2.0),
+        );
+
+        let in_region = triangles_in_region(triangles, &region);
+
+        // Quads close to the region are kept too, so parts outside of it must be clipped.
+        assert!(in_region.len() >= 2 * 10 * 10);
+        assert!(in_region.len() < 2 * 20 * 20);
+
+        let center = region.center();
+        let (width, height) = (10, 4);
+        let grid_box = BoundingBox::from_grid(&center, width, height);
+
+        let obstacles = voxelize(
+            in_region,
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            ClipPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let world_obstacles = to_world_vectors(&dedup_obstacles(obstacles), &center, width, height);
+
+        assert_eq!(world_obstacles.len(), 10 * 10);
+        assert!(world_obstacles.iter().all(|o| {
+            let o = Vector3::new(o.x as f32, o.y as f32, o.z as f32);
+            grid_box.contains(&o) && region.contains(&o)
+        }));
+    }
+
+    #[test]
+    fn test_convert_with_index() {
+        let bvh = Bvh::new(generate_sphere(12.0, 3));
+        let center = Vector3::new(1.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        for &(cell_size, width, height) in [(1.0, 30, 30), (0.5, 56, 52)].iter() {
+            let scale = 1.0 / cell_size;
+
+            let fresh = voxelize_occupancy(
+                generate_sphere(12.0, 3)
+                    .into_iter()
+                    .map(|t| t.scale(scale))
+                    .collect(),
+                center * scale,
+                width,
+                height,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &options,
+            )
+            .unwrap();
+
+            let indexed = convert_with_index(
+                &bvh,
+                center,
+                cell_size,
+                width,
+                height,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &options,
+            )
+            .unwrap();
+
+            assert!(fresh.obstacle_count() > 0);
+            assert_eq!(indexed, fresh);
+        }
+
+        // Grid around side of the sphere, triangles outside of it aren't clamped to its borders.
+        let center = Vector3::new(12.0, 0.0, 0.0);
+        let region = BoundingBox::from_grid(&center, 12, 12);
+
+        let indexed = convert_with_index(
+            &bvh,
+            center,
+            1.0,
+            12,
+            12,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let fresh = voxelize_occupancy(
+            triangles_in_region(generate_sphere(12.0, 3), &region),
+            center,
+            12,
+            12,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert!(indexed.obstacle_count() > 0);
+        assert_eq!(indexed, fresh);
+    }
+
+    #[test]
+    fn test_preview() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        let full = voxelize_occupancy(
+            generate_sphere(12.0, 3),
+            center,
+            32,
+            30,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let coarse = preview(
+            generate_sphere(12.0, 3),
+            center,
+            32,
+            30,
+            4,
+            NoOpProgress {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!((coarse.width(), coarse.height()), (8, 8));
+
+        let expected = full.resample(8, 8);
+        let mut both = expected.clone();
+        both.intersection(&coarse).unwrap();
+
+        // Surface of the coarse sphere mostly lies where the full one does.
+        assert!(both.obstacle_count() * 4 >= expected.obstacle_count() * 3);
+        assert!(both.obstacle_count() * 4 >= coarse.obstacle_count() * 3);
+    }
+
+    #[test]
+    fn test_voxelize_occupancy() {
+        // Every triangle is there twice, so copies voxelized by different threads overlap,
+        // and the sphere sticks out of the grid to be clamped.
+        let triangles = || {
+            let mut triangles = generate_sphere(12.0, 3);
+            triangles.extend(generate_sphere(12.0, 3));
+            triangles
+        };
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let (width, height) = (26, 20);
+
+        let options = ConvertOptions {
+            threads: Some(4),
+            ..ConvertOptions::default()
+        };
+
+        let occupancy = voxelize_occupancy(
+            triangles(),
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let serial_options = ConvertOptions {
+            threads: Some(1),
+            ..ConvertOptions::default()
+        };
+
+        let obstacles = voxelize(
+            triangles(),
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &serial_options,
+        )
+        .unwrap();
+
+        let clamped_obstacles: Vec<LocalVector> = obstacles
+            .iter()
+            .map(|o| LocalVector::new(o.x.min(width - 1), o.y.min(width - 1), o.z.min(height - 1)))
+            .collect();
+
+        let expected = OccupancyGrid::from_obstacles(&clamped_obstacles, width, height);
+
+        assert!(expected.obstacle_count() > 0);
+        assert_eq!(occupancy, expected);
+    }
+
+    #[test]
+    fn test_obstacle_iter() {
+        let obj = Obj::load("tests/teapot.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        let bounding_box = bounding_box(&triangles);
+        let center = bounding_box.center();
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+
+        let options = ConvertOptions::default();
+
+        let lazy_obstacles: Vec<LocalVector> = obstacle_iter(
+            parse_triangles(&obj),
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+        let obstacles = voxelize(
+            triangles,
+            center,
+            width,
+            height,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(lazy_obstacles, obstacles);
+    }
+
+    #[test]
+    fn test_obstacle_iter_error() {
+        let triangle = Triangle::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(20.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+
+        let options = ConvertOptions {
+            out_of_bounds: OutOfBounds::Error,
+            ..ConvertOptions::default()
+        };
+
+        let obstacles: Vec<_> = obstacle_iter(
+            vec![triangle],
+            Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .collect();
+
+        assert_eq!(obstacles, vec![Err(ConvertError::OutOfBounds(0))]);
+    }
+
+    #[test]
+    fn test_convert_with_stats() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(5.0, 5.0, 0.0);
+        let c = Vector3::new(-5.0, -5.0, 0.0);
+
+        let triangles = vec![Triangle::new(a, b, c), Triangle::new(a, b, c)];
+
+        let start = Instant::now();
+
+        let (_, stats) = convert_with_stats(
+            triangles,
+            Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let elapsed = start.elapsed();
+        let timings = &stats.timings;
+
+        // Both triangles mark the same cells, so only cells of one are left after dedup.
+        let expected = dedup_obstacles(find_obstacles(
+            &Triangle::new(a, b, c),
+            &Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            DEFAULT_EPSILON,
+            0,
+        ));
+
+        assert_eq!(stats.triangles, 2);
+        assert_eq!(stats.obstacles, expected.len());
+        assert_eq!(stats.clamped_obstacles, 0);
+        assert_eq!(stats.empty_triangles.total(), 0);
+
+        // Phases run one after another inside of the measured time, parse and export aren't run.
+        assert_eq!(timings.parse, Duration::from_secs(0));
+        assert_eq!(timings.export, Duration::from_secs(0));
+        assert!(timings.voxelize > Duration::from_secs(0));
+        assert!(timings.total() <= elapsed);
+    }
+
+    #[test]
+    fn test_inside_tests() {
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(4.0, 0.0, 0.0),
+                Vector3::new(0.0, 4.0, 0.0),
+            )
+        };
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let (_, stats) = convert_with_stats(
+            vec![triangle()],
+            center,
+            16,
+            16,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        // Bounding box padded by a cell spans 7..13 on x and y and 7..9 on z.
+        assert_eq!(stats.inside_tests, Some(6 * 6 * 2));
+
+        let inside_tests = AtomicU64::new(0);
+        let options = ConvertOptions {
+            inside_tests: Some(&inside_tests),
+            mode: VoxelMode::CenterSample,
+            ..ConvertOptions::default()
+        };
+
+        let triangles = vec![
+            triangle(),
+            triangle().translate(&Vector3::new(0.0, 0.0, 3.0)),
+        ];
+        voxelize(
+            triangles,
+            center,
+            16,
+            16,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(inside_tests.load(Ordering::Relaxed), 2 * 6 * 6 * 2);
+
+        // Extra pass explaining empty triangles isn't a part of conversion.
+        empty_triangles(&[triangle()], center, 16, 16, &options);
+
+        assert_eq!(inside_tests.into_inner(), 2 * 6 * 6 * 2);
+    }
+
+    #[test]
+    fn test_add_extra_obstacles() {
+        let a = Vector3::new(-4.0, -4.0, 0.0);
+        let b = Vector3::new(4.0, -4.0, 0.0);
+        let c = Vector3::new(4.0, 4.0, 0.0);
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let obstacles = voxelize(
+            vec![Triangle::new(a, b, c)],
+            center,
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let obstacles = dedup_obstacles(obstacles);
+
+        // One cell given in local coordinates, one in world coordinates above the triangle.
+        let wall = LocalVector::new(1, 8, 7);
+        let world = LocalVector::from_world_vector(&Vector3::new(0.0, 0.0, 3.0), &center, 10, 10);
+        let extra_obstacles = vec![wall, world, obstacles[0]];
+
+        let merged = add_extra_obstacles(obstacles.clone(), extra_obstacles);
+
+        assert_eq!(world, LocalVector::new(5, 5, 8));
+        assert!(!obstacles.contains(&wall) && !obstacles.contains(&world));
+        assert_eq!(merged.len(), obstacles.len() + 2);
+        assert!(merged.contains(&wall) && merged.contains(&world));
+        assert!(obstacles.iter().all(|o| merged.contains(o)));
+    }
+
+    #[test]
+    fn test_empty_triangles() {
+        let triangle = |a: [f32; 3], b: [f32; 3], c: [f32; 3]| {
+            Triangle::new(Vector3::from(a), Vector3::from(b), Vector3::from(c))
+        };
+
+        let triangles = || {
+            vec![
+                triangle([-3.0, -3.0, 0.0], [3.0, -3.0, 0.0], [0.0, 3.0, 0.0]),
+                triangle([1.0, 1.0, 0.0], [2.0, 2.0, 0.0], [3.0, 3.0, 0.0]),
+                triangle([20.0, 0.0, 0.0], [22.0, 0.0, 0.0], [21.0, 2.0, 0.0]),
+                triangle([0.3, 0.3, 0.0], [0.7, 0.3, 0.0], [0.5, 0.6, 0.0]),
+                triangle([-3.0, 0.4, 0.0], [3.0, 0.4, 0.0], [3.0, 0.6, 0.0]),
+                triangle([-3.0, 3.0, 1.0], [3.0, 3.0, 1.0], [0.0, -3.0, 1.0]),
+            ]
+        };
+
+        // Center sampling misses cells, which the precise test never does inside the grid.
+        let options = ConvertOptions {
+            mode: VoxelMode::CenterSample,
+            ..ConvertOptions::default()
+        };
+
+        let (_, stats) = convert_with_stats(
+            triangles(),
+            Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let empty = stats.empty_triangles;
+
+        assert_eq!(
+            empty_triangles(&triangles(), Vector3::new(0.0, 0.0, 0.0), 10, 10, &options),
+            empty
+        );
+
+        // Triangles are counted after preprocessing, clipping drops the one outside.
+        let (_, clipped_stats) = convert_with_stats(
+            triangles(),
+            Vector3::new(0.0, 0.0, 0.0),
+            10,
+            10,
+            NoOpProgress {},
+            ClipPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(clipped_stats.empty_triangles.outside, 0);
+        assert_eq!(clipped_stats.empty_triangles.total(), 3);
+
+        assert_eq!(stats.triangles, 6);
+        assert!(stats.obstacles > 0);
+        assert_eq!(
+            empty,
+            EmptyTriangles {
+                degenerate: 1,
+                outside: 1,
+                too_small: 1,
+                grazing: 1,
+            }
+        );
+        assert_eq!(empty.total(), 4);
+    }
+
+    #[test]
+    fn test_parse_triangles_with_texture_and_normal_indices() {
+        let obj = Obj::load("tests/textured.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        assert_eq!(triangles.len(), 2);
+
+        assert_eq!(triangles[0].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[0].b, Vector3::new(5.0, 5.0, 0.0));
+        assert_eq!(triangles[0].c, Vector3::new(-5.0, 5.0, 0.0));
+
+        assert_eq!(triangles[1].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[1].b, Vector3::new(5.0, 5.0, 0.0));
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn test_parse_triangles_with_mixed_indices() {
+        let obj = Obj::load("tests/mixed.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        assert_eq!(triangles.len(), 3);
+
+        assert_eq!(triangles[0].c, Vector3::new(-5.0, 5.0, 0.0));
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 0.0, 5.0));
+
+        assert_eq!(triangles[2].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[2].b, Vector3::new(-5.0, 5.0, 0.0));
+        assert_eq!(triangles[2].c, Vector3::new(0.0, 0.0, 5.0));
+    }
+
+    #[test]
+    fn test_parse_triangles_for_objects() {
+        let obj = Obj::load("tests/objects.obj").unwrap();
+
+        assert_eq!(parse_triangles(&obj).len(), 3);
+        assert_eq!(parse_triangles_for(&obj, &[]).len(), 3);
+
+        let triangles = parse_triangles_for(&obj, &["terrain".to_string()]);
+
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].c, Vector3::new(-5.0, 5.0, 0.0));
+
+        let triangles = parse_triangles_for(&obj, &["barrels".to_string()]);
+
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].b, Vector3::new(-5.0, 5.0, 0.0));
+
+        assert_eq!(parse_triangles_for(&obj, &["props".to_string()]).len(), 2);
+        assert!(parse_triangles_for(&obj, &["missing".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_triangles_placed() {
+        let obj = Obj::load("tests/objects.obj").unwrap();
+
+        let placements = vec![
+            "terrain=0,0,10".parse::<Placement>().unwrap(),
+            "barrels=1,2,3".parse::<Placement>().unwrap(),
+        ];
+
+        let (triangles, _) =
+            parse_triangles_placed(&obj, &[], &placements, NonFinite::Skip).unwrap();
+        let original_triangles = parse_triangles(&obj);
+
+        assert_eq!(triangles.len(), 3);
+
+        // Terrain is moved, crates stay in place and barrels are moved by their group offset.
+        assert_eq!(
+            triangles[0].c,
+            original_triangles[0].c + Vector3::new(0.0, 0.0, 10.0)
+        );
+        assert_eq!(triangles[1].c, original_triangles[1].c);
+        assert_eq!(
+            triangles[2].c,
+            original_triangles[2].c + Vector3::new(1.0, 2.0, 3.0)
+        );
+
+        assert!("terrain".parse::<Placement>().is_err());
+        assert!("terrain=1,2".parse::<Placement>().is_err());
+    }
+
+    #[test]
+    fn test_parse_triangles_without_objects() {
+        let obj = Obj::load("tests/bare.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[0].b, Vector3::new(3.0, 0.0, 0.0));
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn test_parse_triangles_malformed() {
+        let obj = Obj::load("tests/malformed.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 0.0, 3.0));
+
+        assert_eq!(
+            malformed_faces(&obj),
+            vec![ConvertError::MalformedFace {
+                face_index: 1,
+                vertex_index: 8,
+            }]
+        );
+        assert!(malformed_faces(&Obj::load("tests/bare.obj").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_triangles_primitives() {
+        let (mut obj, skipped) = load_obj_checked("tests/primitives.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        assert_eq!(skipped, 4);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[0].b, Vector3::new(3.0, 0.0, 0.0));
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 0.0, 3.0));
+
+        // Line stored as a face by the parser.
+        let line = SimplePolygon(vec![IndexTuple(0, None, None), IndexTuple(1, None, None)]);
+        obj.data.objects[0].groups[0].polys.push(line);
+
+        assert_eq!(short_faces(&obj), 1);
+        assert_eq!(parse_triangles(&obj).len(), 2);
+        assert!(malformed_faces(&obj).is_empty());
+    }
+
+    #[test]
+    fn test_parse_triangles_non_finite() {
+        let obj = Obj::load("tests/non_finite.obj").unwrap();
+
+        let (triangles, skipped) = parse_triangles_checked(&obj, &[], NonFinite::Skip).unwrap();
+
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(skipped, 2);
+        assert_eq!(parse_triangles(&obj).len(), 1);
+
+        let bounding_box = bounding_box(&triangles);
+
+        assert!(bounding_box.min.iter().all(|v| v.is_finite()));
+        assert!(bounding_box.max.iter().all(|v| v.is_finite()));
+
+        let result = parse_triangles_checked(&obj, &[], NonFinite::Error);
+        assert_eq!(result.unwrap_err(), ConvertError::NonFinite(1));
+    }
+
+    #[test]
+    fn test_parse_triangles_gzip() {
+        let source = std::fs::read("tests/simple.obj").unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&source).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert!(compressed.starts_with(&[0x1f, 0x8b]));
+
+        let path = std::env::temp_dir().join("obj-to-pathfinding-grid-simple.obj.gz");
+        std::fs::write(&path, compressed).unwrap();
+
+        let triangles = parse_triangles_auto(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected_triangles = parse_triangles_auto("tests/simple.obj").unwrap();
+        assert!(!triangles.is_empty());
+
+        let bounding_box = bounding_box(&expected_triangles);
+        let center = bounding_box.center();
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+
+        let to_obstacles = |triangles| {
+            voxelize(
+                triangles,
+                center,
+                width,
+                height,
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &ConvertOptions::default(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(to_obstacles(triangles), to_obstacles(expected_triangles));
+    }
+
+    #[test]
+    fn test_triangles_iter() {
+        for path in &[
+            "tests/teapot.obj",
+            "tests/polygons.obj",
+            "tests/non_finite.obj",
+        ] {
+            let obj = Obj::load(path).unwrap();
+
+            let triangles = parse_triangles(&obj);
+            let iterated_triangles: Vec<Triangle> = triangles_iter(&obj).collect();
+
+            assert_eq!(
+                format!("{:?}", iterated_triangles),
+                format!("{:?}", triangles)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_polygons() {
+        let obj = Obj::load("tests/polygons.obj").unwrap();
+        let triangles = parse_triangles(&obj);
+
+        // Quad is split into two triangles and pentagon into three.
+        assert_eq!(triangles.len(), 5);
+
+        assert_eq!(triangles[0].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[0].b, Vector3::new(4.0, 0.0, 0.0));
+        assert_eq!(triangles[0].c, Vector3::new(4.0, 4.0, 0.0));
+
+        assert_eq!(triangles[1].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[1].b, Vector3::new(4.0, 4.0, 0.0));
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 4.0, 0.0));
+
+        assert!(triangles[2..]
+            .iter()
+            .all(|t| t.a == Vector3::new(0.0, 0.0, 2.0)));
     }
 }
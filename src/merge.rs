@@ -0,0 +1,350 @@
+use crate::geometry::Triangle;
+use nalgebra::Vector3;
+use std::collections::{HashMap, VecDeque};
+
+/// Vertices closer than `1 / QUANTIZATION` are treated as the same vertex.
+const QUANTIZATION: f32 = 1024.0;
+
+/// Sine of the largest angle between two boundary edges which are still considered collinear.
+const COLLINEAR_TOLERANCE: f32 = 1e-5;
+
+type VertexKey = [i64; 3];
+
+/// Replaces every group of adjacent coplanar triangles with the smallest set of triangles
+/// covering the same polygon, so tessellated planes are voxelized with less triangles.
+/// Triangles are coplanar if angle between their normals and normal of the first triangle of
+/// the group is at most `max_angle` radians. Groups with holes or not simple boundary are kept
+/// as they are, same as degenerate triangles.
+pub fn merge_coplanar(triangles: Vec<Triangle>, max_angle: f32) -> Vec<Triangle> {
+    let mut vertices = Vec::new();
+    let mut vertex_ids = HashMap::new();
+
+    let mut vertex_id = |vertex: Vector3<f32>| {
+        let key = quantize(&vertex);
+
+        *vertex_ids.entry(key).or_insert_with(|| {
+            vertices.push(vertex);
+            vertices.len() - 1
+        })
+    };
+
+    let faces: Vec<[usize; 3]> = triangles
+        .iter()
+        .map(|t| [vertex_id(t.a), vertex_id(t.b), vertex_id(t.c)])
+        .collect();
+
+    let normals: Vec<Option<Vector3<f32>>> = triangles
+        .iter()
+        .zip(&faces)
+        .map(|(triangle, face)| {
+            let normal = triangle.normal();
+            let distinct = face[0] != face[1] && face[1] != face[2] && face[2] != face[0];
+
+            if distinct && normal.norm() > 0.0 {
+                Some(normal.normalize())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let mut edge_faces: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+
+    for (index, face) in faces.iter().enumerate() {
+        if normals[index].is_none() {
+            continue;
+        }
+
+        for (start, end) in face_edges(face) {
+            let edge = (start.min(end), start.max(end));
+            edge_faces.entry(edge).or_default().push(index);
+        }
+    }
+
+    let min_cos = max_angle.cos();
+    let mut triangles: Vec<Option<Triangle>> = triangles.into_iter().map(Some).collect();
+    let mut visited = vec![false; triangles.len()];
+    let mut merged = Vec::with_capacity(triangles.len());
+
+    for seed in 0..triangles.len() {
+        if visited[seed] {
+            continue;
+        }
+
+        visited[seed] = true;
+
+        let seed_normal = match normals[seed] {
+            Some(normal) => normal,
+            None => {
+                merged.extend(triangles[seed].take());
+                continue;
+            }
+        };
+
+        // Breadth-first search over triangles sharing an edge with exactly one other triangle.
+        let mut patch = vec![seed];
+        let mut queue = VecDeque::new();
+        queue.push_back(seed);
+
+        while let Some(index) = queue.pop_front() {
+            for (start, end) in face_edges(&faces[index]) {
+                let neighbours = &edge_faces[&(start.min(end), start.max(end))];
+
+                if neighbours.len() != 2 {
+                    continue;
+                }
+
+                for &neighbour in neighbours {
+                    let coplanar =
+                        normals[neighbour].map_or(false, |n| n.dot(&seed_normal) >= min_cos);
+
+                    if !visited[neighbour] && coplanar {
+                        visited[neighbour] = true;
+                        patch.push(neighbour);
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+        }
+
+        let retriangulated = if patch.len() > 1 {
+            boundary_loop(&patch, &faces)
+                .map(|boundary| remove_collinear(boundary, &vertices))
+                .and_then(|boundary| triangulate(&boundary, &vertices, &seed_normal))
+                .filter(|retriangulated| retriangulated.len() < patch.len())
+        } else {
+            None
+        };
+
+        match retriangulated {
+            Some(retriangulated) => merged.extend(retriangulated),
+            None => merged.extend(patch.iter().filter_map(|&index| triangles[index].take())),
+        }
+    }
+
+    merged
+}
+
+fn quantize(vertex: &Vector3<f32>) -> VertexKey {
+    let component = |value: f32| (value * QUANTIZATION).round() as i64;
+    [
+        component(vertex.x),
+        component(vertex.y),
+        component(vertex.z),
+    ]
+}
+
+fn face_edges(face: &[usize; 3]) -> [(usize, usize); 3] {
+    [(face[0], face[1]), (face[1], face[2]), (face[2], face[0])]
+}
+
+/// Vertices of the patch boundary in winding order of its triangles, `None` if boundary isn't
+/// a single loop visiting every vertex once.
+fn boundary_loop(patch: &[usize], faces: &[[usize; 3]]) -> Option<Vec<usize>> {
+    let mut edge_counts: HashMap<(usize, usize), usize> = HashMap::new();
+
+    for &index in patch {
+        for (start, end) in face_edges(&faces[index]) {
+            *edge_counts
+                .entry((start.min(end), start.max(end)))
+                .or_default() += 1;
+        }
+    }
+
+    let mut next = HashMap::new();
+
+    for &index in patch {
+        for (start, end) in face_edges(&faces[index]) {
+            let boundary = edge_counts[&(start.min(end), start.max(end))] == 1;
+
+            if boundary && next.insert(start, end).is_some() {
+                return None;
+            }
+        }
+    }
+
+    let first = *next.keys().min()?;
+    let mut boundary = vec![first];
+    let mut current = next[&first];
+
+    while current != first {
+        if boundary.len() >= next.len() {
+            return None;
+        }
+
+        boundary.push(current);
+        current = *next.get(&current)?;
+    }
+
+    if boundary.len() == next.len() {
+        Some(boundary)
+    } else {
+        None
+    }
+}
+
+/// Removes vertices lying on the segment between their neighbours.
+fn remove_collinear(mut boundary: Vec<usize>, vertices: &[Vector3<f32>]) -> Vec<usize> {
+    while boundary.len() > 3 {
+        let length = boundary.len();
+
+        let collinear = (0..length).find(|&index| {
+            let previous = vertices[boundary[(index + length - 1) % length]];
+            let current = vertices[boundary[index]];
+            let next = vertices[boundary[(index + 1) % length]];
+
+            let incoming = current - previous;
+            let outgoing = next - current;
+            let sine = incoming.cross(&outgoing).norm() / (incoming.norm() * outgoing.norm());
+
+            sine <= COLLINEAR_TOLERANCE && incoming.dot(&outgoing) > 0.0
+        });
+
+        match collinear {
+            Some(index) => boundary.remove(index),
+            None => break,
+        };
+    }
+
+    boundary
+}
+
+/// Ear clipping of the boundary polygon, `None` if no ear is found.
+fn triangulate(
+    boundary: &[usize],
+    vertices: &[Vector3<f32>],
+    normal: &Vector3<f32>,
+) -> Option<Vec<Triangle>> {
+    let mut remaining = boundary.to_vec();
+    let mut triangles = Vec::with_capacity(boundary.len().saturating_sub(2));
+
+    while remaining.len() > 3 {
+        let length = remaining.len();
+
+        let ear = (0..length).find(|&index| {
+            let previous = vertices[remaining[(index + length - 1) % length]];
+            let current = vertices[remaining[index]];
+            let next = vertices[remaining[(index + 1) % length]];
+
+            let ear = Triangle::new(previous, current, next);
+
+            if ear.normal().dot(normal) <= 0.0 {
+                return false;
+            }
+
+            let edges = [(previous, current), (current, next), (next, previous)];
+
+            // No other vertex may lie inside of the ear or on its edges.
+            remaining
+                .iter()
+                .map(|&other| vertices[other])
+                .filter(|point| *point != previous && *point != current && *point != next)
+                .all(|point| {
+                    !edges.iter().all(|(start, end)| {
+                        (end - start).cross(&(point - start)).dot(normal) >= 0.0
+                    })
+                })
+        })?;
+
+        let previous = remaining[(ear + length - 1) % length];
+        let next = remaining[(ear + 1) % length];
+
+        triangles.push(Triangle::new(
+            vertices[previous],
+            vertices[remaining[ear]],
+            vertices[next],
+        ));
+
+        remaining.remove(ear);
+    }
+
+    if let [a, b, c] = remaining[..] {
+        triangles.push(Triangle::new(vertices[a], vertices[b], vertices[c]));
+    }
+
+    Some(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{LocalVector, Triangle, DEFAULT_EPSILON};
+    use crate::merge::merge_coplanar;
+    use crate::testutil::{generate_grid_plane, generate_sphere};
+    use crate::{dedup_obstacles, find_obstacles_by};
+    use nalgebra::Vector3;
+    use std::cell::Cell;
+
+    /// Obstacles of all triangles and count of inside tests done to find them.
+    fn voxelize_counted(triangles: &[Triangle]) -> (Vec<LocalVector>, usize) {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let tests = Cell::new(0);
+
+        let obstacles = triangles
+            .iter()
+            .flat_map(|triangle| {
+                find_obstacles_by(triangle, &center, 20, 20, 0, |v| {
+                    tests.set(tests.get() + 1);
+                    triangle.is_inside_with_epsilon(v, DEFAULT_EPSILON)
+                })
+            })
+            .collect();
+
+        (dedup_obstacles(obstacles), tests.get())
+    }
+
+    #[test]
+    fn test_merge_quad() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(4.0, 0.0, 0.0);
+        let c = Vector3::new(4.0, 4.0, 0.0);
+        let d = Vector3::new(0.0, 4.0, 0.0);
+
+        let triangles = || vec![Triangle::new(a, b, c), Triangle::new(a, c, d)];
+        let merged = merge_coplanar(triangles(), 0.0);
+
+        // Quad can't be covered by less than two triangles.
+        assert_eq!(merged.len(), 2);
+        assert_eq!(
+            voxelize_counted(&merged).0,
+            voxelize_counted(&triangles()).0
+        );
+    }
+
+    #[test]
+    fn test_merge_tessellated_plane() {
+        let triangles = generate_grid_plane(8);
+        let merged = merge_coplanar(generate_grid_plane(8), 0.0);
+
+        assert_eq!(triangles.len(), 128);
+        assert_eq!(merged.len(), 2);
+
+        let (obstacles, tests) = voxelize_counted(&triangles);
+        let (merged_obstacles, merged_tests) = voxelize_counted(&merged);
+
+        assert_eq!(merged_obstacles, obstacles);
+        assert!(merged_tests < tests);
+    }
+
+    #[test]
+    fn test_merge_keeps_curved_surface() {
+        let triangles = generate_sphere(5.0, 1);
+
+        assert_eq!(triangles.len(), 80);
+        assert_eq!(merge_coplanar(triangles, 1f32.to_radians()).len(), 80);
+    }
+
+    #[test]
+    fn test_merge_keeps_plane_with_hole() {
+        // Square ring around the center quad of a 3x3 plane.
+        let triangles: Vec<Triangle> = generate_grid_plane(3)
+            .into_iter()
+            .filter(|t| {
+                let center = (t.a + t.b + t.c) / 3.0;
+                center.x.abs() > 0.5 || center.y.abs() > 0.5
+            })
+            .collect();
+
+        assert_eq!(triangles.len(), 16);
+        assert_eq!(merge_coplanar(triangles, 0.0).len(), 16);
+    }
+}
@@ -0,0 +1,536 @@
+use crate::error::GridError;
+use crate::geometry::LocalVector;
+use crate::sink::GridSink;
+use flying_pathfinding::Grid;
+use std::fmt;
+use std::ops::Range;
+
+/// Dense occupancy grid which, unlike `Grid`, can be queried after obstacles are set.
+/// Cells are indexed from 0 to width - 1 on x and y axes and from 0 to height - 1 on z axis.
+#[derive(Clone, Eq, PartialEq)]
+pub struct OccupancyGrid {
+    width: u32,
+    height: u32,
+    cells: Vec<bool>,
+}
+
+impl OccupancyGrid {
+    pub fn new(width: u32, height: u32) -> Self {
+        let length = width as usize * width as usize * height as usize;
+
+        OccupancyGrid {
+            width,
+            height,
+            cells: vec![false; length],
+        }
+    }
+
+    pub fn from_obstacles(obstacles: &[LocalVector], width: u32, height: u32) -> Self {
+        let mut grid = OccupancyGrid::new(width, height);
+
+        for obstacle in obstacles {
+            grid.set_obstacle(obstacle.x, obstacle.y, obstacle.z);
+        }
+
+        grid
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn contains(&self, x: u32, y: u32, z: u32) -> bool {
+        x < self.width && y < self.width && z < self.height
+    }
+
+    /// Cells outside of the grid are never obstacles.
+    pub fn is_obstacle(&self, x: u32, y: u32, z: u32) -> bool {
+        self.contains(x, y, z) && self.cells[self.index(x, y, z)]
+    }
+
+    /// Obstacles outside of the grid are ignored.
+    pub fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
+        if self.contains(x, y, z) {
+            let index = self.index(x, y, z);
+            self.cells[index] = true;
+        }
+    }
+
+    pub fn obstacles(&self) -> Vec<LocalVector> {
+        let mut obstacles = Vec::new();
+
+        for x in 0..self.width {
+            for y in 0..self.width {
+                for z in 0..self.height {
+                    if self.is_obstacle(x, y, z) {
+                        obstacles.push(LocalVector::new(x, y, z));
+                    }
+                }
+            }
+        }
+
+        obstacles
+    }
+
+    /// Obstacles outside of the grid are ignored.
+    pub fn remove_obstacle(&mut self, x: u32, y: u32, z: u32) {
+        if self.contains(x, y, z) {
+            let index = self.index(x, y, z);
+            self.cells[index] = false;
+        }
+    }
+
+    pub fn obstacle_count(&self) -> usize {
+        self.cells.iter().filter(|&&cell| cell).count()
+    }
+
+    /// Returns part of the grid between min and max, both inclusive, with min moved to
+    /// (0, 0, 0). Grid is always square on x and y axes, so region must be square too.
+    pub fn crop(&self, min: LocalVector, max: LocalVector) -> Result<OccupancyGrid, GridError> {
+        let is_valid = min.x <= max.x
+            && min.y <= max.y
+            && min.z <= max.z
+            && self.contains(max.x, max.y, max.z)
+            && max.x - min.x == max.y - min.y;
+
+        if !is_valid {
+            return Err(GridError::InvalidRegion {
+                min: (min.x, min.y, min.z),
+                max: (max.x, max.y, max.z),
+            });
+        }
+
+        let width = max.x - min.x + 1;
+        let height = max.z - min.z + 1;
+
+        let mut grid = OccupancyGrid::new(width, height);
+
+        for index in 0..grid.len() {
+            let cell = grid.position(index);
+
+            if self.is_obstacle(min.x + cell.x, min.y + cell.y, min.z + cell.z) {
+                grid.cells[index] = true;
+            }
+        }
+
+        Ok(grid)
+    }
+
+    /// Returns grid of given dimensions covering the same space. Every cell of this grid belongs
+    /// to the cell of the new grid containing its center, new cell is an obstacle if any of
+    /// its cells is, so downsampling by 2 merges 2x2x2 cells. New cells without any center,
+    /// for example when upsampling, copy the cell under their own center.
+    pub fn resample(&self, width: u32, height: u32) -> OccupancyGrid {
+        let mut grid = OccupancyGrid::new(width, height);
+
+        if self.cells.is_empty() {
+            return grid;
+        }
+
+        let x_ranges: Vec<_> = (0..width)
+            .map(|x| covered_cells(x, self.width, width))
+            .collect();
+        let z_ranges: Vec<_> = (0..height)
+            .map(|z| covered_cells(z, self.height, height))
+            .collect();
+
+        for index in 0..grid.len() {
+            let cell = grid.position(index);
+            let (xs, ys, zs) = (
+                &x_ranges[cell.x as usize],
+                &x_ranges[cell.y as usize],
+                &z_ranges[cell.z as usize],
+            );
+
+            grid.cells[index] = xs.clone().any(|x| {
+                ys.clone()
+                    .any(|y| zs.clone().any(|z| self.is_obstacle(x, y, z)))
+            });
+        }
+
+        grid
+    }
+
+    /// Smallest and largest coordinates of obstacles on every axis, `None` if there are none.
+    pub fn occupied_extent(&self) -> Option<(LocalVector, LocalVector)> {
+        let mut obstacles = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, &cell)| cell)
+            .map(|(index, _)| self.position(index));
+
+        let first = obstacles.next()?;
+        let mut min = LocalVector::new(first.x, first.y, first.z);
+        let mut max = first;
+
+        for obstacle in obstacles {
+            min.x = min.x.min(obstacle.x);
+            min.y = min.y.min(obstacle.y);
+            min.z = min.z.min(obstacle.z);
+            max.x = max.x.max(obstacle.x);
+            max.y = max.y.max(obstacle.y);
+            max.z = max.z.max(obstacle.z);
+        }
+
+        Some((min, max))
+    }
+
+    /// Human readable description of dimensions, obstacle count and occupied extent.
+    pub fn summary(&self) -> String {
+        let cells = self.len();
+        let obstacles = self.obstacle_count();
+
+        let fill_ratio = if cells == 0 {
+            0.0
+        } else {
+            obstacles as f64 / cells as f64
+        };
+
+        let extent = match self.occupied_extent() {
+            Some((min, max)) => format!(
+                "x {}..={}, y {}..={}, z {}..={}",
+                min.x, max.x, min.y, max.y, min.z, max.z
+            ),
+            None => "none".to_string(),
+        };
+
+        format!(
+            "{}x{}x{} grid, {} cells, {} obstacles ({:.2}% filled), occupied {}",
+            self.width,
+            self.width,
+            self.height,
+            cells,
+            obstacles,
+            fill_ratio * 100.0,
+            extent
+        )
+    }
+
+    /// Turns every free cell into obstacle and every obstacle into free cell.
+    /// Surface grids have free space on both sides of every wall, so after inversion inside and
+    /// outside of the mesh are connected obstacles. Result is most meaningful for solid grids.
+    pub fn invert(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = !*cell;
+        }
+    }
+
+    /// Marks every obstacle of other grid as obstacle of this grid.
+    pub fn union(&mut self, other: &OccupancyGrid) -> Result<(), GridError> {
+        self.check_dimensions(other)?;
+
+        for (cell, other_cell) in self.cells.iter_mut().zip(&other.cells) {
+            *cell |= *other_cell;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps only obstacles which are also obstacles of other grid.
+    pub fn intersection(&mut self, other: &OccupancyGrid) -> Result<(), GridError> {
+        self.check_dimensions(other)?;
+
+        for (cell, other_cell) in self.cells.iter_mut().zip(&other.cells) {
+            *cell &= *other_cell;
+        }
+
+        Ok(())
+    }
+
+    fn check_dimensions(&self, other: &OccupancyGrid) -> Result<(), GridError> {
+        if self.width != other.width || self.height != other.height {
+            return Err(GridError::DimensionMismatch {
+                expected: (self.width, self.height),
+                actual: (other.width, other.height),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn to_grid(&self) -> Grid {
+        self.to_sink()
+    }
+
+    /// Copies obstacles into a new grid of any backend.
+    pub fn to_sink<S: GridSink>(&self) -> S {
+        let mut grid = S::new(self.width, self.height);
+
+        for obstacle in self.obstacles() {
+            grid.set_obstacle(obstacle.x, obstacle.y, obstacle.z);
+        }
+
+        grid
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub(crate) fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        let width = self.width as usize;
+
+        x as usize + y as usize * width + z as usize * width * width
+    }
+
+    pub(crate) fn position(&self, index: usize) -> LocalVector {
+        let width = self.width as usize;
+
+        let x = index % width;
+        let y = index / width % width;
+        let z = index / (width * width);
+
+        LocalVector::new(x as u32, y as u32, z as u32)
+    }
+}
+
+/// Cells of axis with `source` cells which centers are inside of cell `target` of axis with
+/// `target_size` cells, or the cell under center of the target cell if there are none.
+fn covered_cells(target: u32, source: u32, target_size: u32) -> Range<u32> {
+    let ratio = source as f64 / target_size as f64;
+
+    let start = (target as f64 * ratio - 0.5).ceil().max(0.0) as u32;
+    let end = ((target + 1) as f64 * ratio - 0.5)
+        .ceil()
+        .min(source as f64) as u32;
+
+    if start < end {
+        start..end
+    } else {
+        let center = ((target as f64 + 0.5) * ratio) as u32;
+        center..center + 1
+    }
+}
+
+/// Sets obstacles of a grid edited by cleanup passes, so it can be exported.
+impl From<&OccupancyGrid> for Grid {
+    fn from(grid: &OccupancyGrid) -> Self {
+        grid.to_grid()
+    }
+}
+
+impl fmt::Display for OccupancyGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.summary())
+    }
+}
+
+/// Doesn't print cells, which are too many for any real grid.
+impl fmt::Debug for OccupancyGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OccupancyGrid")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("obstacles", &self.obstacle_count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::GridError;
+    use crate::geometry::LocalVector;
+    use crate::occupancy::OccupancyGrid;
+
+    #[test]
+    fn test_set_obstacle() {
+        let mut grid = OccupancyGrid::new(4, 2);
+
+        grid.set_obstacle(3, 2, 1);
+        grid.set_obstacle(4, 0, 0);
+
+        assert!(grid.is_obstacle(3, 2, 1));
+        assert!(!grid.is_obstacle(2, 3, 1));
+        assert!(!grid.is_obstacle(4, 0, 0));
+        assert_eq!(grid.obstacles(), vec![LocalVector::new(3, 2, 1)]);
+    }
+
+    #[test]
+    fn test_position() {
+        let grid = OccupancyGrid::new(4, 2);
+        let index = grid.index(3, 2, 1);
+
+        assert_eq!(grid.position(index), LocalVector::new(3, 2, 1));
+    }
+
+    #[test]
+    fn test_union() {
+        let mut grid = OccupancyGrid::new(4, 2);
+        let mut other = OccupancyGrid::new(4, 2);
+
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(1, 1, 1);
+        other.set_obstacle(1, 1, 1);
+        other.set_obstacle(3, 2, 1);
+
+        grid.union(&other).unwrap();
+
+        let expected_obstacles = vec![
+            LocalVector::new(0, 0, 0),
+            LocalVector::new(1, 1, 1),
+            LocalVector::new(3, 2, 1),
+        ];
+
+        assert_eq!(grid.obstacles(), expected_obstacles);
+    }
+
+    #[test]
+    fn test_intersection() {
+        let mut grid = OccupancyGrid::new(4, 2);
+        let mut other = OccupancyGrid::new(4, 2);
+
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(1, 1, 1);
+        other.set_obstacle(1, 1, 1);
+        other.set_obstacle(3, 2, 1);
+
+        grid.intersection(&other).unwrap();
+
+        assert_eq!(grid.obstacles(), vec![LocalVector::new(1, 1, 1)]);
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let mut grid = OccupancyGrid::new(4, 2);
+        let other = OccupancyGrid::new(4, 3);
+
+        let expected_error = GridError::DimensionMismatch {
+            expected: (4, 2),
+            actual: (4, 3),
+        };
+
+        assert_eq!(grid.union(&other), Err(expected_error));
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut grid = OccupancyGrid::new(4, 2);
+
+        assert_eq!(
+            grid.summary(),
+            "4x4x2 grid, 32 cells, 0 obstacles (0.00% filled), occupied none"
+        );
+
+        grid.set_obstacle(1, 3, 0);
+        grid.set_obstacle(2, 0, 1);
+
+        assert_eq!(
+            grid.occupied_extent(),
+            Some((LocalVector::new(1, 0, 0), LocalVector::new(2, 3, 1)))
+        );
+        assert_eq!(
+            grid.summary(),
+            "4x4x2 grid, 32 cells, 2 obstacles (6.25% filled), occupied x 1..=2, y 0..=3, z 0..=1"
+        );
+        assert_eq!(
+            format!("{:?}", grid),
+            "OccupancyGrid { width: 4, height: 2, obstacles: 2 }"
+        );
+    }
+
+    #[test]
+    fn test_invert() {
+        let mut grid = OccupancyGrid::new(4, 2);
+        grid.set_obstacle(1, 2, 0);
+        grid.set_obstacle(3, 3, 1);
+
+        let original = grid.clone();
+        grid.invert();
+
+        for index in 0..grid.len() {
+            let cell = grid.position(index);
+
+            assert_ne!(
+                grid.is_obstacle(cell.x, cell.y, cell.z),
+                original.is_obstacle(cell.x, cell.y, cell.z)
+            );
+        }
+
+        assert_eq!(grid.obstacle_count(), 30);
+
+        grid.invert();
+        assert_eq!(grid, original);
+    }
+
+    #[test]
+    fn test_resample() {
+        let mut grid = OccupancyGrid::new(4, 4);
+        grid.set_obstacle(0, 1, 0);
+        grid.set_obstacle(3, 3, 3);
+
+        let coarse = grid.resample(2, 2);
+
+        assert_eq!((coarse.width(), coarse.height()), (2, 2));
+        assert_eq!(
+            coarse.obstacles(),
+            vec![LocalVector::new(0, 0, 0), LocalVector::new(1, 1, 1)]
+        );
+
+        let fine = coarse.resample(4, 4);
+
+        assert_eq!(fine.obstacle_count(), 16);
+        assert!(fine.is_obstacle(1, 1, 1));
+        assert!(fine.is_obstacle(2, 3, 2));
+        assert!(!fine.is_obstacle(1, 2, 1));
+        assert_eq!(fine.resample(2, 2), coarse);
+
+        // Centers of cells 1 and 2 of three fall into the second of two cells.
+        let mut grid = OccupancyGrid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+
+        assert_eq!(
+            grid.resample(2, 2).obstacles(),
+            vec![LocalVector::new(1, 1, 1)]
+        );
+        assert_eq!(grid.resample(3, 3), grid);
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut grid = OccupancyGrid::new(6, 4);
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(2, 3, 1);
+        grid.set_obstacle(4, 4, 2);
+        grid.set_obstacle(5, 2, 2);
+
+        let cropped = grid
+            .crop(LocalVector::new(2, 2, 1), LocalVector::new(4, 4, 2))
+            .unwrap();
+
+        assert_eq!(cropped.width(), 3);
+        assert_eq!(cropped.height(), 2);
+        assert_eq!(
+            cropped.obstacles(),
+            vec![LocalVector::new(0, 1, 0), LocalVector::new(2, 2, 1)]
+        );
+    }
+
+    #[test]
+    fn test_crop_invalid() {
+        let grid = OccupancyGrid::new(6, 4);
+
+        let crop = |min: (u32, u32, u32), max: (u32, u32, u32)| {
+            grid.crop(
+                LocalVector::new(min.0, min.1, min.2),
+                LocalVector::new(max.0, max.1, max.2),
+            )
+        };
+
+        assert!(crop((0, 0, 0), (5, 5, 3)).is_ok());
+        assert!(crop((0, 0, 0), (6, 6, 3)).is_err());
+        assert!(crop((3, 3, 0), (2, 2, 1)).is_err());
+        assert!(crop((0, 0, 0), (2, 3, 1)).is_err());
+        assert_eq!(
+            crop((0, 0, 2), (1, 1, 1)),
+            Err(GridError::InvalidRegion {
+                min: (0, 0, 2),
+                max: (1, 1, 1)
+            })
+        );
+    }
+}
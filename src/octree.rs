@@ -0,0 +1,314 @@
+use crate::geometry::LocalVector;
+use std::fmt;
+
+/// Sparse occupancy grid, which stores only subdivided parts of the world, so huge mostly empty
+/// grids take little memory. Cells are indexed like in `OccupancyGrid`.
+#[derive(Clone)]
+pub struct OctreeGrid {
+    width: u32,
+    height: u32,
+    /// Edge length of the root node, the smallest power of two covering the grid.
+    size: u32,
+    root: Node,
+    obstacles: usize,
+}
+
+#[derive(Clone, Default)]
+enum Node {
+    #[default]
+    Empty,
+    /// Every cell of the node is an obstacle.
+    Full,
+    /// Children ordered by x, then y, then z bit of their position inside of the node.
+    Branch(Box<[Node; 8]>),
+}
+
+impl OctreeGrid {
+    pub fn new(width: u32, height: u32) -> Self {
+        OctreeGrid {
+            width,
+            height,
+            size: width.max(height).max(1).next_power_of_two(),
+            root: Node::Empty,
+            obstacles: 0,
+        }
+    }
+
+    pub fn from_obstacles(obstacles: &[LocalVector], width: u32, height: u32) -> Self {
+        let mut grid = OctreeGrid::new(width, height);
+
+        for obstacle in obstacles {
+            grid.set_obstacle(obstacle.x, obstacle.y, obstacle.z);
+        }
+
+        grid
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn contains(&self, x: u32, y: u32, z: u32) -> bool {
+        x < self.width && y < self.width && z < self.height
+    }
+
+    /// Cells outside of the grid are never obstacles.
+    pub fn is_obstacle(&self, x: u32, y: u32, z: u32) -> bool {
+        if !self.contains(x, y, z) {
+            return false;
+        }
+
+        let mut node = &self.root;
+        let mut size = self.size;
+        let mut position = [x, y, z];
+
+        loop {
+            match node {
+                Node::Empty => return false,
+                Node::Full => return true,
+                Node::Branch(children) => {
+                    size /= 2;
+                    node = &children[child_index(&mut position, size)];
+                }
+            }
+        }
+    }
+
+    /// Obstacles outside of the grid are ignored.
+    pub fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
+        if self.contains(x, y, z) && set(&mut self.root, self.size, [x, y, z]) {
+            self.obstacles += 1;
+        }
+    }
+
+    pub fn obstacle_count(&self) -> usize {
+        self.obstacles
+    }
+
+    /// Count of nodes of the tree, which is proportional to used memory.
+    pub fn node_count(&self) -> usize {
+        count_nodes(&self.root)
+    }
+
+    /// Iterates over obstacles in octree order, not sorted by coordinates.
+    pub fn iter(&self) -> impl Iterator<Item = LocalVector> + '_ {
+        OctreeIter {
+            stack: vec![(&self.root, [0, 0, 0], self.size)],
+            region: None,
+        }
+    }
+
+    pub fn obstacles(&self) -> Vec<LocalVector> {
+        self.iter().collect()
+    }
+}
+
+/// Moves position into the child containing it and returns index of the child.
+fn child_index(position: &mut [u32; 3], half_size: u32) -> usize {
+    let mut index = 0;
+
+    for (axis, coordinate) in position.iter_mut().enumerate() {
+        if *coordinate >= half_size {
+            *coordinate -= half_size;
+            index |= 1 << axis;
+        }
+    }
+
+    index
+}
+
+/// Returns true if the cell wasn't an obstacle before.
+fn set(node: &mut Node, size: u32, mut position: [u32; 3]) -> bool {
+    match node {
+        Node::Full => return false,
+        _ if size == 1 => {
+            *node = Node::Full;
+            return true;
+        }
+        Node::Empty => *node = Node::Branch(Box::default()),
+        Node::Branch(_) => {}
+    }
+
+    let children = match node {
+        Node::Branch(children) => children,
+        _ => unreachable!(),
+    };
+
+    let half_size = size / 2;
+    let index = child_index(&mut position, half_size);
+    let added = set(&mut children[index], half_size, position);
+
+    // Merge children back once the whole node is filled.
+    if children.iter().all(|child| matches!(child, Node::Full)) {
+        *node = Node::Full;
+    }
+
+    added
+}
+
+fn count_nodes(node: &Node) -> usize {
+    match node {
+        Node::Branch(children) => 1 + children.iter().map(count_nodes).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+struct OctreeIter<'a> {
+    /// Nodes left to visit with their minimal corner and size.
+    stack: Vec<(&'a Node, [u32; 3], u32)>,
+    /// Full node being iterated with index of its next cell.
+    region: Option<([u32; 3], u32, u64)>,
+}
+
+impl Iterator for OctreeIter<'_> {
+    type Item = LocalVector;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((origin, size, index)) = &mut self.region {
+                let size = *size as u64;
+
+                if *index < size * size * size {
+                    let x = origin[0] + (*index % size) as u32;
+                    let y = origin[1] + (*index / size % size) as u32;
+                    let z = origin[2] + (*index / (size * size)) as u32;
+
+                    *index += 1;
+
+                    return Some(LocalVector::new(x, y, z));
+                }
+
+                self.region = None;
+            }
+
+            let (node, origin, size) = self.stack.pop()?;
+
+            match node {
+                Node::Empty => {}
+                Node::Full => self.region = Some((origin, size, 0)),
+                Node::Branch(children) => {
+                    let half_size = size / 2;
+
+                    // Pushed in reverse, so the first child is visited first.
+                    for (index, child) in children.iter().enumerate().rev() {
+                        let mut child_origin = origin;
+
+                        for (axis, coordinate) in child_origin.iter_mut().enumerate() {
+                            if index & (1 << axis) != 0 {
+                                *coordinate += half_size;
+                            }
+                        }
+
+                        self.stack.push((child, child_origin, half_size));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Doesn't print nodes, which are too many for any real grid.
+impl fmt::Debug for OctreeGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OctreeGrid")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("obstacles", &self.obstacles)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::LocalVector;
+    use crate::octree::OctreeGrid;
+    use crate::testutil::Lcg;
+    use std::collections::HashSet;
+
+    /// Deterministic pseudo random coordinates, so failures are reproducible.
+    fn random_positions(count: usize, width: u32, height: u32) -> Vec<LocalVector> {
+        let mut lcg = Lcg::new(0x2545_f491_4f6c_dd1d);
+        let mut next = move |limit: u32| ((lcg.next_u64() >> 33) % limit as u64) as u32;
+
+        (0..count)
+            .map(|_| LocalVector::new(next(width), next(width), next(height)))
+            .collect()
+    }
+
+    #[test]
+    fn test_random_insertions() {
+        let (width, height) = (300, 70);
+
+        let mut grid = OctreeGrid::new(width, height);
+        let mut reference = HashSet::new();
+
+        for position in random_positions(5000, width, height) {
+            grid.set_obstacle(position.x, position.y, position.z);
+            reference.insert(position);
+        }
+
+        assert_eq!(grid.obstacle_count(), reference.len());
+
+        for position in random_positions(20000, width, height) {
+            let expected = reference.contains(&position);
+            assert_eq!(
+                grid.is_obstacle(position.x, position.y, position.z),
+                expected
+            );
+        }
+
+        let obstacles: HashSet<LocalVector> = grid.iter().collect();
+
+        assert_eq!(obstacles, reference);
+        assert_eq!(grid.obstacles().len(), reference.len());
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut grid = OctreeGrid::new(5, 3);
+
+        grid.set_obstacle(4, 4, 2);
+        grid.set_obstacle(5, 0, 0);
+        grid.set_obstacle(0, 0, 3);
+
+        assert!(grid.is_obstacle(4, 4, 2));
+        assert!(!grid.is_obstacle(5, 0, 0));
+        assert!(!grid.is_obstacle(0, 0, 3));
+        assert_eq!(grid.obstacle_count(), 1);
+    }
+
+    #[test]
+    fn test_sparse() {
+        let mut grid = OctreeGrid::new(4000, 4000);
+
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(3999, 3999, 3999);
+
+        assert_eq!(grid.obstacle_count(), 2);
+        assert!(grid.node_count() < 200);
+    }
+
+    #[test]
+    fn test_full_nodes_merge() {
+        let mut grid = OctreeGrid::new(4, 4);
+
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        grid.set_obstacle(1, 2, 3);
+
+        assert_eq!(grid.obstacle_count(), 64);
+        assert_eq!(grid.node_count(), 1);
+        assert_eq!(grid.iter().count(), 64);
+        assert!(grid.is_obstacle(3, 0, 2));
+    }
+}
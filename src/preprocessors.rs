@@ -0,0 +1,590 @@
+use crate::geometry::{Axis, BoundingBox, Triangle};
+use crate::Preprocessor;
+use nalgebra::Vector3;
+
+/// Clips triangles against world space bounding box of the grid, so parts outside of the grid
+/// aren't clamped to its borders. Clipped polygon is triangulated as a fan.
+pub struct ClipPreprocessor {}
+
+impl Preprocessor for ClipPreprocessor {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        width: u32,
+        height: u32,
+        center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        let grid_bounding_box = BoundingBox::from_grid(&center, width, height);
+
+        let mut polygon = vec![triangle.a, triangle.b, triangle.c];
+
+        for axis in 0..3 {
+            polygon = clip_polygon(&polygon, axis, grid_bounding_box.min[axis], false);
+            polygon = clip_polygon(&polygon, axis, grid_bounding_box.max[axis], true);
+        }
+
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+
+        (1..polygon.len() - 1)
+            .map(|i| Triangle::new(polygon[0], polygon[i], polygon[i + 1]))
+            .collect()
+    }
+}
+
+/// Most times a triangle is split by `SubdividePreprocessor`, so it never gives more than
+/// `4^8` triangles, even if its edges stay longer than the limit.
+pub const MAX_SUBDIVISION_DEPTH: u32 = 8;
+
+/// Splits triangles with any edge longer than `max_edge_length` into four by edge midpoints,
+/// until every edge is short enough or `MAX_SUBDIVISION_DEPTH` is reached. Triangles with
+/// non-finite coordinates are kept as they are.
+pub struct SubdividePreprocessor {
+    pub max_edge_length: f32,
+}
+
+impl SubdividePreprocessor {
+    /// Panics if `max_edge_length` isn't a positive finite number.
+    pub fn new(max_edge_length: f32) -> Self {
+        assert!(
+            max_edge_length.is_finite() && max_edge_length > 0.0,
+            "Max edge length must be positive"
+        );
+
+        SubdividePreprocessor { max_edge_length }
+    }
+}
+
+impl Preprocessor for SubdividePreprocessor {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        _width: u32,
+        _height: u32,
+        _center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        // Non-positive length would never be reached, non-finite edges are never short enough.
+        if self.max_edge_length.is_nan() || self.max_edge_length <= 0.0 || !triangle.is_finite() {
+            return vec![triangle];
+        }
+
+        let mut triangles = Vec::new();
+        let mut stack = vec![(triangle, 0)];
+
+        while let Some((triangle, depth)) = stack.pop() {
+            if depth == MAX_SUBDIVISION_DEPTH
+                || longest_edge_length(&triangle) <= self.max_edge_length
+            {
+                triangles.push(triangle);
+                continue;
+            }
+
+            let ab = (triangle.a + triangle.b) / 2.0;
+            let bc = (triangle.b + triangle.c) / 2.0;
+            let ca = (triangle.c + triangle.a) / 2.0;
+
+            stack.push((Triangle::new(triangle.a, ab, ca), depth + 1));
+            stack.push((Triangle::new(ab, triangle.b, bc), depth + 1));
+            stack.push((Triangle::new(ca, bc, triangle.c), depth + 1));
+            stack.push((Triangle::new(ab, bc, ca), depth + 1));
+        }
+
+        triangles
+    }
+}
+
+/// Flattens triangles nearly parallel to the plane perpendicular to `axis`, so noisy floors
+/// don't spread over two layers. Triangle is nearly parallel if angle between its normal and
+/// the axis is at most `max_angle` radians. Its vertices are moved on the axis to the cell
+/// center nearest to their average.
+pub struct SnapToPlanePreprocessor {
+    pub axis: Axis,
+    pub max_angle: f32,
+}
+
+impl SnapToPlanePreprocessor {
+    pub fn new(axis: Axis, max_angle: f32) -> Self {
+        SnapToPlanePreprocessor { axis, max_angle }
+    }
+}
+
+impl Preprocessor for SnapToPlanePreprocessor {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        _width: u32,
+        _height: u32,
+        _center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        let index = self.axis.index();
+        let normal = triangle.normal();
+        let length = normal.norm();
+
+        // Degenerate triangle has no orientation.
+        if length == 0.0 {
+            return vec![triangle];
+        }
+
+        let cos_angle = normal[index].abs() / length;
+
+        if cos_angle < self.max_angle.cos() {
+            return vec![triangle];
+        }
+
+        // Cells are centered around integer coordinates.
+        let plane = ((triangle.a[index] + triangle.b[index] + triangle.c[index]) / 3.0).round();
+
+        let snap = |mut vector: Vector3<f32>| {
+            vector[index] = plane;
+            vector
+        };
+
+        vec![Triangle::new(
+            snap(triangle.a),
+            snap(triangle.b),
+            snap(triangle.c),
+        )]
+    }
+}
+
+/// Rounds vertex coordinates to given count of decimal places, so meshes differing only by float
+/// noise, for example from different rounding of earlier transforms, give identical grids.
+/// Vertices move by up to half of `10^-decimals`, so cells which triangle only grazes may change,
+/// too few decimals can also collapse small triangles.
+pub struct QuantizePreprocessor {
+    pub decimals: u32,
+}
+
+/// Most decimals kept by `QuantizePreprocessor`, `f32` has no more significant digits anyway
+/// and larger powers of ten overflow.
+pub const MAX_QUANTIZE_DECIMALS: u32 = 9;
+
+impl QuantizePreprocessor {
+    /// Decimals are clamped to `MAX_QUANTIZE_DECIMALS`.
+    pub fn new(decimals: u32) -> Self {
+        QuantizePreprocessor {
+            decimals: decimals.min(MAX_QUANTIZE_DECIMALS),
+        }
+    }
+}
+
+impl Preprocessor for QuantizePreprocessor {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        _width: u32,
+        _height: u32,
+        _center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        let scale = 10f32.powi(self.decimals.min(MAX_QUANTIZE_DECIMALS) as i32);
+
+        // Coordinates too large to scale have no decimals left to round.
+        let quantize = |vector: Vector3<f32>| {
+            vector.map(|c| {
+                let scaled = c * scale;
+
+                if scaled.is_finite() {
+                    scaled.round() / scale
+                } else {
+                    c
+                }
+            })
+        };
+
+        vec![Triangle::new(
+            quantize(triangle.a),
+            quantize(triangle.b),
+            quantize(triangle.c),
+        )]
+    }
+}
+
+/// Keeps only parts of triangles between two planes perpendicular to z, like a single floor
+/// of a building. Unlike `ClipPreprocessor` it ignores the grid, planes are in units of given
+/// triangles and either of them may be left out. Triangles outside of the band are dropped.
+pub struct ZBandPreprocessor {
+    pub min_z: Option<f32>,
+    pub max_z: Option<f32>,
+}
+
+impl ZBandPreprocessor {
+    pub fn new(min_z: Option<f32>, max_z: Option<f32>) -> Self {
+        ZBandPreprocessor { min_z, max_z }
+    }
+}
+
+impl Preprocessor for ZBandPreprocessor {
+    fn pre_process(
+        &self,
+        triangle: Triangle,
+        _width: u32,
+        _height: u32,
+        _center: Vector3<f32>,
+    ) -> Vec<Triangle> {
+        let mut polygon = vec![triangle.a, triangle.b, triangle.c];
+
+        if let Some(min_z) = self.min_z {
+            polygon = clip_polygon(&polygon, 2, min_z, false);
+        }
+
+        if let Some(max_z) = self.max_z {
+            polygon = clip_polygon(&polygon, 2, max_z, true);
+        }
+
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+
+        (1..polygon.len() - 1)
+            .map(|i| Triangle::new(polygon[0], polygon[i], polygon[i + 1]))
+            .collect()
+    }
+}
+
+fn longest_edge_length(triangle: &Triangle) -> f32 {
+    let ab = (triangle.b - triangle.a).norm();
+    let bc = (triangle.c - triangle.b).norm();
+    let ca = (triangle.a - triangle.c).norm();
+
+    ab.max(bc).max(ca)
+}
+
+/// Sutherland–Hodgman clipping of polygon by single axis aligned plane.
+/// Keeps points below the plane if `keep_below` is set, otherwise points above it.
+fn clip_polygon(
+    polygon: &[Vector3<f32>],
+    axis: usize,
+    plane: f32,
+    keep_below: bool,
+) -> Vec<Vector3<f32>> {
+    let is_inside = |point: &Vector3<f32>| {
+        if keep_below {
+            point[axis] <= plane
+        } else {
+            point[axis] >= plane
+        }
+    };
+
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (index, current) in polygon.iter().enumerate() {
+        let previous = &polygon[(index + polygon.len() - 1) % polygon.len()];
+
+        let current_inside = is_inside(current);
+        let previous_inside = is_inside(previous);
+
+        if current_inside != previous_inside {
+            let t = (plane - previous[axis]) / (current[axis] - previous[axis]);
+            let mut intersection = previous + (current - previous) * t;
+
+            // Remove float error, so intersection is exactly on the plane.
+            intersection[axis] = plane;
+            clipped.push(intersection);
+        }
+
+        if current_inside {
+            clipped.push(*current);
+        }
+    }
+
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::{Axis, Triangle, DEFAULT_EPSILON};
+    use crate::preprocessors::{
+        longest_edge_length, ClipPreprocessor, QuantizePreprocessor, SnapToPlanePreprocessor,
+        SubdividePreprocessor, ZBandPreprocessor, MAX_QUANTIZE_DECIMALS, MAX_SUBDIVISION_DEPTH,
+    };
+    use crate::{dedup_obstacles, find_obstacles, Preprocessor};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_clip_preprocessor() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(10.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 4.0, 0.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        // Grid covers cells from -5 to 4, so it ends at 4.5.
+        let triangles = ClipPreprocessor {}.pre_process(triangle, 10, 10, center);
+
+        assert_eq!(triangles.len(), 2);
+
+        assert_eq!(triangles[0].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[0].b, Vector3::new(4.5, 0.0, 0.0));
+        assert_eq!(triangles[0].c, Vector3::new(4.5, 2.2, 0.0));
+
+        assert_eq!(triangles[1].a, Vector3::new(0.0, 0.0, 0.0));
+        assert_eq!(triangles[1].b, Vector3::new(4.5, 2.2, 0.0));
+        assert_eq!(triangles[1].c, Vector3::new(0.0, 4.0, 0.0));
+    }
+
+    #[test]
+    fn test_clip_preprocessor_inside() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(2.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 2.0, 0.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let triangles = ClipPreprocessor {}.pre_process(triangle, 10, 10, center);
+
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].a, a);
+        assert_eq!(triangles[0].b, b);
+        assert_eq!(triangles[0].c, c);
+    }
+
+    #[test]
+    fn test_clip_preprocessor_outside() {
+        let a = Vector3::new(20.0, 0.0, 0.0);
+        let b = Vector3::new(30.0, 0.0, 0.0);
+        let c = Vector3::new(20.0, 4.0, 0.0);
+
+        let triangle = Triangle::new(a, b, c);
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let triangles = ClipPreprocessor {}.pre_process(triangle, 10, 10, center);
+
+        assert!(triangles.is_empty());
+    }
+
+    #[test]
+    fn test_subdivide_preprocessor() {
+        let a = Vector3::new(-40.0, -30.0, -3.0);
+        let b = Vector3::new(40.0, -20.0, 0.0);
+        let c = Vector3::new(0.0, 40.0, 5.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let preprocessor = SubdividePreprocessor::new(10.0);
+
+        let triangles = preprocessor.pre_process(Triangle::new(a, b, c), 100, 20, center);
+
+        assert_eq!(triangles.len(), 256);
+        assert!(triangles.iter().all(|t| longest_edge_length(t) <= 10.0));
+
+        let mut subdivided_obstacles: Vec<_> = triangles
+            .iter()
+            .flat_map(|t| find_obstacles(t, &center, 100, 20, DEFAULT_EPSILON, 0))
+            .collect();
+
+        subdivided_obstacles.sort_by_key(|o| (o.x, o.y, o.z));
+        subdivided_obstacles.dedup();
+
+        let triangle = Triangle::new(a, b, c);
+        let obstacles = find_obstacles(&triangle, &center, 100, 20, DEFAULT_EPSILON, 0);
+
+        assert_eq!(subdivided_obstacles, obstacles);
+    }
+
+    #[test]
+    fn test_subdivide_preprocessor_small_triangle() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(2.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 2.0, 0.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let triangles =
+            SubdividePreprocessor::new(10.0).pre_process(Triangle::new(a, b, c), 10, 10, center);
+
+        assert_eq!(triangles.len(), 1);
+    }
+
+    #[test]
+    fn test_subdivide_preprocessor_limits() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(0.0, 2.0, 0.0),
+            )
+        };
+
+        // Edges never get this short, splitting stops at the depth limit.
+        let triangles =
+            SubdividePreprocessor::new(f32::MIN_POSITIVE).pre_process(triangle(), 10, 10, center);
+
+        assert_eq!(triangles.len(), 4usize.pow(MAX_SUBDIVISION_DEPTH));
+
+        // Length of an edge with NaN is never short enough, triangle is kept as is.
+        let nan = Triangle::new(
+            Vector3::new(f32::NAN, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+        let triangles = SubdividePreprocessor::new(1.0).pre_process(nan, 10, 10, center);
+
+        assert_eq!(triangles.len(), 1);
+        assert!(triangles[0].a.x.is_nan());
+
+        let preprocessor = SubdividePreprocessor {
+            max_edge_length: 0.0,
+        };
+
+        assert_eq!(
+            preprocessor.pre_process(triangle(), 10, 10, center).len(),
+            1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Max edge length must be positive")]
+    fn test_subdivide_preprocessor_zero_length() {
+        SubdividePreprocessor::new(0.0);
+    }
+
+    #[test]
+    fn test_snap_to_plane_preprocessor() {
+        let a = Vector3::new(-4.0, -4.0, 0.3);
+        let b = Vector3::new(4.0, -4.0, 0.7);
+        let c = Vector3::new(0.0, 4.0, 0.5);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let preprocessor = SnapToPlanePreprocessor::new(Axis::Z, 5f32.to_radians());
+
+        let tilted_obstacles =
+            find_obstacles(&Triangle::new(a, b, c), &center, 20, 20, DEFAULT_EPSILON, 0);
+        assert!(tilted_obstacles
+            .iter()
+            .any(|o| o.z != tilted_obstacles[0].z));
+
+        let triangles = preprocessor.pre_process(Triangle::new(a, b, c), 20, 20, center);
+
+        assert_eq!(triangles.len(), 1);
+        assert_eq!(triangles[0].a, Vector3::new(-4.0, -4.0, 1.0));
+        assert_eq!(triangles[0].b, Vector3::new(4.0, -4.0, 1.0));
+        assert_eq!(triangles[0].c, Vector3::new(0.0, 4.0, 1.0));
+
+        let obstacles = find_obstacles(&triangles[0], &center, 20, 20, DEFAULT_EPSILON, 0);
+
+        assert!(!obstacles.is_empty());
+        assert!(obstacles.iter().all(|o| o.z == 11));
+    }
+
+    #[test]
+    fn test_snap_to_plane_preprocessor_steep() {
+        let a = Vector3::new(0.0, 0.0, 0.0);
+        let b = Vector3::new(4.0, 0.0, 0.0);
+        let c = Vector3::new(0.0, 0.0, 4.0);
+
+        let preprocessor = SnapToPlanePreprocessor::new(Axis::Z, 5f32.to_radians());
+        let triangles =
+            preprocessor.pre_process(Triangle::new(a, b, c), 20, 20, Vector3::new(0.0, 0.0, 0.0));
+
+        assert_eq!(triangles[0].c, c);
+    }
+
+    #[test]
+    fn test_quantize_preprocessor() {
+        let a = Vector3::new(0.25, 1.5, -3.0);
+        let b = Vector3::new(7.12, 0.5, 2.0);
+        let c = Vector3::new(-4.0, 6.49, 0.5);
+
+        let noise = Vector3::new(3e-4, -2e-4, 1e-4);
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let preprocessor = QuantizePreprocessor::new(2);
+
+        let triangles = preprocessor.pre_process(Triangle::new(a, b, c), 20, 20, center);
+        let noisy_triangles = preprocessor.pre_process(
+            Triangle::new(a + noise, b - noise, c + noise),
+            20,
+            20,
+            center,
+        );
+
+        let bits = |triangle: &Triangle| {
+            [triangle.a, triangle.b, triangle.c]
+                .iter()
+                .flat_map(|v| v.iter().map(|c| c.to_bits()).collect::<Vec<u32>>())
+                .collect::<Vec<u32>>()
+        };
+
+        assert_eq!(bits(&noisy_triangles[0]), bits(&triangles[0]));
+
+        let obstacles = find_obstacles(&triangles[0], &center, 20, 20, DEFAULT_EPSILON, 0);
+        let noisy_obstacles =
+            find_obstacles(&noisy_triangles[0], &center, 20, 20, DEFAULT_EPSILON, 0);
+
+        assert!(!obstacles.is_empty());
+        assert_eq!(noisy_obstacles, obstacles);
+    }
+
+    #[test]
+    fn test_quantize_preprocessor_limits() {
+        let preprocessor = QuantizePreprocessor::new(40);
+        assert_eq!(preprocessor.decimals, MAX_QUANTIZE_DECIMALS);
+
+        let a = Vector3::new(1e30, 0.5, -3e35);
+        let b = Vector3::new(0.123_456_79, 0.0, 0.0);
+        let c = Vector3::new(0.0, 2.0, 0.0);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        // Field set directly isn't clamped by the constructor.
+        for preprocessor in &[preprocessor, QuantizePreprocessor { decimals: 40 }] {
+            let triangles = preprocessor.pre_process(Triangle::new(a, b, c), 20, 20, center);
+
+            assert!(triangles[0].is_finite());
+            assert_eq!(triangles[0].a, a);
+        }
+    }
+
+    #[test]
+    fn test_z_band_preprocessor() {
+        let floor = |z: f32| {
+            Triangle::new(
+                Vector3::new(-4.0, -4.0, z),
+                Vector3::new(4.0, -4.0, z),
+                Vector3::new(-4.0, 4.0, z),
+            )
+        };
+        let stairs = || {
+            Triangle::new(
+                Vector3::new(-4.0, 0.0, -6.0),
+                Vector3::new(4.0, 0.0, -6.0),
+                Vector3::new(0.0, 0.0, 6.0),
+            )
+        };
+
+        let band = ZBandPreprocessor::new(Some(-2.0), Some(2.0));
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        assert!(band.pre_process(floor(-5.0), 16, 16, center).is_empty());
+        assert!(band.pre_process(floor(5.0), 16, 16, center).is_empty());
+        assert_eq!(band.pre_process(floor(0.0), 16, 16, center).len(), 1);
+
+        let clipped = band.pre_process(stairs(), 16, 16, center);
+
+        assert!(!clipped.is_empty());
+        assert!(clipped
+            .iter()
+            .flat_map(|t| vec![t.a, t.b, t.c])
+            .all(|v| v.z >= -2.0 && v.z <= 2.0));
+
+        // Only cells of the band from z -2 to 2 are voxelized, layers 6 to 10 of the grid.
+        let obstacles: Vec<_> = vec![floor(-5.0), floor(0.0), floor(5.0), stairs()]
+            .into_iter()
+            .flat_map(|t| band.pre_process(t, 16, 16, center))
+            .flat_map(|t| find_obstacles(&t, &center, 16, 16, DEFAULT_EPSILON, 0))
+            .collect();
+        let obstacles = dedup_obstacles(obstacles);
+
+        assert!(obstacles.iter().all(|o| o.z >= 6 && o.z <= 10));
+        assert!(obstacles.iter().any(|o| o.z == 6));
+        assert!(obstacles.iter().any(|o| o.z == 10));
+        assert!(obstacles.iter().any(|o| o.z == 8 && o.y != 8));
+
+        // Band open on one side keeps everything above its only plane.
+        let above = ZBandPreprocessor::new(Some(1.0), None);
+
+        assert!(above.pre_process(floor(-5.0), 16, 16, center).is_empty());
+        assert_eq!(above.pre_process(floor(5.0), 16, 16, center).len(), 1);
+    }
+}
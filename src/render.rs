@@ -0,0 +1,292 @@
+use crate::occupancy::OccupancyGrid;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const OBSTACLE: u8 = 0;
+const FREE: u8 = 255;
+const BACKGROUND: u8 = 160;
+
+/// Space between tiles of a contact sheet and around them, in pixels.
+const GAP: u32 = 2;
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Height of the strip with layer index above every tile.
+const LABEL_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+
+/// Largest contact sheet rendered without downscaling, 4096x4096 pixels.
+pub const CONTACT_SHEET_MAX_PIXELS: u64 = 4096 * 4096;
+
+/// Rows of digits 0 to 9, three bits per row with the leftmost pixel in the highest bit.
+const DIGITS: [[u8; GLYPH_HEIGHT as usize]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// 8-bit grayscale image, pixels are stored row by row.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, value: u8) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![value; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> u8 {
+        self.pixels[self.index(x, y)]
+    }
+
+    /// Pixels outside of the image are ignored.
+    pub fn set_pixel(&mut self, x: u32, y: u32, value: u8) {
+        if x < self.width && y < self.height {
+            let index = self.index(x, y);
+            self.pixels[index] = value;
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        x as usize + y as usize * self.width as usize
+    }
+
+    /// Copies other image, so its top left corner is at given position.
+    fn draw(&mut self, other: &Image, left: u32, top: u32) {
+        for y in 0..other.height {
+            for x in 0..other.width {
+                self.set_pixel(left + x, top + y, other.pixel(x, y));
+            }
+        }
+    }
+
+    fn draw_number(&mut self, number: u32, left: u32, top: u32) {
+        for (position, digit) in number.to_string().bytes().enumerate() {
+            let glyph = &DIGITS[(digit - b'0') as usize];
+            let glyph_left = left + position as u32 * (GLYPH_WIDTH + 1);
+
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        self.set_pixel(glyph_left + x, top + y as u32, OBSTACLE);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Renders layer `z` of the grid, x grows to the right and y down. Every pixel covers
+/// `step` x `step` cells and is black if any of them is an obstacle, so thin walls stay visible.
+pub fn render_slice(grid: &OccupancyGrid, z: u32, step: u32) -> Image {
+    let step = step.max(1);
+    let size = (grid.width() + step - 1) / step;
+
+    let mut image = Image::new(size, size, FREE);
+
+    for x in 0..grid.width() {
+        for y in 0..grid.width() {
+            if grid.is_obstacle(x, y, z) {
+                image.set_pixel(x / step, y / step, OBSTACLE);
+            }
+        }
+    }
+
+    image
+}
+
+/// Tiles all layers of the grid into a single image with layer index above every layer.
+/// Layers are laid out in rows of `ceil(sqrt(height))` starting from the top left.
+/// Layers are downscaled just enough for the image to have at most `max_pixels` pixels,
+/// unless labels alone are bigger.
+pub fn contact_sheet(grid: &OccupancyGrid, max_pixels: u64) -> Image {
+    let layers = grid.height().max(1);
+    let columns = (layers as f64).sqrt().ceil() as u32;
+    let rows = (layers + columns - 1) / columns;
+
+    let digits = (layers - 1).to_string().len() as u32;
+    let label_width = digits * (GLYPH_WIDTH + 1) - 1;
+
+    let sheet_size = |step: u32| {
+        let slice_size = (grid.width() + step - 1) / step;
+        let tile_width = slice_size.max(label_width);
+        let tile_height = slice_size + LABEL_HEIGHT;
+
+        (
+            columns * tile_width + (columns + 1) * GAP,
+            rows * tile_height + (rows + 1) * GAP,
+        )
+    };
+
+    let mut step = 1;
+
+    loop {
+        let (width, height) = sheet_size(step);
+
+        if width as u64 * height as u64 <= max_pixels || step >= grid.width() {
+            break;
+        }
+
+        step += 1;
+    }
+
+    let (width, height) = sheet_size(step);
+    let slice_size = (grid.width() + step - 1) / step;
+    let tile_width = slice_size.max(label_width);
+    let tile_height = slice_size + LABEL_HEIGHT;
+
+    let mut sheet = Image::new(width, height, BACKGROUND);
+
+    for z in 0..grid.height() {
+        let left = GAP + (z % columns) * (tile_width + GAP);
+        let top = GAP + (z / columns) * (tile_height + GAP);
+
+        sheet.draw(&Image::new(tile_width, LABEL_HEIGHT, FREE), left, top);
+        sheet.draw_number(z, left, top + 1);
+        sheet.draw(&render_slice(grid, z, step), left, top + LABEL_HEIGHT);
+    }
+
+    sheet
+}
+
+/// Writes image as grayscale PNG.
+pub fn write_png<W: Write>(image: &Image, writer: &mut W) -> io::Result<()> {
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&image.width.to_be_bytes());
+    header.extend_from_slice(&image.height.to_be_bytes());
+    // Bit depth 8, grayscale, deflate, adaptive filtering, no interlace.
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    write_chunk(writer, b"IHDR", &header)?;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+    for row in image.pixels.chunks(image.width.max(1) as usize) {
+        // Every row starts with filter type, rows aren't filtered.
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+
+    write_chunk(writer, b"IDAT", &encoder.finish()?)?;
+    write_chunk(writer, b"IEND", &[])
+}
+
+pub fn save_png<P: AsRef<Path>>(image: &Image, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write_png(image, &mut writer)?;
+    writer.flush()
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    writer.write_all(&crc.to_be_bytes())
+}
+
+/// CRC-32 used by PNG chunks, computed bit by bit, images are small enough to not need a table.
+fn crc32<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::occupancy::OccupancyGrid;
+    use crate::render::{contact_sheet, crc32, render_slice, write_png, OBSTACLE};
+
+    #[test]
+    fn test_render_slice() {
+        let mut grid = OccupancyGrid::new(4, 2);
+        grid.set_obstacle(3, 1, 1);
+
+        let image = render_slice(&grid, 1, 1);
+
+        assert_eq!((image.width(), image.height()), (4, 4));
+        assert_eq!(image.pixel(3, 1), OBSTACLE);
+        assert_ne!(image.pixel(1, 3), OBSTACLE);
+
+        // Downscaled pixel is an obstacle if any of its cells is.
+        let image = render_slice(&grid, 1, 3);
+
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.pixel(1, 0), OBSTACLE);
+        assert_ne!(image.pixel(0, 0), OBSTACLE);
+    }
+
+    #[test]
+    fn test_contact_sheet() {
+        let mut grid = OccupancyGrid::new(10, 3);
+        grid.set_obstacle(0, 0, 2);
+
+        // Three layers in two rows of two tiles, every tile is 10 pixels wide and 17 high.
+        let sheet = contact_sheet(&grid, u64::MAX);
+
+        assert_eq!((sheet.width(), sheet.height()), (26, 40));
+
+        // The last layer is the first tile of the second row, below its label.
+        assert_eq!(sheet.pixel(2, 21 + 7), OBSTACLE);
+
+        // Downscaled by half to fit, tiles are 5 pixels wide and 12 high.
+        let sheet = contact_sheet(&grid, 500);
+
+        assert_eq!((sheet.width(), sheet.height()), (16, 30));
+    }
+
+    #[test]
+    fn test_write_png() {
+        let sheet = contact_sheet(&OccupancyGrid::new(10, 3), u64::MAX);
+
+        let mut png = Vec::new();
+        write_png(&sheet, &mut png).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..20], &26u32.to_be_bytes());
+        assert_eq!(&png[20..24], &40u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+    }
+}
@@ -0,0 +1,223 @@
+use flying_pathfinding::Grid;
+
+/// Weight of obstacles set without one, weight 0 is a free cell.
+pub const DEFAULT_WEIGHT: u8 = 1;
+
+/// Grid backend filled by conversion. Every grid is filled through it, including `Grid` from
+/// `convert` and `create_grid`, only `export_grid` still calls `flying_pathfinding` directly.
+/// Implement it to convert into another backend with `convert_into`.
+pub trait GridSink {
+    /// Empty grid with cells from 0 to width - 1 on x and y axes and to height - 1 on z axis.
+    fn new(width: u32, height: u32) -> Self
+    where
+        Self: Sized;
+
+    /// Called only for cells inside the grid, at most once for every cell.
+    fn set_obstacle(&mut self, x: u32, y: u32, z: u32);
+
+    /// Same as `set_obstacle`, but with cost of the cell, like from its material or tag.
+    /// Binary backends ignore the weight.
+    fn set_weighted_obstacle(&mut self, x: u32, y: u32, z: u32, _weight: u8) {
+        self.set_obstacle(x, y, z)
+    }
+}
+
+impl GridSink for Grid {
+    fn new(width: u32, height: u32) -> Self {
+        Grid::new(width, height)
+    }
+
+    fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
+        Grid::set_obstacle(self, x, y, z)
+    }
+}
+
+/// Grid keeping weight of every obstacle, free cells have weight 0.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WeightedGrid {
+    width: u32,
+    height: u32,
+    weights: Vec<u8>,
+}
+
+impl WeightedGrid {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn weight(&self, x: u32, y: u32, z: u32) -> u8 {
+        self.weights[self.index(x, y, z)]
+    }
+
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        let width = self.width as usize;
+        x as usize + y as usize * width + z as usize * width * width
+    }
+}
+
+impl GridSink for WeightedGrid {
+    fn new(width: u32, height: u32) -> Self {
+        let length = width as usize * width as usize * height as usize;
+
+        WeightedGrid {
+            width,
+            height,
+            weights: vec![0; length],
+        }
+    }
+
+    fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
+        self.set_weighted_obstacle(x, y, z, DEFAULT_WEIGHT);
+    }
+
+    /// Weight 0 would make the cell free, so it is stored as 1.
+    fn set_weighted_obstacle(&mut self, x: u32, y: u32, z: u32, weight: u8) {
+        let index = self.index(x, y, z);
+        self.weights[index] = weight.max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{colored_triangles, load_vertex_colors, voxelize_tagged, Color};
+    use crate::connectivity::remove_isolated;
+    use crate::geometry::Triangle;
+    use crate::occupancy::OccupancyGrid;
+    use crate::sink::{GridSink, WeightedGrid, DEFAULT_WEIGHT};
+    use crate::testutil::NoOpProgress;
+    use crate::{
+        convert_into, create_weighted_sink_checked, voxelize_occupancy, ConvertOptions,
+        NoOpPreprocessor,
+    };
+    use nalgebra::Vector3;
+    use obj::Obj;
+
+    struct RecordingSink {
+        dimensions: (u32, u32),
+        cells: Vec<(u32, u32, u32)>,
+        weights: Vec<u8>,
+    }
+
+    impl GridSink for RecordingSink {
+        fn new(width: u32, height: u32) -> Self {
+            RecordingSink {
+                dimensions: (width, height),
+                cells: Vec::new(),
+                weights: Vec::new(),
+            }
+        }
+
+        fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
+            self.set_weighted_obstacle(x, y, z, DEFAULT_WEIGHT);
+        }
+
+        fn set_weighted_obstacle(&mut self, x: u32, y: u32, z: u32, weight: u8) {
+            self.cells.push((x, y, z));
+            self.weights.push(weight);
+        }
+    }
+
+    #[test]
+    fn test_convert_into() {
+        let triangles = || {
+            vec![Triangle::new(
+                Vector3::new(-3.0, -3.0, 1.0),
+                Vector3::new(3.0, -3.0, 1.0),
+                Vector3::new(0.0, 3.0, 1.0),
+            )]
+        };
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        let sink: RecordingSink = convert_into(
+            triangles(),
+            center,
+            10,
+            4,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        let expected = voxelize_occupancy(
+            triangles(),
+            center,
+            10,
+            4,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+        let expected: Vec<_> = expected
+            .obstacles()
+            .into_iter()
+            .map(|o| (o.x, o.y, o.z))
+            .collect();
+
+        assert_eq!(sink.dimensions, (10, 4));
+        assert!(!sink.cells.is_empty());
+        assert_eq!(sink.cells, expected);
+        assert!(sink.weights.iter().all(|w| *w == DEFAULT_WEIGHT));
+    }
+
+    #[test]
+    fn test_cleaned_to_sink() {
+        let mut grid = OccupancyGrid::new(8, 4);
+
+        for x in 1..4 {
+            grid.set_obstacle(x, 2, 1);
+        }
+
+        grid.set_obstacle(6, 6, 3);
+
+        assert_eq!(remove_isolated(&mut grid, 1), 1);
+
+        let sink: RecordingSink = grid.to_sink();
+
+        assert_eq!(sink.dimensions, (8, 4));
+        assert_eq!(sink.cells, vec![(1, 2, 1), (2, 2, 1), (3, 2, 1)]);
+        assert!(sink.weights.iter().all(|w| *w == DEFAULT_WEIGHT));
+    }
+
+    #[test]
+    fn test_weighted_sink() {
+        let obj = Obj::load("tests/colored.obj").unwrap();
+        let colors = load_vertex_colors("tests/colored.obj").unwrap();
+        let triangles = colored_triangles(&obj, &colors);
+
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let tagged = voxelize_tagged(&triangles, center, 16, 16, &ConvertOptions::default());
+
+        // Red cells are cheap to pass, blue ones expensive.
+        let weight = |color: &Color| if color.r > 0.5 { 10 } else { 200 };
+        let mut obstacles: Vec<_> = tagged.iter().map(|(o, c)| (*o, weight(c))).collect();
+
+        // Same cell listed again with lower weight keeps the higher one.
+        obstacles.push((tagged[0].0, 1));
+
+        let (sink, clamped) = create_weighted_sink_checked::<RecordingSink>(&obstacles, 16, 16);
+
+        assert_eq!(clamped, 0);
+        assert_eq!(sink.cells.len(), tagged.len());
+
+        for ((x, y, z), weight) in sink.cells.iter().zip(&sink.weights) {
+            match z {
+                8 => assert_eq!(*weight, 10),
+                12 => assert_eq!(*weight, 200),
+                _ => panic!("Unexpected cell {:?}", (x, y, z)),
+            }
+        }
+
+        let (grid, _) = create_weighted_sink_checked::<WeightedGrid>(&obstacles, 16, 16);
+        let (x, y, z) = sink.cells[0];
+
+        assert_eq!(grid.weight(x, y, z), 10);
+        assert_eq!(grid.weight(0, 0, 0), 0);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::geometry::{BoundingBox, Triangle};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Time spent in every conversion phase.
+/// Phases which weren't run, like parsing for already parsed triangles, stay zero.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    pub parse: Duration,
+    pub voxelize: Duration,
+    pub dedup: Duration,
+    pub populate: Duration,
+    pub export: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.parse + self.voxelize + self.dedup + self.populate + self.export
+    }
+}
+
+/// Triangles which didn't mark any cell, by reason, see `empty_triangles`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct EmptyTriangles {
+    /// Triangles without area, with repeated or collinear vertices.
+    pub degenerate: usize,
+    /// Triangles which lie entirely outside of the grid.
+    pub outside: usize,
+    /// Triangles with all edges shorter than a cell.
+    pub too_small: usize,
+    /// Larger triangles which passed between cells, like slivers in center-sample mode.
+    pub grazing: usize,
+}
+
+impl EmptyTriangles {
+    pub fn total(&self) -> usize {
+        self.degenerate + self.outside + self.too_small + self.grazing
+    }
+}
+
+/// `EmptyTriangles` counted by all threads of a conversion, see
+/// `ConvertOptions::empty_triangles`.
+#[derive(Debug, Default)]
+pub struct EmptyTriangleCounter {
+    degenerate: AtomicUsize,
+    outside: AtomicUsize,
+    too_small: AtomicUsize,
+    grazing: AtomicUsize,
+}
+
+impl EmptyTriangleCounter {
+    /// Counts triangle in cell units, which didn't mark any cell, by reason.
+    pub(crate) fn add(&self, triangle: &Triangle, grid_bounding_box: &BoundingBox) {
+        // Unlike `Triangle::bounding_box`, exact box without padding.
+        let min = triangle
+            .a
+            .zip_map(&triangle.b, f32::min)
+            .zip_map(&triangle.c, f32::min);
+        let max = triangle
+            .a
+            .zip_map(&triangle.b, f32::max)
+            .zip_map(&triangle.c, f32::max);
+
+        let longest_edge = (triangle.b - triangle.a)
+            .norm()
+            .max((triangle.c - triangle.b).norm())
+            .max((triangle.a - triangle.c).norm());
+
+        let count = if triangle.dominant_axis().is_none() {
+            &self.degenerate
+        } else if !grid_bounding_box.intersects(&BoundingBox::new(min, max)) {
+            &self.outside
+        } else if longest_edge < 1.0 {
+            &self.too_small
+        } else {
+            &self.grazing
+        };
+
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> EmptyTriangles {
+        EmptyTriangles {
+            degenerate: self.degenerate.load(Ordering::Relaxed),
+            outside: self.outside.load(Ordering::Relaxed),
+            too_small: self.too_small.load(Ordering::Relaxed),
+            grazing: self.grazing.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ConversionStats {
+    pub triangles: usize,
+    /// Count of unique obstacles set in the grid.
+    pub obstacles: usize,
+    /// Count of obstacles outside of the grid moved to its last cells, see `create_grid_checked`.
+    pub clamped_obstacles: usize,
+    pub empty_triangles: EmptyTriangles,
+    /// Count of cells tested against triangles, `None` if they weren't counted,
+    /// see `ConvertOptions::inside_tests`.
+    pub inside_tests: Option<u64>,
+    pub timings: Timings,
+}
+
+/// Runs function and returns its result together with elapsed time.
+pub fn timed<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+
+    (result, start.elapsed())
+}
@@ -0,0 +1,410 @@
+use crate::error::ConvertError;
+use crate::geometry::{LocalVector, Triangle};
+use crate::{
+    check_grid_size, find_obstacles_with_options, ConvertOptions, Preprocessor, Progress,
+    ProgressReporter,
+};
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// Walkable floor of the mesh, at most one cell per column: the highest cell of triangles
+/// facing up, which normals are at most `max_slope` radians away from +Z. Triangles facing
+/// down, like ceilings or back faces, walls and slopes too steep to climb mark nothing, so
+/// unlike `voxelize` the result is a sparse surface a character can stand on. Slope of
+/// `FRAC_PI_2` keeps every triangle facing up. Cells are sorted like in `dedup_obstacles`,
+/// triangles are in grid units like in `voxelize`. Slope is measured before triangles are
+/// scaled to cells, so cells taller or wider than a unit don't change it. Fails like `voxelize`
+/// if the grid is too large.
+#[allow(clippy::too_many_arguments)]
+pub fn walkable_surface<Prg: Progress, Pre: Preprocessor>(
+    triangles: Vec<Triangle>,
+    center: Vector3<f32>,
+    width: u32,
+    height: u32,
+    max_slope: f32,
+    progress: Prg,
+    preprocessor: Pre,
+    options: &ConvertOptions,
+) -> Result<Vec<LocalVector>, ConvertError> {
+    check_grid_size(width, height, options.max_cells)?;
+
+    let center = options.grid_center(center, width, height);
+    let min_cos = max_slope.cos();
+
+    let weight = options.progress_weight;
+    let total = triangles
+        .iter()
+        .map(|t| weight.work(&options.to_cells(t), width, height))
+        .sum();
+    let mut processed = 0;
+    let mut reporter = ProgressReporter::new(progress, total);
+
+    // Highest floor cell of every (x, y) column.
+    let mut columns: HashMap<(u32, u32), u32> = HashMap::new();
+
+    for triangle in &triangles {
+        let cells_triangle = options.to_cells(triangle);
+        processed += weight.work(&cells_triangle, width, height);
+
+        // Parts made by preprocessors lie in the plane of their triangle, so they have its slope.
+        if faces_up(triangle, min_cos) {
+            let parts = preprocessor.pre_process(cells_triangle, width, height, center);
+
+            for part in &parts {
+                for cell in find_obstacles_with_options(part, &center, width, height, options) {
+                    let z = columns.entry((cell.x, cell.y)).or_insert(cell.z);
+                    *z = (*z).max(cell.z);
+                }
+            }
+        }
+
+        reporter.update(processed);
+    }
+
+    reporter.finish();
+
+    let mut surface: Vec<LocalVector> = columns
+        .into_iter()
+        .map(|((x, y), z)| LocalVector::new(x, y, z))
+        .collect();
+
+    surface.sort();
+
+    Ok(surface)
+}
+
+/// Checks if agent can walk between floor cells of adjacent columns, climbing or descending
+/// at most `step_height` cells. Columns sharing only a corner aren't adjacent.
+pub fn can_step(from: &LocalVector, to: &LocalVector, step_height: u32) -> bool {
+    let distance = |a: u32, b: u32| (i64::from(a) - i64::from(b)).abs();
+
+    distance(from.x, to.x) + distance(from.y, to.y) == 1
+        && distance(from.z, to.z) <= i64::from(step_height)
+}
+
+/// Floor cells of `walkable_surface` next to a floor which can't be reached by a step, like
+/// edges of cliffs and bottoms of walls. Borders of the surface without any floor next to
+/// them aren't ledges.
+pub fn ledge_cells(surface: &[LocalVector], step_height: u32) -> Vec<LocalVector> {
+    let columns: HashMap<(u32, u32), u32> = surface.iter().map(|c| ((c.x, c.y), c.z)).collect();
+
+    let neighbors = |cell: &LocalVector| {
+        let offsets: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+        offsets
+            .iter()
+            .filter_map(|(dx, dy)| {
+                let x = u32::try_from(i64::from(cell.x) + dx).ok()?;
+                let y = u32::try_from(i64::from(cell.y) + dy).ok()?;
+
+                columns.get(&(x, y)).map(|&z| LocalVector::new(x, y, z))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    surface
+        .iter()
+        .filter(|cell| {
+            neighbors(cell)
+                .iter()
+                .any(|neighbor| !can_step(cell, neighbor, step_height))
+        })
+        .copied()
+        .collect()
+}
+
+fn faces_up(triangle: &Triangle, min_cos: f32) -> bool {
+    let normal = triangle.normal();
+    let length = normal.norm();
+
+    // Vertical walls are never walkable, even though cosine of right angle rounds below zero.
+    normal.z > 0.0 && normal.z / length >= min_cos
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ConvertError;
+    use crate::geometry::LocalVector;
+    use crate::geometry::Triangle;
+    use crate::surface::{can_step, ledge_cells, walkable_surface};
+    use crate::testutil::NoOpProgress;
+    use crate::{find_obstacles_with_options, ChannelProgress, ConvertOptions, NoOpPreprocessor};
+    use nalgebra::Vector3;
+    use std::collections::HashSet;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_walkable_surface() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+        let max_slope = 45f32.to_radians();
+
+        // Terrain rising by one cell every four cells along x, thick enough in voxels to take
+        // more than one cell of some columns.
+        let slope = || {
+            Triangle::new(
+                Vector3::new(-6.0, -6.0, -2.0),
+                Vector3::new(6.0, -6.0, 1.0),
+                Vector3::new(6.0, 6.0, 1.0),
+            )
+        };
+
+        let surface = walkable_surface(
+            vec![slope()],
+            center,
+            16,
+            16,
+            max_slope,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert!(!surface.is_empty());
+
+        let columns: HashSet<_> = surface.iter().map(|c| (c.x, c.y)).collect();
+        assert_eq!(columns.len(), surface.len());
+
+        // Floor cell is the highest cell of its column.
+        let voxels = find_obstacles_with_options(&slope(), &center, 16, 16, &options);
+
+        let voxel_columns: HashSet<_> = voxels.iter().map(|c| (c.x, c.y)).collect();
+
+        assert!(voxels.len() > surface.len());
+        assert_eq!(voxel_columns, columns);
+
+        for cell in &surface {
+            assert!(voxels
+                .iter()
+                .filter(|v| v.x == cell.x && v.y == cell.y)
+                .all(|v| v.z <= cell.z));
+        }
+
+        // Back face of the slope points down, so it isn't walkable.
+        let flipped = Triangle::new(
+            Vector3::new(-6.0, -6.0, -2.0),
+            Vector3::new(6.0, 6.0, 1.0),
+            Vector3::new(6.0, -6.0, 1.0),
+        );
+        let wall = Triangle::new(
+            Vector3::new(-6.0, 0.0, -4.0),
+            Vector3::new(6.0, 0.0, -4.0),
+            Vector3::new(0.0, 0.0, 4.0),
+        );
+
+        let surface = walkable_surface(
+            vec![flipped, wall],
+            center,
+            16,
+            16,
+            max_slope,
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        )
+        .unwrap();
+
+        assert!(surface.is_empty());
+    }
+
+    #[test]
+    fn test_ledge_cells() {
+        // Floor of two flat areas, x 0 to 2 at z 1 and x 3 to 4 at z 4, and a step up
+        // to z 2 at x 0, y 1.
+        let mut surface = Vec::new();
+
+        for x in 0..5 {
+            for y in 0..2 {
+                let z = match (x, y) {
+                    (0, 1) => 2,
+                    (x, _) if x < 3 => 1,
+                    _ => 4,
+                };
+
+                surface.push(LocalVector::new(x, y, z));
+            }
+        }
+
+        let low = LocalVector::new(2, 0, 1);
+        let high = LocalVector::new(3, 0, 4);
+
+        assert!(!can_step(&low, &high, 2));
+        assert!(can_step(&low, &high, 3));
+        assert!(can_step(
+            &LocalVector::new(0, 0, 1),
+            &LocalVector::new(0, 1, 2),
+            1
+        ));
+        assert!(!can_step(
+            &LocalVector::new(0, 0, 1),
+            &LocalVector::new(1, 1, 1),
+            1
+        ));
+
+        // Height difference of 3 is a ledge for step of 2, difference of 1 is walkable.
+        let ledges = ledge_cells(&surface, 2);
+
+        assert_eq!(
+            ledges,
+            vec![
+                LocalVector::new(2, 0, 1),
+                LocalVector::new(2, 1, 1),
+                LocalVector::new(3, 0, 4),
+                LocalVector::new(3, 1, 4),
+            ]
+        );
+
+        assert!(ledge_cells(&surface, 3).is_empty());
+        assert_eq!(ledge_cells(&surface, 0).len(), 4 + 3);
+    }
+
+    #[test]
+    fn test_max_slope() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let options = ConvertOptions::default();
+
+        // About 14 degrees steep on x from -6 to 6, the other one about 63 degrees on y
+        // from 0 to 6.
+        let gentle = || {
+            Triangle::new(
+                Vector3::new(-6.0, -6.0, -3.0),
+                Vector3::new(6.0, -6.0, 0.0),
+                Vector3::new(-6.0, -1.0, -3.0),
+            )
+        };
+        let steep = || {
+            Triangle::new(
+                Vector3::new(-6.0, 1.0, -6.0),
+                Vector3::new(6.0, 1.0, -6.0),
+                Vector3::new(0.0, 4.0, 0.0),
+            )
+        };
+
+        let surface = |max_slope: f32| {
+            walkable_surface(
+                vec![gentle(), steep()],
+                center,
+                16,
+                16,
+                max_slope.to_radians(),
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                &options,
+            )
+            .unwrap()
+        };
+
+        let gentle_cells = find_obstacles_with_options(&gentle(), &center, 16, 16, &options);
+        let gentle_columns: HashSet<_> = gentle_cells.iter().map(|c| (c.x, c.y)).collect();
+
+        let walkable = surface(30.0);
+        let columns: HashSet<_> = walkable.iter().map(|c| (c.x, c.y)).collect();
+
+        assert_eq!(columns, gentle_columns);
+
+        // Steep triangle is walkable when the limit allows it.
+        assert!(surface(70.0).len() > walkable.len());
+        assert!(surface(10.0).is_empty());
+    }
+
+    #[test]
+    fn test_walkable_surface_cell_size() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+
+        let surface = |triangle: Triangle, options: &ConvertOptions| {
+            walkable_surface(
+                vec![triangle],
+                center,
+                16,
+                16,
+                45f32.to_radians(),
+                NoOpProgress {},
+                NoOpPreprocessor {},
+                options,
+            )
+            .unwrap()
+        };
+
+        // About 30 degrees steep, but 49 degrees in cells twice as wide as tall.
+        let gentle = Triangle::new(
+            Vector3::new(-6.0, -6.0, 0.0),
+            Vector3::new(6.0, -6.0, 7.0),
+            Vector3::new(6.0, 6.0, 7.0),
+        );
+        let wide_cells = ConvertOptions {
+            cell_size_xy: 2.0,
+            ..ConvertOptions::default()
+        };
+
+        assert!(!surface(gentle, &wide_cells).is_empty());
+
+        // About 63 degrees steep, but 34 degrees in cells three times taller than wide.
+        let steep = Triangle::new(
+            Vector3::new(-6.0, -6.0, -12.0),
+            Vector3::new(6.0, -6.0, 12.0),
+            Vector3::new(6.0, 6.0, 12.0),
+        );
+        let tall_cells = ConvertOptions {
+            cell_size_z: 3.0,
+            ..ConvertOptions::default()
+        };
+
+        assert!(surface(steep, &tall_cells).is_empty());
+    }
+
+    #[test]
+    fn test_walkable_surface_limits() {
+        let triangle = || {
+            Triangle::new(
+                Vector3::new(-6.0, -6.0, 0.0),
+                Vector3::new(6.0, -6.0, 0.0),
+                Vector3::new(6.0, 6.0, 0.0),
+            )
+        };
+        let (sender, receiver) = channel();
+
+        let surface = walkable_surface(
+            vec![triangle()],
+            Vector3::new(0.0, 0.0, 0.0),
+            16,
+            16,
+            45f32.to_radians(),
+            ChannelProgress::new(sender),
+            NoOpPreprocessor {},
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+
+        let reported: Vec<f32> = receiver.iter().map(|info| info.percent).collect();
+
+        assert!(!surface.is_empty());
+        assert_eq!(reported.last(), Some(&100.0));
+
+        let options = ConvertOptions {
+            max_cells: 15 * 15 * 15,
+            ..ConvertOptions::default()
+        };
+
+        let surface = walkable_surface(
+            vec![triangle()],
+            Vector3::new(0.0, 0.0, 0.0),
+            16,
+            16,
+            45f32.to_radians(),
+            NoOpProgress {},
+            NoOpPreprocessor {},
+            &options,
+        );
+
+        assert_eq!(
+            surface,
+            Err(ConvertError::GridTooLarge {
+                width: 16,
+                height: 16,
+                max_cells: 15 * 15 * 15,
+            })
+        );
+    }
+}
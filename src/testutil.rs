@@ -0,0 +1,203 @@
+use crate::geometry::Triangle;
+use crate::Progress;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Progress which ignores all updates.
+pub struct NoOpProgress {}
+
+impl Progress for NoOpProgress {
+    fn update_progress(&self, _percent: f32) {}
+}
+
+/// Deterministic pseudo random numbers of a 64 bit linear congruential generator, so failures
+/// of randomized tests are reproducible.
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Lcg(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+
+        self.0
+    }
+}
+
+/// Sphere centered at origin made by subdividing icosahedron, every subdivision splits each
+/// triangle into four. Produces `20 * 4^subdivisions` triangles. Vertices shared by triangles
+/// are exactly equal, so the mesh is watertight.
+pub fn generate_sphere(radius: f32, subdivisions: u32) -> Vec<Triangle> {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+
+    let mut vertices: Vec<Vector3<f32>> = vec![
+        Vector3::new(-1.0, t, 0.0),
+        Vector3::new(1.0, t, 0.0),
+        Vector3::new(-1.0, -t, 0.0),
+        Vector3::new(1.0, -t, 0.0),
+        Vector3::new(0.0, -1.0, t),
+        Vector3::new(0.0, 1.0, t),
+        Vector3::new(0.0, -1.0, -t),
+        Vector3::new(0.0, 1.0, -t),
+        Vector3::new(t, 0.0, -1.0),
+        Vector3::new(t, 0.0, 1.0),
+        Vector3::new(-t, 0.0, -1.0),
+        Vector3::new(-t, 0.0, 1.0),
+    ];
+
+    for vertex in vertices.iter_mut() {
+        *vertex = vertex.normalize();
+    }
+
+    let mut faces: Vec<[usize; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Midpoint of every edge is created once, so neighbour triangles share it.
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+
+        let mut midpoint = |a: usize, b: usize, vertices: &mut Vec<Vector3<f32>>| {
+            let key = (a.min(b), a.max(b));
+
+            *midpoints.entry(key).or_insert_with(|| {
+                vertices.push(((vertices[a] + vertices[b]) / 2.0).normalize());
+                vertices.len() - 1
+            })
+        };
+
+        let mut subdivided = Vec::with_capacity(faces.len() * 4);
+
+        for [a, b, c] in faces {
+            let ab = midpoint(a, b, &mut vertices);
+            let bc = midpoint(b, c, &mut vertices);
+            let ca = midpoint(c, a, &mut vertices);
+
+            subdivided.push([a, ab, ca]);
+            subdivided.push([b, bc, ab]);
+            subdivided.push([c, ca, bc]);
+            subdivided.push([ab, bc, ca]);
+        }
+
+        faces = subdivided;
+    }
+
+    faces
+        .iter()
+        .map(|&[a, b, c]| {
+            Triangle::new(
+                vertices[a] * radius,
+                vertices[b] * radius,
+                vertices[c] * radius,
+            )
+        })
+        .collect()
+}
+
+/// Flat square plane at z = 0 made of `n * n` unit quads, split into two triangles each.
+/// Plane is centered at origin.
+pub fn generate_grid_plane(n: u32) -> Vec<Triangle> {
+    let offset = n as f32 / 2.0;
+    let vertex = |x: u32, y: u32| Vector3::new(x as f32 - offset, y as f32 - offset, 0.0);
+
+    let mut triangles = Vec::with_capacity(n as usize * n as usize * 2);
+
+    for x in 0..n {
+        for y in 0..n {
+            let a = vertex(x, y);
+            let b = vertex(x + 1, y);
+            let c = vertex(x + 1, y + 1);
+            let d = vertex(x, y + 1);
+
+            triangles.push(Triangle::new(a, b, c));
+            triangles.push(Triangle::new(a, c, d));
+        }
+    }
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geometry::Triangle;
+    use crate::testutil::{generate_grid_plane, generate_sphere};
+    use std::collections::HashMap;
+
+    /// Counts how many triangles share every edge, vertices are compared by exact bits.
+    fn edge_counts(triangles: &[Triangle]) -> HashMap<([u32; 3], [u32; 3]), usize> {
+        let mut counts = HashMap::new();
+
+        for triangle in triangles {
+            let vertices = [triangle.a, triangle.b, triangle.c];
+
+            for index in 0..3 {
+                let start = vertices[index];
+                let end = vertices[(index + 1) % 3];
+
+                let start = [start.x.to_bits(), start.y.to_bits(), start.z.to_bits()];
+                let end = [end.x.to_bits(), end.y.to_bits(), end.z.to_bits()];
+
+                *counts.entry((start.min(end), start.max(end))).or_insert(0) += 1;
+            }
+        }
+
+        counts
+    }
+
+    #[test]
+    fn test_generate_sphere() {
+        let triangles = generate_sphere(10.0, 2);
+
+        assert_eq!(triangles.len(), 320);
+        assert!(triangles.iter().all(|t| t.normal().norm() > 0.0));
+        assert!(edge_counts(&triangles).values().all(|&count| count == 2));
+
+        for triangle in &triangles {
+            for vertex in &[triangle.a, triangle.b, triangle.c] {
+                assert!((vertex.norm() - 10.0).abs() < 1e-4);
+            }
+
+            // Normals point outside of the sphere.
+            assert!(triangle.normal().dot(&triangle.a) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_generate_grid_plane() {
+        let triangles = generate_grid_plane(4);
+
+        assert_eq!(triangles.len(), 32);
+        assert!(triangles.iter().all(|t| t.normal().norm() > 0.0));
+
+        // Only edges on the border of the plane belong to a single triangle.
+        let counts = edge_counts(&triangles);
+        let border_edges = counts.values().filter(|&&count| count == 1).count();
+
+        assert_eq!(border_edges, 16);
+        assert!(counts.values().all(|&count| count <= 2));
+    }
+}